@@ -0,0 +1,106 @@
+use crate::config::processing::set_key;
+use clap::Parser;
+use serde_yaml::{Mapping, Value};
+use std::path::Path;
+
+// Everything given here is laid over config.yaml, so runs with different settings
+// don't need different config files.
+#[derive(Parser, Debug, Clone)]
+#[clap(
+    name = "publicflow",
+    about = "Simulates people and pods in a public transport network"
+)]
+pub struct Args {
+    #[clap(
+        long,
+        default_value = "./config/config.yaml",
+        help = "The root config, towns are looked up in the folder it is in"
+    )]
+    pub config: String,
+    #[clap(long, help = "Overrides general.town")]
+    pub town: Option<String>,
+    #[clap(
+        long,
+        help = "Overrides general.environment, the subfolder of metrics/"
+    )]
+    pub environment: Option<String>,
+    #[clap(long, value_parser = ["visual", "headless", "import", "sweep"], help = "Overrides general.mode")]
+    pub mode: Option<String>,
+    #[clap(long, help = "Overrides logic.seed")]
+    pub seed: Option<u64>,
+    #[clap(
+        long = "set",
+        value_name = "KEY=VALUE",
+        help = "Overrides any key of config.yaml, e.g. --set logic.pod_capacity=120, can be repeated"
+    )]
+    pub sets: Vec<String>,
+    #[clap(
+        long,
+        help = "A script to run on start instead of general.command_on_start"
+    )]
+    pub script: Option<String>,
+}
+
+impl Args {
+    // With a trailing slash, so the names of towns and files can be appended
+    pub fn get_config_root(&self) -> String {
+        match Path::new(&self.config).parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => format!("{}/", parent.display()),
+            _ => String::from("./"),
+        }
+    }
+
+    pub fn get_config_name(&self) -> String {
+        match Path::new(&self.config).file_name() {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => self.config.clone(),
+        }
+    }
+
+    // The flags as a yaml shaped like config.yaml. The later --set wins if a key is
+    // given more than once.
+    pub fn to_overrides(&self) -> Result<Value, String> {
+        let mut overrides = Value::Mapping(Mapping::new());
+        if let Some(town) = &self.town {
+            set_key(&mut overrides, "general.town", Value::String(town.clone()));
+        }
+        if let Some(environment) = &self.environment {
+            set_key(
+                &mut overrides,
+                "general.environment",
+                Value::String(environment.clone()),
+            );
+        }
+        if let Some(mode) = &self.mode {
+            set_key(&mut overrides, "general.mode", Value::String(mode.clone()));
+        }
+        if let Some(seed) = self.seed {
+            set_key(&mut overrides, "logic.seed", Value::from(seed));
+        }
+        if let Some(script) = &self.script {
+            set_key(
+                &mut overrides,
+                "general.command_on_start",
+                Value::String(format!("run {}", script)),
+            );
+        }
+        for set in &self.sets {
+            let (key_path, raw_value) = match set.split_once('=') {
+                Some((key_path, raw_value)) if !key_path.trim().is_empty() => {
+                    (key_path.trim(), raw_value.trim())
+                }
+                _ => return Err(format!("--set {} is not formatted as key=value", set)),
+            };
+            // Read like a value in the yaml, so 120 is a number and true a bool. Nothing
+            // after the = is an empty text.
+            let value = match serde_yaml::from_str::<Value>(raw_value) {
+                _ if raw_value.is_empty() => Value::String(String::new()),
+                Ok(Value::Null) => Value::String(raw_value.to_string()),
+                Ok(value) => value,
+                Err(e) => return Err(format!("--set {}: {}", set, e)),
+            };
+            set_key(&mut overrides, key_path, value);
+        }
+        Ok(overrides)
+    }
+}
@@ -1,8 +1,15 @@
 pub const _SPEED_FACTOR: u64 = 1000;
 pub const _SIMULATION_DURATION: u64 = 1000;
 
-pub const CONFIG_ROOT: &str = "./config/";
-pub const CONFIG_NAME: &str = "config.yaml";
+// Seconds a pod needs per stop on top of travel and station time: one each for
+// arriving, switching from JustArrived to InStation and departing
+pub const POD_SECONDS_PER_STOP: u32 = 3;
+
+// Where the overrides given as arguments come from in the reports of the validator
+pub const COMMAND_LINE: &str = "command line";
 pub const GENERAL_CONFIG_NAME: &str = "general.yaml";
 pub const STATIONS_CONFIG_NAME: &str = "stations.yaml";
 pub const LINES_CONFIG_NAME: &str = "lines.yaml";
+pub const DEMAND_CONFIG_NAME: &str = "demand.yaml";
+pub const DEMAND_CSV_NAME: &str = "demand.csv";
+pub const TIMETABLE_CONFIG_NAME: &str = "timetable.yaml";
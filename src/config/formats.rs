@@ -0,0 +1,96 @@
+// The yamls write some values differently than the structs hold them, these modules
+// translate between the two for serde's `with` and `deserialize_with`.
+use crate::helper::functions::parse_time_of_day;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+#[derive(Serialize, Deserialize)]
+struct Xy {
+    x: f32,
+    y: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MinMax {
+    min: f32,
+    max: f32,
+}
+
+// (x, y) as {x: 1920.0, y: 1150.0}
+pub mod xy {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &(f32, f32), serializer: S) -> Result<S::Ok, S::Error> {
+        Xy {
+            x: value.0,
+            y: value.1,
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<(f32, f32), D::Error> {
+        let xy = Xy::deserialize(deserializer)?;
+        Ok((xy.x, xy.y))
+    }
+}
+
+// (min, max) as {min: 0.0, max: 11.0}
+pub mod min_max {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(value: &(f32, f32), serializer: S) -> Result<S::Ok, S::Error> {
+        MinMax {
+            min: value.0,
+            max: value.1,
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<(f32, f32), D::Error> {
+        let min_max = MinMax::deserialize(deserializer)?;
+        Ok((min_max.min, min_max.max))
+    }
+}
+
+// Seconds since midnight as "HH:MM:SS", "HH:MM" is read as well
+pub mod time_of_day {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(seconds: &u32, serializer: S) -> Result<S::Ok, S::Error> {
+        let time = format!(
+            "{:02}:{:02}:{:02}",
+            seconds / 3600,
+            seconds % 3600 / 60,
+            seconds % 60
+        );
+        serializer.serialize_str(&time)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+        let time = String::deserialize(deserializer)?;
+        match parse_time_of_day(&time) {
+            Some(seconds) => Ok(seconds),
+            None => Err(D::Error::custom(format!(
+                "{} is not formatted as HH:MM(:SS)",
+                time
+            ))),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TextOrInteger {
+    Text(String),
+    Integer(i64),
+}
+
+// Ids like the ones of timetable trips may be written with or without quotes
+pub fn deserialize_text_or_integer<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<String, D::Error> {
+    match TextOrInteger::deserialize(deserializer)? {
+        TextOrInteger::Text(text) => Ok(text),
+        TextOrInteger::Integer(integer) => Ok(integer.to_string()),
+    }
+}
@@ -1,3 +1,6 @@
+pub mod args;
 pub mod constants;
+pub mod formats;
 pub mod processing;
 pub mod structs;
+pub mod validation;
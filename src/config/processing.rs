@@ -1,421 +1,460 @@
 use crate::config::constants::{
-    CONFIG_ROOT, GENERAL_CONFIG_NAME, LINES_CONFIG_NAME, STATIONS_CONFIG_NAME,
+    COMMAND_LINE, DEMAND_CONFIG_NAME, DEMAND_CSV_NAME, GENERAL_CONFIG_NAME, LINES_CONFIG_NAME,
+    STATIONS_CONFIG_NAME, TIMETABLE_CONFIG_NAME,
 };
-use crate::config::structs::{Config, ExecutionMode, LogicConfig, NetworkConfig, VisualConfig};
+use crate::config::structs::{
+    Config, DemandConfig, DemandFile, DemandProfile, ExecutionMode, GeneralConfig, ImportConfig,
+    LineConfig, LogicConfig, NetworkConfig, OdMatrix, OdTripFile, StationConfig, SweepConfig,
+    TimetableConfig, TimetableLine, TimetableLineFile, TimetableTrip, VisualConfig,
+};
+use crate::config::validation::ConfigValidator;
 use crate::connection::Connection;
 use crate::helper::enums::LineName;
-use crate::helper::functions::transform_line_name_to_enum;
+use crate::helper::functions::{parse_str_to_line_and_directions, read_lines};
 use crate::line::line::Line;
+use serde::de::DeserializeOwned;
+use serde_yaml::{Mapping, Value};
 use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::prelude::*;
-use yaml_rust::yaml::Yaml;
-use yaml_rust::YamlLoader;
+use std::path::Path;
+use std::process::exit;
+use std::str::FromStr;
 
-pub fn load_yaml(config_root: &str, config_name: &str) -> Yaml {
+pub fn load_yaml(config_root: &str, config_name: &str) -> Value {
     let config_path = format!("{}{}", config_root, config_name);
-    let mut file = File::open(config_path).expect("Unable to open file");
+    let mut file = File::open(&config_path).expect("Unable to open file");
     let mut contents = String::new();
 
     file.read_to_string(&mut contents)
         .expect("Unable to read file");
 
-    let docs = YamlLoader::load_from_str(&contents).unwrap();
-    return docs[0].clone();
+    match serde_yaml::from_str(&contents) {
+        Ok(yaml) => yaml,
+        Err(e) => {
+            println!("{} is no valid yaml: {}", config_path, e);
+            exit(1);
+        }
+    }
 }
 
-pub fn parse_or_override_visual_config(raw_config: &Yaml, visual_config: &mut VisualConfig) {
-    if let Yaml::Hash(hash) = raw_config {
-        if let Some(yaml) = hash.get(&Yaml::String(String::from("visual"))) {
-            if let Yaml::Hash(hash) = yaml {
-                if let Some(yaml) = hash.get(&Yaml::String(String::from("screen_size"))) {
-                    if let Yaml::Hash(hash) = yaml {
-                        if let Some(yaml) = hash.get(&Yaml::String(String::from("x"))) {
-                            if let Some(float) = yaml.as_f64() {
-                                visual_config.screen_size.0 = float as f32;
-                            }
-                        }
-                        if let Some(yaml) = hash.get(&Yaml::String(String::from("y"))) {
-                            if let Some(float) = yaml.as_f64() {
-                                visual_config.screen_size.1 = float as f32;
-                            }
-                        }
-                    }
-                }
-                if let Some(yaml) = hash.get(&Yaml::String(String::from("latitude"))) {
-                    if let Yaml::Hash(hash) = yaml {
-                        if let Some(yaml) = hash.get(&Yaml::String(String::from("min"))) {
-                            if let Some(float) = yaml.as_f64() {
-                                visual_config.latitude_range_bounds.0 = float as f32;
-                                visual_config.latitude_range_current.0 = float as f32;
-                            }
-                        }
-                        if let Some(yaml) = hash.get(&Yaml::String(String::from("max"))) {
-                            if let Some(float) = yaml.as_f64() {
-                                visual_config.latitude_range_bounds.1 = float as f32;
-                                visual_config.latitude_range_current.1 = float as f32;
-                            }
-                        }
-                    }
-                }
-                if let Some(yaml) = hash.get(&Yaml::String(String::from("longitude"))) {
-                    if let Yaml::Hash(hash) = yaml {
-                        if let Some(yaml) = hash.get(&Yaml::String(String::from("min"))) {
-                            if let Some(float) = yaml.as_f64() {
-                                visual_config.longitude_range_bounds.0 = float as f32;
-                                visual_config.longitude_range_current.0 = float as f32;
-                            }
-                        }
-                        if let Some(yaml) = hash.get(&Yaml::String(String::from("max"))) {
-                            if let Some(float) = yaml.as_f64() {
-                                visual_config.longitude_range_bounds.1 = float as f32;
-                                visual_config.longitude_range_current.1 = float as f32;
-                            }
-                        }
-                    }
-                }
-                if let Some(yaml) = hash.get(&Yaml::String(String::from("screen_offset"))) {
-                    if let Some(float) = yaml.as_f64() {
-                        visual_config.screen_offset = float as f32;
-                    }
-                }
-                if let Some(yaml) = hash.get(&Yaml::String(String::from("radius_station"))) {
-                    if let Some(float) = yaml.as_f64() {
-                        visual_config.radius_station = float as f32;
-                    }
-                }
-                if let Some(yaml) = hash.get(&Yaml::String(String::from("radius_pod"))) {
-                    if let Some(float) = yaml.as_f64() {
-                        visual_config.radius_pod = float as f32;
-                    }
-                }
-                if let Some(yaml) = hash.get(&Yaml::String(String::from("width_line"))) {
-                    if let Some(float) = yaml.as_f64() {
-                        visual_config.width_line = float as f32;
-                    }
-                }
-                if let Some(yaml) = hash.get(&Yaml::String(String::from("vsync"))) {
-                    if let Yaml::Boolean(value) = yaml {
-                        visual_config.vsync = *value;
+// Lays one yaml over another. Maps are merged key by key, everything else in the
+// layer replaces what is below it.
+pub fn merge_yaml(base: &mut Value, layer: &Value) {
+    match (base, layer) {
+        (_, Value::Null) => {}
+        (Value::Mapping(base_map), Value::Mapping(layer_map)) => {
+            for (key, value) in layer_map {
+                match base_map.get_mut(key) {
+                    Some(base_value) => merge_yaml(base_value, value),
+                    None => {
+                        base_map.insert(key.clone(), value.clone());
                     }
                 }
             }
         }
+        (base, layer) => *base = layer.clone(),
     }
 }
 
-pub fn parse_or_override_logic_config(raw_config: &Yaml, logic_config: &mut LogicConfig) {
-    if let Yaml::Hash(hash) = raw_config {
-        if let Some(yaml) = hash.get(&Yaml::String(String::from("logic"))) {
-            if let Yaml::Hash(hash) = yaml {
-                if let Some(yaml) = hash.get(&Yaml::String(String::from("use_earth_coordinates"))) {
-                    if let Yaml::Boolean(value) = yaml {
-                        logic_config.use_earth_coordinates = *value;
-                    }
-                }
-                if let Some(yaml) = hash.get(&Yaml::String(String::from("distance_factor"))) {
-                    if let Yaml::Integer(value) = yaml {
-                        logic_config.distance_factor = *value as i32;
-                    }
-                }
-                if let Some(yaml) = hash.get(&Yaml::String(String::from("number_of_people"))) {
-                    if let Yaml::Integer(value) = yaml {
-                        logic_config.number_of_people = *value as i32;
-                    }
-                }
-                if let Some(yaml) = hash.get(&Yaml::String(String::from("pod_capacity"))) {
-                    if let Yaml::Integer(value) = yaml {
-                        logic_config.pod_capacity = *value as i32;
-                    }
-                }
-                if let Some(yaml) = hash.get(&Yaml::String(String::from("transition_time"))) {
-                    if let Yaml::Integer(value) = yaml {
-                        logic_config.transition_time = *value as i32;
-                    }
-                }
-                if let Some(yaml) = hash.get(&Yaml::String(String::from("pod_in_station_seconds")))
-                {
-                    if let Yaml::Integer(value) = yaml {
-                        logic_config.pod_in_station_seconds = *value as i32;
-                    }
-                }
-                if let Some(yaml) = hash.get(&Yaml::String(String::from("line_pods_per_hour"))) {
-                    if let Yaml::Integer(value) = yaml {
-                        logic_config.line_pods_per_hour = *value as i32;
-                    }
-                }
-                if let Some(yaml) = hash.get(&Yaml::String(String::from("station_pods_per_hour"))) {
-                    if let Yaml::Integer(value) = yaml {
-                        logic_config.station_pods_per_hour = *value as i32;
-                    }
-                }
-                if let Some(yaml) = hash.get(&Yaml::String(String::from("shuffle_people"))) {
-                    if let Yaml::Boolean(value) = yaml {
-                        logic_config.shuffle_people = *value;
-                    }
-                }
-                if let Some(yaml) = hash.get(&Yaml::String(String::from("speed_multiplier"))) {
-                    if let Yaml::Integer(value) = yaml {
-                        logic_config.speed_multiplier = *value as u32;
-                    }
-                }
-            }
-        }
+// logic.pod_capacity and 120 becomes {logic: {pod_capacity: 120}}
+pub fn set_key(overrides: &mut Value, key_path: &str, value: Value) {
+    let mut layer = value;
+    for key in key_path.rsplit('.') {
+        let mut mapping = Mapping::new();
+        mapping.insert(Value::String(key.to_string()), layer);
+        layer = Value::Mapping(mapping);
     }
+    merge_yaml(overrides, &layer);
 }
 
-// This function essentially parses the raw Yaml typed structure we get into the more
-// usable Config structure from above.
-// It only respects correctly formatted yamls.
-// TODO: introduce a validator that panics if yaml is incorrectly formatted.
-pub fn parse_config(raw_config: &Yaml) -> Config {
-    let mut town = String::default();
-    let mut command_on_start = String::default();
-    let mut environment = String::from("dev");
-    let mut mode = ExecutionMode::Visual;
-    let mut overide_general = false;
-
-    if let Yaml::Hash(hash) = raw_config {
-        if let Some(yaml) = hash.get(&Yaml::String(String::from("general"))) {
-            if let Yaml::Hash(hash) = yaml {
-                if let Some(yaml) = hash.get(&Yaml::String(String::from("town"))) {
-                    if let Yaml::String(value) = yaml {
-                        town = value.to_string();
-                    }
-                }
-                if let Some(yaml) = hash.get(&Yaml::String(String::from("command_on_start"))) {
-                    if let Yaml::String(value) = yaml {
-                        command_on_start = value.to_string();
-                    }
-                }
-                if let Some(yaml) = hash.get(&Yaml::String(String::from("override"))) {
-                    if let Yaml::Boolean(value) = yaml {
-                        overide_general = *value;
-                    }
-                }
-                if let Some(yaml) = hash.get(&Yaml::String(String::from("mode"))) {
-                    if let Yaml::String(value) = yaml {
-                        match value.as_str() {
-                            "headless" => mode = ExecutionMode::Headless,
-                            "visual" => mode = ExecutionMode::Visual,
-                            other => {
-                                panic!("Execution Mode {} invalid", other);
-                            }
-                        }
-                    }
-                }
-                if let Some(yaml) = hash.get(&Yaml::String(String::from("environment"))) {
-                    if let Yaml::String(value) = yaml {
-                        environment = value.to_string();
-                    }
-                }
+// A missing or empty section keeps the defaults of the struct
+fn parse_section<T: DeserializeOwned + Default>(
+    raw: &Value,
+    key: &str,
+    file: &str,
+    validator: &mut ConfigValidator,
+) -> T {
+    match raw.get(key) {
+        None | Some(Value::Null) => T::default(),
+        Some(section) => match serde_yaml::from_value(section.clone()) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                validator.push(file, key, e.to_string());
+                T::default()
             }
+        },
+    }
+}
+
+fn parse_file<T: DeserializeOwned + Default>(
+    raw: &Value,
+    file: &str,
+    validator: &mut ConfigValidator,
+) -> T {
+    match serde_yaml::from_value(raw.clone()) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            validator.push(file, "", e.to_string());
+            T::default()
         }
     }
+}
+
+// Only returns something if the mode is import. Importing happens before the config of
+// the town is loaded, since the town is the one that gets written.
+pub fn parse_import_config(
+    config_file: &str,
+    raw_config: &Value,
+    raw_overrides: &Value,
+) -> Option<ImportConfig> {
+    let mut validator = ConfigValidator::new();
+    let mut raw_root = raw_config.clone();
+    merge_yaml(&mut raw_root, raw_overrides);
+    let general: GeneralConfig = parse_section(&raw_root, "general", config_file, &mut validator);
+    if !matches!(general.mode, ExecutionMode::Import) {
+        return None;
+    }
 
-    let town_specific_config_root_path = format!("{}{}/", CONFIG_ROOT, town);
+    validator.validate_config(config_file, raw_config);
+    validator.validate_config(COMMAND_LINE, raw_overrides);
+    validator.exit_if_invalid();
+    let import_config = parse_section(&raw_root, "import", config_file, &mut validator);
+    validator.exit_if_invalid();
+    Some(import_config)
+}
+
+// Like the import the sweep is only read in its mode. The values of the runs are
+// checked against config.yaml here, so a typo doesn't stop the sweep halfway.
+pub fn parse_sweep_config(
+    config_file: &str,
+    raw_config: &Value,
+    raw_overrides: &Value,
+) -> Option<SweepConfig> {
+    let mut validator = ConfigValidator::new();
+    let mut raw_root = raw_config.clone();
+    merge_yaml(&mut raw_root, raw_overrides);
+    let general: GeneralConfig = parse_section(&raw_root, "general", config_file, &mut validator);
+    if !matches!(general.mode, ExecutionMode::Sweep) {
+        return None;
+    }
+
+    validator.validate_config(config_file, raw_config);
+    validator.validate_config(COMMAND_LINE, raw_overrides);
+    validator.exit_if_invalid();
+    let sweep_config = parse_section(&raw_root, "sweep", config_file, &mut validator);
+    validator.exit_if_invalid();
+    Some(sweep_config)
+}
+
+// Deserializes the config into the Config structure from above. The visual and logic
+// values are layered: the town's general.yaml, then config.yaml if it overrides the
+// town and then the overrides from the command line.
+// The yamls are validated first and the run stops with a report of all problems if
+// they aren't fine.
+pub fn parse_config(
+    config_root: &str,
+    config_name: &str,
+    raw_config: &Value,
+    raw_overrides: &Value,
+) -> Config {
+    let config_file = format!("{}{}", config_root, config_name);
+    let mut validator = ConfigValidator::new();
+    validator.validate_config(&config_file, raw_config);
+    validator.validate_config(COMMAND_LINE, raw_overrides);
+
+    let mut raw_root = raw_config.clone();
+    merge_yaml(&mut raw_root, raw_overrides);
+    let general: GeneralConfig = parse_section(&raw_root, "general", &config_file, &mut validator);
+
+    let town_specific_config_root_path = format!("{}{}/", config_root, general.town);
+    let town_file = match raw_overrides.get("general").and_then(|g| g.get("town")) {
+        Some(_) => COMMAND_LINE,
+        None => &config_file,
+    };
+    let mut is_town_complete = true;
+    for name in [GENERAL_CONFIG_NAME, LINES_CONFIG_NAME, STATIONS_CONFIG_NAME] {
+        let path = format!("{}{}", town_specific_config_root_path, name);
+        if !Path::new(&path).exists() {
+            validator.push(town_file, "general.town", format!("{} is missing", path));
+            is_town_complete = false;
+        }
+    }
+    // Without the files of the town there is nothing more to check
+    if !is_town_complete {
+        validator.exit_if_invalid();
+    }
 
     let raw_general = load_yaml(&town_specific_config_root_path, GENERAL_CONFIG_NAME);
     let raw_lines = load_yaml(&town_specific_config_root_path, LINES_CONFIG_NAME);
     let raw_stations = load_yaml(&town_specific_config_root_path, STATIONS_CONFIG_NAME);
 
-    let (network_config, number_of_pods) = gen_network_config(&raw_stations, &raw_lines);
-
-    // println!("{:?}", network_config);
-
-    let mut logic_config = LogicConfig {
-        use_earth_coordinates: false,
-        distance_factor: 1000,
-        command_on_start: command_on_start,
-        number_of_people: i32::default(),
-        number_of_pods: number_of_pods,
-        pod_capacity: i32::default(),
-        transition_time: i32::default(),
-        pod_in_station_seconds: i32::default(),
-        line_pods_per_hour: i32::default(),
-        station_pods_per_hour: i32::default(),
-        shuffle_people: false,
-        on_pause: false,
-        speed_multiplier: 1,
-    };
+    let general_file = format!("{}{}", town_specific_config_root_path, GENERAL_CONFIG_NAME);
+    let stations_file = format!("{}{}", town_specific_config_root_path, STATIONS_CONFIG_NAME);
+    let lines_file = format!("{}{}", town_specific_config_root_path, LINES_CONFIG_NAME);
+    validator.validate_town_general(&general_file, &raw_general);
 
-    parse_or_override_logic_config(&raw_general, &mut logic_config);
-    // println!("{:?}", logic_config);
-    if overide_general {
-        parse_or_override_logic_config(&raw_config, &mut logic_config);
-    }
-    // println!("{:?}", logic_config);
-
-    let mut visual_config = VisualConfig {
-        screen_size: <(f32, f32)>::default(),
-        latitude_range_bounds: <(f32, f32)>::default(),
-        longitude_range_bounds: <(f32, f32)>::default(),
-        latitude_range_current: <(f32, f32)>::default(),
-        longitude_range_current: <(f32, f32)>::default(),
-        screen_offset: f32::default(),
-        radius_station: f32::default(),
-        radius_pod: f32::default(),
-        width_line: f32::default(),
-        vsync: bool::default(),
-        last_mouse: (0., 0.),
-        last_mouse_while_zooming_relative: (0., 0.),
-        last_mouse_left: (0., 0.),
-    };
-
-    parse_or_override_visual_config(&raw_general, &mut visual_config);
-    // println!("{:?}", visual_config);
-    if overide_general {
-        parse_or_override_visual_config(&raw_config, &mut visual_config);
+    let mut raw_layered = raw_general.clone();
+    if general.override_town {
+        merge_yaml(&mut raw_layered, raw_config);
     }
-    // println!("{:?}", visual_config);
+    merge_yaml(&mut raw_layered, raw_overrides);
+
+    validator.validate_required_logic(&general_file, &raw_layered);
+    validator.validate_network(&stations_file, &raw_stations, &lines_file, &raw_lines);
+    validator.exit_if_invalid();
+
+    let mut logic_config: LogicConfig =
+        parse_section(&raw_layered, "logic", &general_file, &mut validator);
+    let mut visual_config: VisualConfig =
+        parse_section(&raw_layered, "visual", &general_file, &mut validator);
+    visual_config.latitude_range_current = visual_config.latitude_range_bounds;
+    visual_config.longitude_range_current = visual_config.longitude_range_bounds;
+
+    let station_configs: Vec<StationConfig> =
+        parse_file(&raw_stations, &stations_file, &mut validator);
+    let line_configs: Vec<LineConfig> = parse_file(&raw_lines, &lines_file, &mut validator);
+    validator.exit_if_invalid();
+
+    let (network_config, number_of_pods) = build_network_config(&station_configs, &line_configs);
+    logic_config.number_of_pods = number_of_pods;
+
+    let demand_config = load_demand_config(
+        &town_specific_config_root_path,
+        &network_config,
+        &mut validator,
+    );
+    let timetable_config = load_timetable_config(
+        &town_specific_config_root_path,
+        &network_config,
+        &mut validator,
+    );
+    validator.exit_if_invalid();
 
     Config {
         timestamp_run: None,
-        mode: mode,
-        environment: environment,
+        output_dir: None,
+        general: general,
         network: network_config,
         logic: logic_config,
         visual: visual_config,
+        demand: demand_config,
+        timetable: timetable_config,
     }
 }
 
-pub fn gen_network_config(raw_stations: &Yaml, raw_lines: &Yaml) -> (NetworkConfig, i32) {
-    let mut n_stations: i64 = 0;
-    let mut coordinates_map_stations: HashMap<i32, (String, Vec<String>, String, (f32, f32))> =
-        HashMap::new();
-    let mut station_platforms: HashMap<i32, Vec<(HashSet<i32>, HashSet<LineName>)>> =
-        HashMap::new();
-    let mut lines: Vec<Line> = vec![];
-    let mut edge_map: HashMap<i32, HashSet<i32>> = HashMap::new();
-
-    if let Yaml::Array(stations_array) = raw_stations {
-        n_stations = stations_array.len() as i64;
-        for station_yaml in stations_array {
-            if let Yaml::Hash(station_hash) = station_yaml {
-                let mut city: String = String::from("placeholder");
-                let mut id: i32 = -1;
-                let mut lat: f32 = -1.0;
-                let mut lon: f32 = -1.0;
-                let mut name: String = String::from("placeholder");
-                let mut entrypoint_for: Vec<String> = vec![];
-
-                if let Some(city_yaml) = station_hash.get(&Yaml::String(String::from("city"))) {
-                    // TODO finish this
-                    if let Yaml::String(city_string) = city_yaml {
-                        city = city_string.clone();
-                    }
-                }
+// The timetable is optional, without one pods are only started from scripts.
+// timetable.yaml:
+//   - line: "u1+"
+//     trips:
+//       - id: "u1-001"
+//         departure: "06:00:00"
+pub fn load_timetable_config(
+    config_root: &str,
+    network_config: &NetworkConfig,
+    validator: &mut ConfigValidator,
+) -> Option<TimetableConfig> {
+    let yaml_path = format!("{}{}", config_root, TIMETABLE_CONFIG_NAME);
+    if !Path::new(&yaml_path).exists() {
+        return None;
+    }
+    let raw_timetable = load_yaml(config_root, TIMETABLE_CONFIG_NAME);
+    validator.validate_timetable(&yaml_path, &raw_timetable, network_config);
+    if !validator.is_valid() {
+        return None;
+    }
+    let timetable_file: Vec<TimetableLineFile> = parse_file(&raw_timetable, &yaml_path, validator);
+    if !validator.is_valid() {
+        return None;
+    }
+    Some(gen_timetable_config(&timetable_file))
+}
 
-                if let Some(id_yaml) = station_hash.get(&Yaml::String(String::from("id"))) {
-                    // TODO finish this
-                    if let Yaml::Integer(id_int) = id_yaml {
-                        id = *id_int as i32;
-                    }
-                }
+// Lines and directions were validated, each line has exactly one direction
+pub fn gen_timetable_config(timetable_file: &Vec<TimetableLineFile>) -> TimetableConfig {
+    let mut timetable_lines: Vec<TimetableLine> = vec![];
+
+    for timetable_line_file in timetable_file {
+        let line_and_direction = &timetable_line_file.line;
+        let (line_name, directions) = parse_str_to_line_and_directions(line_and_direction);
+        let trips = timetable_line_file
+            .trips
+            .iter()
+            .map(|trip| TimetableTrip {
+                id: trip.id.clone(),
+                departure: trip.departure,
+            })
+            .collect();
+        timetable_lines.push(TimetableLine {
+            line_name: line_name,
+            direction: directions[0].clone(),
+            trips: trips,
+        });
+    }
 
-                if let Some(lat_yaml) = station_hash.get(&Yaml::String(String::from("lat"))) {
-                    // TODO finish this
-                    if let Some(lat_float) = lat_yaml.as_f64() {
-                        lat = lat_float as f32;
-                    }
-                }
+    TimetableConfig::new(timetable_lines)
+}
 
-                if let Some(lon_yaml) = station_hash.get(&Yaml::String(String::from("lon"))) {
-                    // TODO finish this
-                    if let Some(lon_float) = lon_yaml.as_f64() {
-                        lon = lon_float as f32;
-                    }
-                }
+// The demand is optional, towns without a demand.yaml or demand.csv keep the uniform random trips.
+// demand.yaml:
+//   od: [{from: 11, to: 25, weight: 3.5}, ...]
+//   profiles: [{name: "morning", from: "06:00", to: "09:00", od: [...]}, ...]
+//   hourly_trips: [20, 10, ..., 40] # 24 values, trips starting in each hour of the day
+// demand.csv:
+//   from,to,weight
+//   11,25,3.5
+pub fn load_demand_config(
+    config_root: &str,
+    network_config: &NetworkConfig,
+    validator: &mut ConfigValidator,
+) -> Option<DemandConfig> {
+    let yaml_path = format!("{}{}", config_root, DEMAND_CONFIG_NAME);
+    let csv_path = format!("{}{}", config_root, DEMAND_CSV_NAME);
+    if Path::new(&yaml_path).exists() {
+        let raw_demand = load_yaml(config_root, DEMAND_CONFIG_NAME);
+        validator.validate_demand(&yaml_path, &raw_demand, network_config);
+        if !validator.is_valid() {
+            return None;
+        }
+        let demand_file: DemandFile = parse_file(&raw_demand, &yaml_path, validator);
+        if !validator.is_valid() {
+            return None;
+        }
+        return Some(gen_demand_config(&demand_file));
+    }
+    if Path::new(&csv_path).exists() {
+        return Some(DemandConfig {
+            od: gen_od_matrix_from_csv(&csv_path, network_config, validator),
+            profiles: vec![],
+            hourly_trips: None,
+        });
+    }
+    None
+}
 
-                if let Some(name_yaml) = station_hash.get(&Yaml::String(String::from("name"))) {
-                    // TODO finish this
-                    if let Yaml::String(name_string) = name_yaml {
-                        name = name_string.clone();
-                    }
-                }
+// Station ids and hourly_trips were validated against the network
+pub fn gen_demand_config(demand_file: &DemandFile) -> DemandConfig {
+    let profiles = demand_file
+        .profiles
+        .iter()
+        .map(|profile| DemandProfile {
+            name: profile.name.clone(),
+            from: profile.from,
+            to: profile.to,
+            od: gen_od_matrix(&profile.od),
+        })
+        .collect();
+
+    DemandConfig {
+        od: gen_od_matrix(&demand_file.od),
+        profiles: profiles,
+        hourly_trips: demand_file.hourly_trips.clone(),
+    }
+}
 
-                if let Some(entrypoint_for_yaml) =
-                    station_hash.get(&Yaml::String(String::from("entrypoint_for")))
-                {
-                    // TODO finish this
-                    if let Yaml::Array(entrypoint_for_array) = entrypoint_for_yaml {
-                        for line_name in entrypoint_for_array {
-                            if let Yaml::String(line_name_string) = line_name {
-                                entrypoint_for.push(line_name_string.clone());
-                            }
-                        }
-                    }
-                }
+fn gen_od_matrix(od_trips: &Vec<OdTripFile>) -> OdMatrix {
+    let mut trips: Vec<(i32, i32, f64)> = vec![];
+    for od_trip in od_trips {
+        push_od_trip(&mut trips, od_trip.from, od_trip.to, od_trip.weight);
+    }
+    OdMatrix::new(trips)
+}
 
-                coordinates_map_stations.insert(id, (name, entrypoint_for, city, (lat, lon)));
+// The csv has no keys to check, unknown stations are reported with the line they are in
+fn gen_od_matrix_from_csv(
+    csv_path: &str,
+    network_config: &NetworkConfig,
+    validator: &mut ConfigValidator,
+) -> OdMatrix {
+    let stations = &network_config.coordinates_map_stations;
+    let mut trips: Vec<(i32, i32, f64)> = vec![];
+    if let Ok(lines) = read_lines(csv_path) {
+        for (i, line) in lines.map_while(Result::ok).enumerate() {
+            let fields: Vec<&str> = line.split(',').map(|field| field.trim()).collect();
+            if fields.len() < 3 {
+                continue;
+            }
+            // This also skips the header
+            if let (Ok(from), Ok(to), Ok(weight)) = (
+                i32::from_str(fields[0]),
+                i32::from_str(fields[1]),
+                f64::from_str(fields[2]),
+            ) {
+                for station_id in [from, to] {
+                    if !stations.contains_key(&station_id) {
+                        validator.push(
+                            csv_path,
+                            &format!("line {}", i + 1),
+                            format!("there is no station {}", station_id),
+                        );
+                    }
+                }
+                push_od_trip(&mut trips, from, to, weight);
             }
         }
     }
+    OdMatrix::new(trips)
+}
+
+fn push_od_trip(trips: &mut Vec<(i32, i32, f64)>, from: i32, to: i32, weight: f64) {
+    if weight <= 0. {
+        return;
+    }
+    trips.push((from, to, weight));
+}
 
+// Stations and lines are parsed from the yamls or come from an importer, the rest of
+// the network is derived from them.
+pub fn build_network_config(
+    station_configs: &Vec<StationConfig>,
+    line_configs: &Vec<LineConfig>,
+) -> (NetworkConfig, i32) {
+    let mut coordinates_map_stations: HashMap<i32, (String, Vec<String>, String, (f32, f32))> =
+        HashMap::new();
+    let mut station_platforms: HashMap<i32, Vec<(HashSet<i32>, HashSet<LineName>)>> =
+        HashMap::new();
+    let mut lines: Vec<Line> = vec![];
+    let mut edge_map: HashMap<i32, HashSet<i32>> = HashMap::new();
     let mut n_stations_line_separated: i32 = 0;
 
-    if let Yaml::Array(lines_array) = raw_lines {
-        for line_yaml in lines_array {
-            if let Yaml::Hash(line_hash) = line_yaml {
-                let mut stations: Vec<i32> = vec![];
-                let mut distances: Vec<i32> = vec![];
-                let mut circular: bool = false;
-                let mut line_name: LineName = LineName::Placeholder;
-
-                if let Some(name_yaml) = line_hash.get(&Yaml::String(String::from("name"))) {
-                    // TODO finish this
-                    if let Yaml::String(name_string) = name_yaml {
-                        line_name = transform_line_name_to_enum(&name_string);
-                    }
-                }
-                if let Some(stations_yaml) = line_hash.get(&Yaml::String(String::from("stations")))
-                {
-                    if let Yaml::Array(stations_array) = stations_yaml {
-                        n_stations_line_separated += stations_array.len() as i32;
-                        for station_yaml in stations_array {
-                            if let Yaml::Integer(station_id) = station_yaml {
-                                stations.push(*station_id as i32);
-                            }
-                        }
-                    }
-                }
-                if let Some(distances_yaml) =
-                    line_hash.get(&Yaml::String(String::from("distances")))
-                {
-                    if let Yaml::Array(distances_array) = distances_yaml {
-                        for distance_yaml in distances_array {
-                            if let Yaml::Integer(distance) = distance_yaml {
-                                distances.push(*distance as i32);
-                            }
-                        }
-                    }
-                }
-                if let Some(circular_yaml) = line_hash.get(&Yaml::String(String::from("circular")))
-                {
-                    if let Yaml::Boolean(circular_bool) = circular_yaml {
-                        circular = *circular_bool;
-                    }
-                }
-                update_edge_map_and_group_platforms(
-                    &line_name,
-                    &stations,
-                    circular,
-                    &mut station_platforms,
-                    &mut edge_map,
-                );
-                let connections = calc_connections(&line_name, &stations, circular, &distances);
-                // println!("{}, {:?}", name, connections);
-                let line = Line {
-                    name: line_name,
-                    stations: stations,
-                    distances: distances,
-                    circular: circular,
-                    connections: connections,
-                };
-                // println!("{:?}", line);
-                lines.push(line);
-            }
-        }
+    for station_config in station_configs {
+        coordinates_map_stations.insert(
+            station_config.id,
+            (
+                station_config.name.clone(),
+                station_config.entrypoint_for.clone(),
+                station_config.city.clone(),
+                (station_config.lat, station_config.lon),
+            ),
+        );
+    }
+
+    for line_config in line_configs {
+        n_stations_line_separated += line_config.stations.len() as i32;
+        update_edge_map_and_group_platforms(
+            &line_config.name,
+            &line_config.stations,
+            line_config.circular,
+            &mut station_platforms,
+            &mut edge_map,
+        );
+        let connections = calc_connections(
+            &line_config.name,
+            &line_config.stations,
+            line_config.circular,
+            &line_config.distances,
+        );
+        // println!("{}, {:?}", name, connections);
+        let line = Line {
+            name: line_config.name.clone(),
+            stations: line_config.stations.clone(),
+            distances: line_config.distances.clone(),
+            circular: line_config.circular,
+            connections: connections,
+            on_block: line_config.on_block.clone(),
+        };
+        // println!("{:?}", line);
+        lines.push(line);
     }
 
     // println!("{:?}", station_platforms);
@@ -424,7 +463,7 @@ pub fn gen_network_config(raw_stations: &Yaml, raw_lines: &Yaml) -> (NetworkConf
     let n_pods = n_stations_line_separated;
 
     let network_config = NetworkConfig {
-        n_stations: n_stations as i32,
+        n_stations: station_configs.len() as i32,
         coordinates_map_stations: coordinates_map_stations,
         station_platforms: station_platforms,
         edge_map: edge_map,
@@ -601,3 +640,116 @@ fn calc_connections(
     }
     connections
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    const TOWN_GENERAL: &str = "
+logic:
+  number_of_people: 5
+  pod_capacity: 10
+  transition_time: 10
+  line_pods_per_hour: 60
+  station_pods_per_hour: 60
+visual:
+  radius_station: 10.0
+";
+    const TOWN_STATIONS: &str = "
+- {id: 1, lat: 48.1, lon: 11.5}
+- {id: 2, lat: 48.2, lon: 11.5}
+";
+    const TOWN_LINES: &str = "
+- {name: u1, stations: [1, 2], distances: [1000]}
+";
+
+    // A root with config.yaml and the town next to it, the caller removes it
+    fn write_config_root(name: &str) -> String {
+        let config_root = std::env::temp_dir().join(format!(
+            "publicflow_processing_{}_{}",
+            name,
+            std::process::id()
+        ));
+        let town_root = config_root.join("town");
+        fs::create_dir_all(&town_root).unwrap();
+        fs::write(town_root.join(GENERAL_CONFIG_NAME), TOWN_GENERAL).unwrap();
+        fs::write(town_root.join(STATIONS_CONFIG_NAME), TOWN_STATIONS).unwrap();
+        fs::write(town_root.join(LINES_CONFIG_NAME), TOWN_LINES).unwrap();
+        format!("{}/", config_root.display())
+    }
+
+    fn get_raw_config(override_town: bool) -> Value {
+        serde_yaml::from_str(&format!(
+            "
+general:
+  town: town
+  override: {}
+  quiet: true
+logic:
+  pod_capacity: 20
+  transition_time: 20
+",
+            override_town
+        ))
+        .unwrap()
+    }
+
+    fn get_raw_overrides() -> Value {
+        let mut overrides = Value::Mapping(Mapping::new());
+        set_key(&mut overrides, "logic.pod_capacity", Value::from(30));
+        overrides
+    }
+
+    #[test]
+    fn command_line_over_config_over_town() {
+        let config_root = write_config_root("layers");
+        let config = parse_config(
+            &config_root,
+            "config.yaml",
+            &get_raw_config(true),
+            &get_raw_overrides(),
+        );
+        let without_override = parse_config(
+            &config_root,
+            "config.yaml",
+            &get_raw_config(false),
+            &get_raw_overrides(),
+        );
+        fs::remove_dir_all(&config_root).unwrap();
+
+        assert_eq!(config.logic.pod_capacity, 30);
+        assert_eq!(config.logic.transition_time, 20);
+        assert_eq!(config.logic.number_of_people, 5);
+        assert_eq!(config.visual.radius_station, 10.);
+        // The town is only overridden by config.yaml if it says so, never by the command line
+        assert_eq!(without_override.logic.pod_capacity, 30);
+        assert_eq!(without_override.logic.transition_time, 10);
+        assert_eq!(without_override.logic.number_of_people, 5);
+    }
+
+    #[test]
+    fn dumped_config_parses_back() {
+        let config_root = write_config_root("dump");
+        let config = parse_config(
+            &config_root,
+            "config.yaml",
+            &get_raw_config(true),
+            &get_raw_overrides(),
+        );
+        let dumped = serde_yaml::to_string(&config.to_config_file()).unwrap();
+        let raw_dumped: Value = serde_yaml::from_str(&dumped).unwrap();
+        let mut validator = ConfigValidator::new();
+        validator.validate_config("config.yaml", &raw_dumped);
+        let no_overrides = Value::Mapping(Mapping::new());
+        let parsed = parse_config(&config_root, "config.yaml", &raw_dumped, &no_overrides);
+        fs::remove_dir_all(&config_root).unwrap();
+
+        assert!(validator.is_valid(), "{:?}", validator.errors);
+        assert_eq!(
+            serde_yaml::to_string(&parsed.to_config_file()).unwrap(),
+            dumped
+        );
+        assert_eq!(parsed.logic.pod_capacity, 30);
+    }
+}
@@ -1,12 +1,76 @@
-use crate::helper::enums::LineName;
+use crate::config::formats::{deserialize_text_or_integer, min_max, time_of_day, xy};
+use crate::helper::enums::{Direction, LineName, OnBlock};
 use crate::line::line::Line;
 use chrono::{DateTime, Utc};
+use rand::distributions::WeightedIndex;
+use rand::prelude::Distribution;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Mapping;
 use std::collections::{HashMap, HashSet};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ExecutionMode {
     Headless,
     Visual,
+    Import,
+    Sweep,
+}
+
+// The general section of config.yaml, it is never taken from the town
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GeneralConfig {
+    pub environment: String,
+    pub town: String,
+    pub mode: ExecutionMode,
+    // If false the visual and logic sections of config.yaml are ignored
+    #[serde(rename = "override")]
+    pub override_town: bool,
+    pub command_on_start: String,
+    // Only problems are printed, the runs of a parallel sweep would mix their output otherwise
+    pub quiet: bool,
+}
+
+impl Default for GeneralConfig {
+    fn default() -> Self {
+        GeneralConfig {
+            environment: String::from("dev"),
+            town: String::default(),
+            mode: ExecutionMode::Visual,
+            override_town: false,
+            command_on_start: String::default(),
+            quiet: false,
+        }
+    }
+}
+
+// A station as it is written in stations.yaml. Like in the existing towns lat is the
+// horizontal and lon the vertical axis on the screen.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StationConfig {
+    pub id: i32,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub city: String,
+    pub lat: f32,
+    pub lon: f32,
+    #[serde(default)]
+    pub entrypoint_for: Vec<String>,
+}
+
+// A line as it is written in lines.yaml
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LineConfig {
+    pub name: LineName,
+    pub stations: Vec<i32>,
+    pub distances: Vec<i32>,
+    #[serde(default)]
+    pub circular: bool,
+    #[serde(default)]
+    pub on_block: OnBlock,
 }
 
 #[derive(Debug, Clone)]
@@ -18,12 +82,16 @@ pub struct NetworkConfig {
     pub lines: Vec<Line>,
 }
 
-#[derive(Debug, Clone)]
+// The logic section of the town's general.yaml and config.yaml. Keys that are missing
+// in both keep the defaults below.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct LogicConfig {
     pub use_earth_coordinates: bool,
     pub distance_factor: i32,
     pub number_of_people: i32,
-    pub command_on_start: String,
+    // Derived from the lines, not configured
+    #[serde(skip)]
     pub number_of_pods: i32,
     pub pod_capacity: i32,
     pub transition_time: i32,
@@ -31,39 +99,422 @@ pub struct LogicConfig {
     pub line_pods_per_hour: i32,
     pub station_pods_per_hour: i32,
     pub shuffle_people: bool,
+    #[serde(skip)]
     pub on_pause: bool,
     pub speed_multiplier: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    #[serde(with = "time_of_day")]
+    pub start_time: u32,
+    // Every how many seconds the time series of each person and pod get a timestamp,
+    // 0 keeps none. The averages and distributions are gathered every second anyway.
+    pub metrics_interval: u32,
 }
 
-#[derive(Debug, Clone)]
+impl Default for LogicConfig {
+    fn default() -> Self {
+        LogicConfig {
+            use_earth_coordinates: false,
+            distance_factor: 1000,
+            number_of_people: 0,
+            number_of_pods: 0,
+            pod_capacity: 0,
+            transition_time: 0,
+            pod_in_station_seconds: 0,
+            line_pods_per_hour: 0,
+            station_pods_per_hour: 0,
+            shuffle_people: false,
+            on_pause: false,
+            speed_multiplier: 1,
+            seed: None,
+            start_time: 0,
+            metrics_interval: 1,
+        }
+    }
+}
+
+// The visual section of the town's general.yaml and config.yaml, the current ranges
+// and the mouse positions only exist while the window is open.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct VisualConfig {
+    #[serde(with = "xy")]
     pub screen_size: (f32, f32),
+    #[serde(rename = "latitude", with = "min_max")]
     pub latitude_range_bounds: (f32, f32),
+    #[serde(rename = "longitude", with = "min_max")]
     pub longitude_range_bounds: (f32, f32),
+    #[serde(skip)]
     pub latitude_range_current: (f32, f32),
+    #[serde(skip)]
     pub longitude_range_current: (f32, f32),
     pub screen_offset: f32,
     pub radius_station: f32,
     pub radius_pod: f32,
     pub width_line: f32,
     pub vsync: bool,
+    #[serde(skip)]
     pub last_mouse: (f32, f32),
+    #[serde(skip)]
     pub last_mouse_while_zooming_relative: (f32, f32),
+    #[serde(skip)]
     pub last_mouse_left: (f32, f32),
 }
 
+// config.yaml as a whole. Serializing it gives the effective config of a run, with the
+// values of the town and the overrides merged in.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConfigFile {
+    pub general: GeneralConfig,
+    pub visual: VisualConfig,
+    pub logic: LogicConfig,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub import: Option<ImportConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sweep: Option<SweepConfig>,
+}
+
 #[derive(Debug, Clone)]
 pub struct Config {
     pub timestamp_run: Option<DateTime<Utc>>,
-    pub mode: ExecutionMode,
-    pub environment: String,
+    // Set for the runs of a sweep, otherwise the output goes to metrics/<environment>/<timestamp_run>
+    pub output_dir: Option<String>,
+    pub general: GeneralConfig,
     pub network: NetworkConfig,
     pub logic: LogicConfig,
     pub visual: VisualConfig,
+    pub demand: Option<DemandConfig>,
+    pub timetable: Option<TimetableConfig>,
 }
 
 impl Config {
     pub fn add_timestamp_run(&mut self, timstamp: DateTime<Utc>) {
         self.timestamp_run = Some(timstamp)
     }
+
+    pub fn get_output_dir(&self) -> Option<String> {
+        if let Some(output_dir) = &self.output_dir {
+            return Some(output_dir.clone());
+        }
+        let timestamp = self
+            .timestamp_run?
+            .naive_utc()
+            .format("%Y.%m.%d %H:%M")
+            .to_string()
+            .replace(" ", "_");
+        Some(format!(
+            "{}/{}/{}",
+            "metrics", self.general.environment, timestamp
+        ))
+    }
+
+    pub fn to_config_file(&self) -> ConfigFile {
+        ConfigFile {
+            general: self.general.clone(),
+            visual: self.visual.clone(),
+            logic: self.logic.clone(),
+            import: None,
+            sweep: None,
+        }
+    }
+}
+
+// Weighted origin destination pairs. The weighted indices are built once here,
+// so sampling a trip does not have to walk the whole matrix every time.
+#[derive(Debug, Clone)]
+pub struct OdMatrix {
+    pub trips: Vec<(i32, i32, f64)>,
+    all: Option<WeightedIndex<f64>>,
+    by_origin: HashMap<i32, (Vec<i32>, WeightedIndex<f64>)>,
+}
+
+impl OdMatrix {
+    pub fn new(trips: Vec<(i32, i32, f64)>) -> Self {
+        let all = WeightedIndex::new(trips.iter().map(|trip| trip.2)).ok();
+
+        let mut destinations_by_origin: HashMap<i32, (Vec<i32>, Vec<f64>)> = HashMap::new();
+        for (from, to, weight) in &trips {
+            let entry = destinations_by_origin.entry(*from).or_default();
+            entry.0.push(*to);
+            entry.1.push(*weight);
+        }
+        let mut by_origin = HashMap::new();
+        for (from, (destinations, weights)) in destinations_by_origin {
+            if let Ok(index) = WeightedIndex::new(weights) {
+                by_origin.insert(from, (destinations, index));
+            }
+        }
+
+        OdMatrix {
+            trips: trips,
+            all: all,
+            by_origin: by_origin,
+        }
+    }
+
+    pub fn try_sample_trip(&self, rng: &mut ChaCha8Rng) -> Option<(i32, i32)> {
+        let index = self.all.as_ref()?;
+        let (from, to, _) = self.trips[index.sample(rng)];
+        Some((from, to))
+    }
+
+    pub fn try_sample_destination(&self, origin: i32, rng: &mut ChaCha8Rng) -> Option<i32> {
+        let (destinations, index) = self.by_origin.get(&origin)?;
+        Some(destinations[index.sample(rng)])
+    }
+}
+
+// A matrix that replaces the base matrix between two times of day, in seconds
+// since midnight. Windows with from > to wrap around midnight.
+#[derive(Debug, Clone)]
+pub struct DemandProfile {
+    pub name: String,
+    pub from: u32,
+    pub to: u32,
+    pub od: OdMatrix,
+}
+
+impl DemandProfile {
+    pub fn is_active(&self, time_of_day: u32) -> bool {
+        if self.from <= self.to {
+            self.from <= time_of_day && time_of_day < self.to
+        } else {
+            self.from <= time_of_day || time_of_day < self.to
+        }
+    }
+}
+
+// demand.yaml as it is written, station ids are checked by the validator before
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct DemandFile {
+    pub od: Vec<OdTripFile>,
+    pub profiles: Vec<DemandProfileFile>,
+    pub hourly_trips: Option<Vec<u32>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct OdTripFile {
+    pub from: i32,
+    pub to: i32,
+    pub weight: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DemandProfileFile {
+    #[serde(default)]
+    pub name: String,
+    #[serde(deserialize_with = "time_of_day::deserialize")]
+    pub from: u32,
+    #[serde(deserialize_with = "time_of_day::deserialize")]
+    pub to: u32,
+    #[serde(default)]
+    pub od: Vec<OdTripFile>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DemandConfig {
+    pub od: OdMatrix,
+    pub profiles: Vec<DemandProfile>,
+    // Trips that start in each hour of the day. When set, people enter at
+    // scheduled times and leave at their destination instead of traveling forever.
+    pub hourly_trips: Option<Vec<u32>>,
+}
+
+impl DemandConfig {
+    pub fn get_active_od(&self, time_of_day: u32) -> &OdMatrix {
+        for profile in &self.profiles {
+            if profile.is_active(time_of_day) {
+                return &profile.od;
+            }
+        }
+        &self.od
+    }
+}
+
+// timetable.yaml is a list of these
+#[derive(Debug, Clone, Deserialize)]
+pub struct TimetableLineFile {
+    // With the direction, e.g. u1+
+    pub line: String,
+    #[serde(default)]
+    pub trips: Vec<TimetableTripFile>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TimetableTripFile {
+    #[serde(default, deserialize_with = "deserialize_text_or_integer")]
+    pub id: String,
+    #[serde(deserialize_with = "time_of_day::deserialize")]
+    pub departure: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct TimetableTrip {
+    pub id: String,
+    // Seconds since midnight
+    pub departure: u32,
+}
+
+// All trips of a line in one direction, they start at the first station of the
+// line in that direction and end at the last one.
+#[derive(Debug, Clone)]
+pub struct TimetableLine {
+    pub line_name: LineName,
+    pub direction: Direction,
+    pub trips: Vec<TimetableTrip>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TimetableConfig {
+    pub lines: Vec<TimetableLine>,
+    // Second of the day -> (index in lines, index in trips), so dispatching does not
+    // have to go through all trips every second
+    departures: HashMap<u32, Vec<(usize, usize)>>,
+}
+
+impl TimetableConfig {
+    pub fn new(lines: Vec<TimetableLine>) -> Self {
+        let mut departures: HashMap<u32, Vec<(usize, usize)>> = HashMap::new();
+        for (i, timetable_line) in lines.iter().enumerate() {
+            for (j, trip) in timetable_line.trips.iter().enumerate() {
+                departures.entry(trip.departure).or_default().push((i, j));
+            }
+        }
+        TimetableConfig {
+            lines: lines,
+            departures: departures,
+        }
+    }
+
+    pub fn get_departures(&self, time_of_day: u32) -> Vec<(&TimetableLine, &TimetableTrip)> {
+        match self.departures.get(&time_of_day) {
+            Some(ixs) => ixs
+                .iter()
+                .map(|(i, j)| (&self.lines[*i], &self.lines[*i].trips[*j]))
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    pub fn get_number_of_trips(&self) -> usize {
+        self.lines
+            .iter()
+            .map(|timetable_line| timetable_line.trips.len())
+            .sum()
+    }
+}
+
+// Where to import a town from and where to write it, used by the import mode
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImportConfig {
+    // A GTFS feed, either the zip or a folder with the unpacked txt files
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gtfs: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wiki: Option<WikiImportConfig>,
+    pub town: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub city: Option<String>,
+    pub overwrite: bool,
+}
+
+// Line descriptions in the format of the wiki csv files in data/ and the stations they name
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WikiImportConfig {
+    #[serde(default)]
+    pub lines: Vec<String>,
+    pub stations: String,
+}
+
+// The runs of the sweep mode, keys are paths into config.yaml like logic.pod_capacity.
+// Every entry of runs is combined with every combination of the grid values and every
+// seed, an empty runs or grid adds nothing to the combinations.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SweepConfig {
+    pub grid: Mapping,
+    pub runs: Vec<Mapping>,
+    pub seeds: Vec<u64>,
+    // Runs simulated at the same time, 0 uses every core
+    pub workers: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn get_od() -> OdMatrix {
+        OdMatrix::new(vec![(1, 2, 3.), (2, 1, 1.), (1, 3, 0.)])
+    }
+
+    #[test]
+    fn same_seed_samples_same_trips() {
+        let od = get_od();
+        let mut rng = ChaCha8Rng::seed_from_u64(7);
+        let mut rng_again = ChaCha8Rng::seed_from_u64(7);
+        for _ in 0..100 {
+            assert_eq!(
+                od.try_sample_trip(&mut rng),
+                od.try_sample_trip(&mut rng_again)
+            );
+        }
+    }
+
+    #[test]
+    fn trips_follow_the_weights() {
+        let od = get_od();
+        let mut rng = ChaCha8Rng::seed_from_u64(7);
+        let mut counts: HashMap<(i32, i32), u32> = HashMap::new();
+        for _ in 0..4000 {
+            *counts
+                .entry(od.try_sample_trip(&mut rng).unwrap())
+                .or_default() += 1;
+        }
+        // A trip without weight is never sampled
+        assert_eq!(counts.get(&(1, 3)), None);
+        let forth = counts[&(1, 2)];
+        assert!(
+            2800 < forth && forth < 3200,
+            "{} of 4000 from 1 to 2",
+            forth
+        );
+        assert_eq!(forth + counts[&(2, 1)], 4000);
+    }
+
+    #[test]
+    fn destinations_only_of_the_origin() {
+        let od = get_od();
+        let mut rng = ChaCha8Rng::seed_from_u64(7);
+        for _ in 0..100 {
+            assert_eq!(od.try_sample_destination(1, &mut rng), Some(2));
+            assert_eq!(od.try_sample_destination(2, &mut rng), Some(1));
+        }
+        assert_eq!(od.try_sample_destination(3, &mut rng), None);
+        assert_eq!(OdMatrix::new(vec![]).try_sample_trip(&mut rng), None);
+    }
+
+    #[test]
+    fn profile_replaces_od_while_active() {
+        let demand = DemandConfig {
+            od: get_od(),
+            profiles: vec![DemandProfile {
+                name: String::from("night"),
+                from: 22 * 3600,
+                to: 6 * 3600,
+                od: OdMatrix::new(vec![(3, 4, 1.)]),
+            }],
+            hourly_trips: None,
+        };
+        let mut rng = ChaCha8Rng::seed_from_u64(7);
+        let night = demand.get_active_od(23 * 3600);
+        assert_eq!(night.try_sample_trip(&mut rng), Some((3, 4)));
+        let early = demand.get_active_od(5 * 3600);
+        assert_eq!(early.try_sample_trip(&mut rng), Some((3, 4)));
+        let day = demand.get_active_od(6 * 3600);
+        assert_ne!(day.try_sample_trip(&mut rng), Some((3, 4)));
+    }
 }
@@ -0,0 +1,1039 @@
+use crate::config::structs::NetworkConfig;
+use crate::helper::enums::LineName;
+use crate::helper::functions::{parse_time_of_day, try_transform_line_name_to_enum};
+use serde_yaml::Value;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::process::exit;
+use strsim::jaro_winkler;
+
+// Unknown keys that are at least this similar to a known one get it suggested
+const MIN_KEY_SIMILARITY: f64 = 0.8;
+// Long lists of station ids are cut off in the report
+const MAX_IDS_IN_REPORT: usize = 10;
+
+// What a value in the yamls has to look like. Real is any number, 1920 is as fine as 1920.0.
+#[derive(Clone, Copy)]
+enum Kind {
+    Bool,
+    Integer,
+    PositiveInteger,
+    Real,
+    Text,
+    // Ids can be written as either
+    TextOrInteger,
+    TimeOfDay,
+    OneOf(&'static [&'static str]),
+    IntegerList,
+    TextList,
+    Map(&'static [(&'static str, Kind)]),
+    MapList(&'static [(&'static str, Kind)]),
+    // Key paths into config.yaml, each with a list of values
+    SweepGrid,
+    // A list of maps from key paths into config.yaml to a value
+    SweepRuns,
+}
+
+const RANGE_KEYS: &[(&str, Kind)] = &[("min", Kind::Real), ("max", Kind::Real)];
+
+const VISUAL_KEYS: &[(&str, Kind)] = &[
+    (
+        "screen_size",
+        Kind::Map(&[("x", Kind::Real), ("y", Kind::Real)]),
+    ),
+    ("latitude", Kind::Map(RANGE_KEYS)),
+    ("longitude", Kind::Map(RANGE_KEYS)),
+    ("screen_offset", Kind::Real),
+    ("radius_station", Kind::Real),
+    ("radius_pod", Kind::Real),
+    ("width_line", Kind::Real),
+    ("vsync", Kind::Bool),
+];
+
+const LOGIC_KEYS: &[(&str, Kind)] = &[
+    ("use_earth_coordinates", Kind::Bool),
+    ("distance_factor", Kind::PositiveInteger),
+    ("number_of_people", Kind::Integer),
+    ("pod_capacity", Kind::PositiveInteger),
+    ("transition_time", Kind::Integer),
+    ("pod_in_station_seconds", Kind::Integer),
+    ("line_pods_per_hour", Kind::PositiveInteger),
+    ("station_pods_per_hour", Kind::PositiveInteger),
+    ("shuffle_people", Kind::Bool),
+    ("speed_multiplier", Kind::PositiveInteger),
+    ("seed", Kind::Integer),
+    ("start_time", Kind::TimeOfDay),
+    ("metrics_interval", Kind::Integer),
+];
+
+// Pods and platforms divide by these, they have no usable default
+const REQUIRED_LOGIC_KEYS: &[&str] = &[
+    "pod_capacity",
+    "line_pods_per_hour",
+    "station_pods_per_hour",
+];
+
+const CONFIG_KEYS: &[(&str, Kind)] = &[
+    (
+        "general",
+        Kind::Map(&[
+            ("environment", Kind::Text),
+            ("town", Kind::Text),
+            (
+                "mode",
+                Kind::OneOf(&["visual", "headless", "import", "sweep"]),
+            ),
+            ("override", Kind::Bool),
+            ("command_on_start", Kind::Text),
+            ("quiet", Kind::Bool),
+        ]),
+    ),
+    ("visual", Kind::Map(VISUAL_KEYS)),
+    ("logic", Kind::Map(LOGIC_KEYS)),
+    (
+        "import",
+        Kind::Map(&[
+            ("gtfs", Kind::Text),
+            (
+                "wiki",
+                Kind::Map(&[("lines", Kind::TextList), ("stations", Kind::Text)]),
+            ),
+            ("town", Kind::Text),
+            ("city", Kind::Text),
+            ("overwrite", Kind::Bool),
+        ]),
+    ),
+    (
+        "sweep",
+        Kind::Map(&[
+            ("grid", Kind::SweepGrid),
+            ("runs", Kind::SweepRuns),
+            ("seeds", Kind::IntegerList),
+            ("workers", Kind::Integer),
+        ]),
+    ),
+];
+
+const GENERAL_KEYS: &[(&str, Kind)] = &[
+    ("visual", Kind::Map(VISUAL_KEYS)),
+    ("logic", Kind::Map(LOGIC_KEYS)),
+];
+
+const STATION_KEYS: &[(&str, Kind)] = &[
+    ("city", Kind::Text),
+    ("id", Kind::Integer),
+    ("lat", Kind::Real),
+    ("lon", Kind::Real),
+    ("name", Kind::Text),
+    ("entrypoint_for", Kind::TextList),
+];
+const REQUIRED_STATION_KEYS: &[&str] = &["id", "lat", "lon"];
+
+const LINE_KEYS: &[(&str, Kind)] = &[
+    ("circular", Kind::Bool),
+    ("distances", Kind::IntegerList),
+    ("name", Kind::Text),
+    ("stations", Kind::IntegerList),
+    ("on_block", Kind::OneOf(&["wait", "turn"])),
+];
+const REQUIRED_LINE_KEYS: &[&str] = &["name", "stations", "distances"];
+
+const OD_TRIP_KEYS: &[(&str, Kind)] = &[
+    ("from", Kind::Integer),
+    ("to", Kind::Integer),
+    ("weight", Kind::Real),
+];
+const REQUIRED_OD_TRIP_KEYS: &[&str] = &["from", "to", "weight"];
+
+const DEMAND_PROFILE_KEYS: &[(&str, Kind)] = &[
+    ("name", Kind::Text),
+    ("from", Kind::TimeOfDay),
+    ("to", Kind::TimeOfDay),
+    ("od", Kind::MapList(OD_TRIP_KEYS)),
+];
+const REQUIRED_DEMAND_PROFILE_KEYS: &[&str] = &["from", "to"];
+
+const DEMAND_KEYS: &[(&str, Kind)] = &[
+    ("od", Kind::MapList(OD_TRIP_KEYS)),
+    ("profiles", Kind::MapList(DEMAND_PROFILE_KEYS)),
+    ("hourly_trips", Kind::IntegerList),
+];
+
+const TIMETABLE_TRIP_KEYS: &[(&str, Kind)] =
+    &[("id", Kind::TextOrInteger), ("departure", Kind::TimeOfDay)];
+const REQUIRED_TIMETABLE_TRIP_KEYS: &[&str] = &["departure"];
+
+const TIMETABLE_LINE_KEYS: &[(&str, Kind)] = &[
+    ("line", Kind::Text),
+    ("trips", Kind::MapList(TIMETABLE_TRIP_KEYS)),
+];
+const REQUIRED_TIMETABLE_LINE_KEYS: &[&str] = &["line"];
+
+// A problem in one of the yamls, key_path is empty if it is about the whole file
+#[derive(Debug, Clone)]
+pub struct ConfigError {
+    pub file: String,
+    pub key_path: String,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.key_path.is_empty() {
+            write!(f, "{}: {}", self.file, self.message)
+        } else {
+            write!(f, "{}: {}: {}", self.file, self.key_path, self.message)
+        }
+    }
+}
+
+fn describe(yaml: &Value) -> String {
+    match yaml {
+        Value::Number(value) if value.is_f64() => format!("the number {}", value),
+        Value::Number(value) => format!("the integer {}", value),
+        Value::String(value) => format!("the text \"{}\"", value),
+        Value::Bool(value) => format!("{}", value),
+        Value::Sequence(_) => String::from("a list"),
+        Value::Mapping(_) => String::from("a map"),
+        Value::Null => String::from("nothing"),
+    }
+}
+
+fn join_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+fn format_ids(ids: &Vec<i32>) -> String {
+    let mut shown: Vec<String> = ids
+        .iter()
+        .take(MAX_IDS_IN_REPORT)
+        .map(|id| id.to_string())
+        .collect();
+    if ids.len() > MAX_IDS_IN_REPORT {
+        shown.push(format!("and {} more", ids.len() - MAX_IDS_IN_REPORT));
+    }
+    shown.join(", ")
+}
+
+fn unknown_key(key: &str, keys: &[(&str, Kind)]) -> String {
+    let maybe_similar = keys
+        .iter()
+        .map(|(name, _)| (name, jaro_winkler(key, name)))
+        .filter(|(_, similarity)| *similarity >= MIN_KEY_SIMILARITY)
+        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+    match maybe_similar {
+        Some((similar, _)) => format!("unknown key, did you mean {}?", similar),
+        None => String::from("unknown key"),
+    }
+}
+
+fn get<'a>(hash: &'a Value, key: &str) -> Option<&'a Value> {
+    hash.get(key)
+}
+
+fn as_integer(yaml: &Value) -> Option<i64> {
+    match yaml {
+        Value::Number(value) if !value.is_f64() => value.as_i64(),
+        _ => None,
+    }
+}
+
+// Collects every problem of the config and the files of the town instead of stopping
+// at the first one, so they can all be fixed in one go.
+pub struct ConfigValidator {
+    pub errors: Vec<ConfigError>,
+}
+
+impl ConfigValidator {
+    pub fn new() -> Self {
+        ConfigValidator { errors: vec![] }
+    }
+
+    pub fn push(&mut self, file: &str, key_path: &str, message: String) {
+        self.errors.push(ConfigError {
+            file: file.to_string(),
+            key_path: key_path.to_string(),
+            message: message,
+        });
+    }
+
+    fn check_keys(
+        &mut self,
+        file: &str,
+        path: &str,
+        yaml: &Value,
+        keys: &'static [(&'static str, Kind)],
+    ) {
+        let hash = match yaml {
+            Value::Mapping(hash) => hash,
+            other => {
+                self.push(
+                    file,
+                    path,
+                    format!("expected a map, got {}", describe(other)),
+                );
+                return;
+            }
+        };
+        for (key_yaml, value) in hash {
+            let key = match key_yaml {
+                Value::String(key) => key,
+                other => {
+                    self.push(file, path, format!("{} is no valid key", describe(other)));
+                    continue;
+                }
+            };
+            match keys.iter().find(|(name, _)| name == key) {
+                Some((_, kind)) => self.check_value(file, &join_path(path, key), value, *kind),
+                None => self.push(file, &join_path(path, key), unknown_key(key, keys)),
+            }
+        }
+    }
+
+    // The kind of the value a path like logic.pod_capacity points to in config.yaml
+    fn check_key_path(&mut self, file: &str, path: &str, key_path: &Value) -> Option<Kind> {
+        let key_path = match key_path {
+            Value::String(key_path) => key_path,
+            other => {
+                self.push(file, path, format!("{} is no valid key", describe(other)));
+                return None;
+            }
+        };
+        let mut keys = CONFIG_KEYS;
+        let mut kind = None;
+        for key in key_path.split('.') {
+            if kind.is_some() {
+                self.push(
+                    file,
+                    &join_path(path, key_path),
+                    String::from("goes past a value"),
+                );
+                return None;
+            }
+            match keys.iter().find(|(name, _)| *name == key) {
+                Some((_, Kind::Map(inner_keys))) => keys = inner_keys,
+                Some((_, found)) => kind = Some(*found),
+                None => {
+                    self.push(file, &join_path(path, key_path), unknown_key(key, keys));
+                    return None;
+                }
+            }
+        }
+        if kind.is_none() {
+            self.push(
+                file,
+                &join_path(path, key_path),
+                String::from("is a section, expected the path to a value"),
+            );
+        }
+        kind
+    }
+
+    fn check_value(&mut self, file: &str, path: &str, yaml: &Value, kind: Kind) {
+        let expected = match (kind, yaml) {
+            (Kind::Bool, Value::Bool(_)) => return,
+            (Kind::Bool, _) => String::from("true or false"),
+            (Kind::Integer, _) if as_integer(yaml).is_some() => return,
+            (Kind::Integer, _) => String::from("an integer"),
+            (Kind::PositiveInteger, _) if as_integer(yaml).unwrap_or(0) > 0 => return,
+            (Kind::PositiveInteger, _) => String::from("an integer greater than 0"),
+            (Kind::Real, Value::Number(_)) => return,
+            (Kind::Real, _) => String::from("a number"),
+            (Kind::Text, Value::String(_)) => return,
+            (Kind::Text, _) => String::from("a text"),
+            (Kind::TextOrInteger, Value::String(_)) => return,
+            (Kind::TextOrInteger, _) if as_integer(yaml).is_some() => return,
+            (Kind::TextOrInteger, _) => String::from("a text or an integer"),
+            (Kind::TimeOfDay, Value::String(value)) if parse_time_of_day(value).is_some() => return,
+            (Kind::TimeOfDay, _) => String::from("a time of day like \"06:00\""),
+            (Kind::OneOf(options), Value::String(value)) if options.contains(&value.as_str()) => {
+                return
+            }
+            (Kind::OneOf(options), _) => format!("one of {}", options.join(", ")),
+            (Kind::IntegerList, Value::Sequence(array)) => {
+                for (i, element) in array.iter().enumerate() {
+                    self.check_value(file, &format!("{}[{}]", path, i), element, Kind::Integer);
+                }
+                return;
+            }
+            (Kind::IntegerList, _) => String::from("a list of integers"),
+            (Kind::TextList, Value::Sequence(array)) => {
+                for (i, element) in array.iter().enumerate() {
+                    self.check_value(file, &format!("{}[{}]", path, i), element, Kind::Text);
+                }
+                return;
+            }
+            (Kind::TextList, _) => String::from("a list of texts"),
+            (Kind::Map(keys), _) => {
+                self.check_keys(file, path, yaml, keys);
+                return;
+            }
+            (Kind::MapList(keys), Value::Sequence(array)) => {
+                for (i, element) in array.iter().enumerate() {
+                    self.check_keys(file, &format!("{}[{}]", path, i), element, keys);
+                }
+                return;
+            }
+            (Kind::MapList(_), _) => String::from("a list of maps"),
+            (Kind::SweepGrid, Value::Mapping(hash)) => {
+                for (key_path, values) in hash {
+                    let kind = match self.check_key_path(file, path, key_path) {
+                        Some(kind) => kind,
+                        None => continue,
+                    };
+                    let key_path = join_path(path, key_path.as_str().unwrap());
+                    match values {
+                        Value::Sequence(array) if !array.is_empty() => {
+                            for (i, element) in array.iter().enumerate() {
+                                self.check_value(
+                                    file,
+                                    &format!("{}[{}]", key_path, i),
+                                    element,
+                                    kind,
+                                );
+                            }
+                        }
+                        other => self.push(
+                            file,
+                            &key_path,
+                            format!("expected a list of values, got {}", describe(other)),
+                        ),
+                    }
+                }
+                return;
+            }
+            (Kind::SweepGrid, _) => {
+                String::from("a map from keys like logic.pod_capacity to lists of values")
+            }
+            (Kind::SweepRuns, Value::Sequence(array)) => {
+                for (i, element) in array.iter().enumerate() {
+                    let run_path = format!("{}[{}]", path, i);
+                    let hash = match element {
+                        Value::Mapping(hash) => hash,
+                        other => {
+                            self.push(
+                                file,
+                                &run_path,
+                                format!("expected a map, got {}", describe(other)),
+                            );
+                            continue;
+                        }
+                    };
+                    for (key_path, value) in hash {
+                        if let Some(kind) = self.check_key_path(file, &run_path, key_path) {
+                            let key_path = join_path(&run_path, key_path.as_str().unwrap());
+                            self.check_value(file, &key_path, value, kind);
+                        }
+                    }
+                }
+                return;
+            }
+            (Kind::SweepRuns, _) => {
+                String::from("a list of maps from keys like logic.pod_capacity to values")
+            }
+        };
+        self.push(
+            file,
+            path,
+            format!("expected {}, got {}", expected, describe(yaml)),
+        );
+    }
+
+    fn check_list_of_maps(
+        &mut self,
+        file: &str,
+        yaml: &Value,
+        keys: &'static [(&'static str, Kind)],
+        required_keys: &[&str],
+    ) {
+        let array = match yaml {
+            Value::Sequence(array) => array,
+            other => {
+                self.push(
+                    file,
+                    "",
+                    format!("expected a list, got {}", describe(other)),
+                );
+                return;
+            }
+        };
+        for (i, element) in array.iter().enumerate() {
+            let path = format!("[{}]", i);
+            self.check_keys(file, &path, element, keys);
+            self.check_required(file, &path, element, required_keys);
+        }
+    }
+
+    fn check_required(&mut self, file: &str, path: &str, yaml: &Value, required_keys: &[&str]) {
+        if let Value::Mapping(_) = yaml {
+            for key in required_keys {
+                if get(yaml, key).is_none() {
+                    self.push(file, &join_path(path, key), String::from("missing"));
+                }
+            }
+        }
+    }
+
+    // The od matrix of the whole day or of a profile, path is where the list is
+    fn check_od_trips(
+        &mut self,
+        file: &str,
+        path: &str,
+        raw_od: Option<&Value>,
+        network_config: &NetworkConfig,
+    ) {
+        let od_trips = match raw_od {
+            Some(Value::Sequence(od_trips)) => od_trips,
+            _ => return,
+        };
+        for (i, od_trip) in od_trips.iter().enumerate() {
+            let od_path = format!("{}[{}]", path, i);
+            self.check_required(file, &od_path, od_trip, REQUIRED_OD_TRIP_KEYS);
+            for key in ["from", "to"] {
+                if let Some(station_id) = get(od_trip, key).and_then(as_integer) {
+                    if !network_config
+                        .coordinates_map_stations
+                        .contains_key(&(station_id as i32))
+                    {
+                        self.push(
+                            file,
+                            &join_path(&od_path, key),
+                            format!("there is no station {}", station_id),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn validate_config(&mut self, file: &str, raw_config: &Value) {
+        self.check_keys(file, "", raw_config, CONFIG_KEYS);
+    }
+
+    pub fn validate_town_general(&mut self, file: &str, raw_general: &Value) {
+        self.check_keys(file, "", raw_general, GENERAL_KEYS);
+    }
+
+    // The keys pods and platforms can't do without have to be in one of the layers, the
+    // general.yaml of the town, the config.yaml if it overrides it or the command line
+    pub fn validate_required_logic(&mut self, general_file: &str, raw_layered: &Value) {
+        for key in REQUIRED_LOGIC_KEYS {
+            if get(raw_layered, "logic")
+                .and_then(|logic| get(logic, key))
+                .is_none()
+            {
+                self.push(
+                    general_file,
+                    &join_path("logic", key),
+                    String::from("missing, expected an integer greater than 0"),
+                );
+            }
+        }
+    }
+
+    // Checks the files on their own and then how stations and lines fit together
+    pub fn validate_network(
+        &mut self,
+        stations_file: &str,
+        raw_stations: &Value,
+        lines_file: &str,
+        raw_lines: &Value,
+    ) {
+        self.check_list_of_maps(
+            stations_file,
+            raw_stations,
+            STATION_KEYS,
+            REQUIRED_STATION_KEYS,
+        );
+        self.check_list_of_maps(lines_file, raw_lines, LINE_KEYS, REQUIRED_LINE_KEYS);
+
+        let empty = vec![];
+        let raw_stations_array = raw_stations.as_sequence().unwrap_or(&empty);
+        let raw_lines_array = raw_lines.as_sequence().unwrap_or(&empty);
+
+        let mut station_ids: Vec<i32> = vec![];
+        for (i, raw_station) in raw_stations_array.iter().enumerate() {
+            if let Some(id) = get(raw_station, "id").and_then(as_integer) {
+                if station_ids.contains(&(id as i32)) {
+                    self.push(
+                        stations_file,
+                        &format!("[{}].id", i),
+                        format!("station {} is defined more than once", id),
+                    );
+                } else {
+                    station_ids.push(id as i32);
+                }
+            }
+        }
+
+        // Stations served by each line, only of the lines that are fine so far
+        let mut line_stations: HashMap<LineName, Vec<i32>> = HashMap::new();
+        for (i, raw_line) in raw_lines_array.iter().enumerate() {
+            let path = format!("[{}]", i);
+            let mut line_name: Option<LineName> = None;
+            if let Some(Value::String(name)) = get(raw_line, "name") {
+                line_name = try_transform_line_name_to_enum(name);
+                match &line_name {
+                    Some(line_name) if line_stations.contains_key(line_name) => {
+                        self.push(
+                            lines_file,
+                            &join_path(&path, "name"),
+                            format!("line {} is defined more than once", name),
+                        );
+                    }
+                    Some(_) => {}
+                    None => self.push(
+                        lines_file,
+                        &join_path(&path, "name"),
+                        format!("{} is no line name like u1, t12 or s8", name),
+                    ),
+                }
+            }
+
+            let stations: Vec<i32> = get(raw_line, "stations")
+                .and_then(|stations| stations.as_sequence())
+                .unwrap_or(&empty)
+                .iter()
+                .filter_map(as_integer)
+                .map(|station| station as i32)
+                .collect();
+            for (j, station_id) in stations.iter().enumerate() {
+                if !station_ids.contains(station_id) {
+                    self.push(
+                        lines_file,
+                        &format!("{}.stations[{}]", path, j),
+                        format!("there is no station {}", station_id),
+                    );
+                }
+            }
+            if stations.len() < 2 && get(raw_line, "stations").is_some() {
+                self.push(
+                    lines_file,
+                    &join_path(&path, "stations"),
+                    String::from("a line needs at least two stations"),
+                );
+            }
+
+            let circular = matches!(get(raw_line, "circular"), Some(Value::Bool(true)));
+            if let Some(Value::Sequence(distances)) = get(raw_line, "distances") {
+                let n_expected = if circular {
+                    stations.len()
+                } else {
+                    stations.len().saturating_sub(1)
+                };
+                if distances.len() != n_expected {
+                    self.push(
+                        lines_file,
+                        &join_path(&path, "distances"),
+                        format!(
+                            "{} distances for {} stations, a {} line needs {}",
+                            distances.len(),
+                            stations.len(),
+                            if circular { "circular" } else { "non circular" },
+                            n_expected
+                        ),
+                    );
+                }
+            }
+
+            if let Some(line_name) = line_name {
+                line_stations.entry(line_name).or_insert(stations);
+            }
+        }
+
+        for (i, raw_station) in raw_stations_array.iter().enumerate() {
+            let station_id = match get(raw_station, "id").and_then(as_integer) {
+                Some(id) => id as i32,
+                None => continue,
+            };
+            let entrypoints = match get(raw_station, "entrypoint_for") {
+                Some(Value::Sequence(entrypoints)) => entrypoints,
+                _ => continue,
+            };
+            for (j, entrypoint) in entrypoints.iter().enumerate() {
+                let entrypoint = match entrypoint {
+                    Value::String(entrypoint) => entrypoint,
+                    _ => continue,
+                };
+                let path = format!("[{}].entrypoint_for[{}]", i, j);
+                let name = entrypoint.replace(&['+', '-'][..], "");
+                match try_transform_line_name_to_enum(&name) {
+                    Some(line_name) => match line_stations.get(&line_name) {
+                        Some(stations) if !stations.contains(&station_id) => self.push(
+                            stations_file,
+                            &path,
+                            format!("{} doesn't serve station {}", name, station_id),
+                        ),
+                        Some(_) => {}
+                        None => {
+                            self.push(stations_file, &path, format!("there is no line {}", name))
+                        }
+                    },
+                    None => self.push(
+                        stations_file,
+                        &path,
+                        format!("{} is no line like u1+, t12- or s8+-", entrypoint),
+                    ),
+                }
+            }
+        }
+
+        self.check_connected(lines_file, &station_ids, &line_stations);
+    }
+
+    // The demand is checked against the network that was built from stations and lines
+    pub fn validate_demand(
+        &mut self,
+        demand_file: &str,
+        raw_demand: &Value,
+        network_config: &NetworkConfig,
+    ) {
+        self.check_keys(demand_file, "", raw_demand, DEMAND_KEYS);
+        self.check_od_trips(demand_file, "od", get(raw_demand, "od"), network_config);
+        if let Some(Value::Sequence(profiles)) = get(raw_demand, "profiles") {
+            for (i, profile) in profiles.iter().enumerate() {
+                let path = format!("profiles[{}]", i);
+                self.check_required(demand_file, &path, profile, REQUIRED_DEMAND_PROFILE_KEYS);
+                self.check_od_trips(
+                    demand_file,
+                    &join_path(&path, "od"),
+                    get(profile, "od"),
+                    network_config,
+                );
+            }
+        }
+        if let Some(Value::Sequence(hourly_trips)) = get(raw_demand, "hourly_trips") {
+            if hourly_trips.len() != 24 {
+                self.push(
+                    demand_file,
+                    "hourly_trips",
+                    format!(
+                        "found {} values, expected one for each of the 24 hours",
+                        hourly_trips.len()
+                    ),
+                );
+            }
+            for (i, trips) in hourly_trips.iter().enumerate() {
+                if as_integer(trips).unwrap_or(0) < 0 {
+                    self.push(
+                        demand_file,
+                        &format!("hourly_trips[{}]", i),
+                        format!("expected 0 or more trips, got {}", describe(trips)),
+                    );
+                }
+            }
+        }
+    }
+
+    pub fn validate_timetable(
+        &mut self,
+        timetable_file: &str,
+        raw_timetable: &Value,
+        network_config: &NetworkConfig,
+    ) {
+        self.check_list_of_maps(
+            timetable_file,
+            raw_timetable,
+            TIMETABLE_LINE_KEYS,
+            REQUIRED_TIMETABLE_LINE_KEYS,
+        );
+        let empty = vec![];
+        let raw_lines_array = raw_timetable.as_sequence().unwrap_or(&empty);
+        for (i, raw_line) in raw_lines_array.iter().enumerate() {
+            let path = format!("[{}]", i);
+            if let Some(Value::String(line)) = get(raw_line, "line") {
+                let name = line.replace(&['+', '-'][..], "");
+                match try_transform_line_name_to_enum(&name) {
+                    Some(line_name) => {
+                        if !network_config
+                            .lines
+                            .iter()
+                            .any(|line| line.name == line_name)
+                        {
+                            self.push(
+                                timetable_file,
+                                &join_path(&path, "line"),
+                                format!("there is no line {}", name),
+                            );
+                        }
+                    }
+                    None => self.push(
+                        timetable_file,
+                        &join_path(&path, "line"),
+                        format!("{} is no line like u1+, t12- or s8+", line),
+                    ),
+                }
+                if line.matches(&['+', '-'][..]).count() != 1 {
+                    self.push(
+                        timetable_file,
+                        &join_path(&path, "line"),
+                        format!("{} needs exactly one direction, + or -", line),
+                    );
+                }
+            }
+            if let Some(Value::Sequence(trips)) = get(raw_line, "trips") {
+                for (j, trip) in trips.iter().enumerate() {
+                    self.check_required(
+                        timetable_file,
+                        &format!("{}.trips[{}]", path, j),
+                        trip,
+                        REQUIRED_TIMETABLE_TRIP_KEYS,
+                    );
+                }
+            }
+        }
+    }
+
+    // People can only get from one station to another if the lines connect them, so
+    // every station needs a line and the lines must not fall apart into separate parts
+    fn check_connected(
+        &mut self,
+        lines_file: &str,
+        station_ids: &Vec<i32>,
+        line_stations: &HashMap<LineName, Vec<i32>>,
+    ) {
+        let mut neighbours: HashMap<i32, HashSet<i32>> = HashMap::new();
+        for stations in line_stations.values() {
+            for pair in stations.windows(2) {
+                neighbours.entry(pair[0]).or_default().insert(pair[1]);
+                neighbours.entry(pair[1]).or_default().insert(pair[0]);
+            }
+        }
+
+        let mut unserved: Vec<i32> = station_ids
+            .iter()
+            .filter(|id| !neighbours.contains_key(id))
+            .copied()
+            .collect();
+        unserved.sort();
+        if !unserved.is_empty() {
+            self.push(
+                lines_file,
+                "",
+                format!(
+                    "{} stations are not served by any line: {}",
+                    unserved.len(),
+                    format_ids(&unserved)
+                ),
+            );
+        }
+
+        let mut unvisited: Vec<i32> = neighbours
+            .keys()
+            .filter(|id| station_ids.contains(id))
+            .copied()
+            .collect();
+        unvisited.sort();
+        let mut components: Vec<Vec<i32>> = vec![];
+        let mut visited: HashSet<i32> = HashSet::new();
+        for start in unvisited {
+            if visited.contains(&start) {
+                continue;
+            }
+            let mut component = vec![];
+            let mut stack = vec![start];
+            visited.insert(start);
+            while let Some(station_id) = stack.pop() {
+                component.push(station_id);
+                for neighbour in &neighbours[&station_id] {
+                    if visited.insert(*neighbour) {
+                        stack.push(*neighbour);
+                    }
+                }
+            }
+            component.sort();
+            components.push(component);
+        }
+        if components.len() > 1 {
+            for (i, component) in components.iter().enumerate() {
+                self.push(
+                    lines_file,
+                    "",
+                    format!(
+                        "the lines fall apart, part {} of {} has stations {}",
+                        i + 1,
+                        components.len(),
+                        format_ids(component)
+                    ),
+                );
+            }
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    // Prints everything that was found and stops, nothing is started with a broken config
+    pub fn exit_if_invalid(&self) {
+        if self.is_valid() {
+            return;
+        }
+        println!("the config has {} problems:", self.errors.len());
+        for error in &self.errors {
+            println!("  {}", error);
+        }
+        exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATIONS: &str = "
+- {id: 1, lat: 48.1, lon: 11.5}
+- {id: 2, lat: 48.2, lon: 11.5}
+- {id: 3, lat: 48.3, lon: 11.5, entrypoint_for: [u1+, u2-]}
+- {id: 4, lat: 48.4, lon: 11.5}
+";
+    const LINES: &str = "
+- {name: u1, stations: [1, 2, 3], distances: [1000, 1000]}
+- {name: u2, stations: [3, 4], distances: [1000]}
+";
+
+    fn yaml(text: &str) -> Value {
+        serde_yaml::from_str(text).unwrap()
+    }
+
+    fn get_errors(validator: &ConfigValidator) -> Vec<String> {
+        validator
+            .errors
+            .iter()
+            .map(|error| error.to_string())
+            .collect()
+    }
+
+    fn validate_network(stations: &str, lines: &str) -> Vec<String> {
+        let mut validator = ConfigValidator::new();
+        validator.validate_network("stations.yaml", &yaml(stations), "lines.yaml", &yaml(lines));
+        get_errors(&validator)
+    }
+
+    #[test]
+    fn unknown_keys() {
+        let mut validator = ConfigValidator::new();
+        validator.validate_config(
+            "config.yaml",
+            &yaml("logic:\n  pod_capacty: 90\nsound:\n  volume: 3\n"),
+        );
+        assert_eq!(
+            get_errors(&validator),
+            vec![
+                "config.yaml: logic.pod_capacty: unknown key, did you mean pod_capacity?",
+                "config.yaml: sound: unknown key",
+            ]
+        );
+    }
+
+    #[test]
+    fn type_mismatches() {
+        let mut validator = ConfigValidator::new();
+        validator.validate_config(
+            "config.yaml",
+            &yaml(
+                "general:\n  mode: fast\nlogic:\n  pod_capacity: 0\n  shuffle_people: 1\n  start_time: \"six\"\nvisual:\n  screen_size:\n    x: wide\n",
+            ),
+        );
+        assert_eq!(
+            get_errors(&validator),
+            vec![
+                "config.yaml: general.mode: expected one of visual, headless, import, sweep, got the text \"fast\"",
+                "config.yaml: logic.pod_capacity: expected an integer greater than 0, got the integer 0",
+                "config.yaml: logic.shuffle_people: expected true or false, got the integer 1",
+                "config.yaml: logic.start_time: expected a time of day like \"06:00\", got the text \"six\"",
+                "config.yaml: visual.screen_size.x: expected a number, got the text \"wide\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn valid_network() {
+        assert_eq!(validate_network(STATIONS, LINES), Vec::<String>::new());
+    }
+
+    #[test]
+    fn missing_stations() {
+        let lines = "
+- {name: u1, stations: [1, 2, 3], distances: [1000, 1000]}
+- {name: u2, stations: [3, 4, 9], distances: [1000, 1000]}
+";
+        assert_eq!(
+            validate_network(STATIONS, lines),
+            vec!["lines.yaml: [1].stations[2]: there is no station 9"]
+        );
+    }
+
+    #[test]
+    fn duplicate_ids() {
+        let stations = format!("{}- {{id: 2, lat: 48.5, lon: 11.5}}\n", STATIONS);
+        let lines = format!(
+            "{}- {{name: u2, stations: [1, 4], distances: [1000]}}\n",
+            LINES
+        );
+        assert_eq!(
+            validate_network(&stations, &lines),
+            vec![
+                "stations.yaml: [4].id: station 2 is defined more than once",
+                "lines.yaml: [2].name: line u2 is defined more than once",
+            ]
+        );
+    }
+
+    #[test]
+    fn distances_length() {
+        let lines = "
+- {name: u1, stations: [1, 2, 3], distances: [1000]}
+- {name: u2, stations: [3, 4], distances: [1000], circular: true}
+";
+        assert_eq!(
+            validate_network(STATIONS, lines),
+            vec![
+                "lines.yaml: [0].distances: 1 distances for 3 stations, a non circular line needs 2",
+                "lines.yaml: [1].distances: 1 distances for 2 stations, a circular line needs 2",
+            ]
+        );
+    }
+
+    #[test]
+    fn entrypoint_for() {
+        let stations = "
+- {id: 1, lat: 48.1, lon: 11.5, entrypoint_for: [u2+]}
+- {id: 2, lat: 48.2, lon: 11.5, entrypoint_for: [u5-]}
+- {id: 3, lat: 48.3, lon: 11.5, entrypoint_for: [x1+]}
+- {id: 4, lat: 48.4, lon: 11.5, entrypoint_for: [u2+-]}
+";
+        assert_eq!(
+            validate_network(stations, LINES),
+            vec![
+                "stations.yaml: [0].entrypoint_for[0]: u2 doesn't serve station 1",
+                "stations.yaml: [1].entrypoint_for[0]: there is no line u5",
+                "stations.yaml: [2].entrypoint_for[0]: x1+ is no line like u1+, t12- or s8+-",
+            ]
+        );
+    }
+
+    #[test]
+    fn disconnected_parts() {
+        let stations = format!(
+            "{}- {{id: 5, lat: 48.5, lon: 11.5}}\n- {{id: 6, lat: 48.6, lon: 11.5}}\n- {{id: 7, lat: 48.7, lon: 11.5}}\n",
+            STATIONS.replace(", entrypoint_for: [u1+, u2-]", "")
+        );
+        let lines = "
+- {name: u1, stations: [1, 2, 3], distances: [1000, 1000]}
+- {name: u2, stations: [4, 5], distances: [1000]}
+";
+        assert_eq!(
+            validate_network(&stations, lines),
+            vec![
+                "lines.yaml: 2 stations are not served by any line: 6, 7",
+                "lines.yaml: the lines fall apart, part 1 of 2 has stations 1, 2, 3",
+                "lines.yaml: the lines fall apart, part 2 of 2 has stations 4, 5",
+            ]
+        );
+    }
+}
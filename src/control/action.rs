@@ -1,8 +1,9 @@
 use crate::helper::enums::{Direction, LineName};
+use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 use std::time::Duration;
 
-#[derive(Debug, Clone, PartialEq, Default)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
 pub enum Action {
     #[default]
     NoAction,
@@ -84,10 +85,33 @@ pub enum Action {
     DumpMetricsPod {
         pod_id: i32,
     },
+    DumpMetricsStations {
+        all: bool,
+        avg: bool,
+    },
+    DumpMetricsStation {
+        station_id: i32,
+    },
+    DumpTrips,
+    DumpLinks,
     DumpConfig,
+    DumpGtfs,
+    SaveState {
+        path: String,
+    },
+    LoadState {
+        path: String,
+    },
+    LogEvents {
+        csv: bool,
+    },
+    StopLogEvents,
     Sleep {
         duration: Duration,
     },
+    SleepSim {
+        seconds: u32,
+    },
     Loop {
         n: u32,
     },
@@ -3,11 +3,13 @@ use crate::control::action::{Action, Actions};
 use crate::control::parsers::block::{parse_block, parse_unblock};
 use crate::control::parsers::dump::parse_dump;
 use crate::control::parsers::get::parse_get;
+use crate::control::parsers::log::parse_log;
 use crate::control::parsers::loops::parse_loop;
 use crate::control::parsers::make::parse_make;
 use crate::control::parsers::route::parse_route;
 use crate::control::parsers::sleep::parse_sleep;
 use crate::control::parsers::spawn::parse_spawn;
+use crate::control::parsers::state::{parse_load, parse_save};
 use crate::control::parsers::visualize::{parse_hide, parse_visualize};
 use crate::helper::functions::interpolate;
 use crate::helper::functions::read_lines;
@@ -18,17 +20,23 @@ use std::sync::mpsc;
 
 // TODO: implement all actions for all entities if possible to match text in Thesis
 
+// The startup command, parsed like a line typed into the cli
+pub fn parse_command_on_start(config: &Config) -> Actions {
+    let engine = Engine::new();
+    let interpolated_cmd = interpolate(&config.general.command_on_start, config, &engine);
+    let input_list = interpolated_cmd.split(" ").collect();
+    parse_input(&input_list, config, &engine)
+}
+
 pub fn run_cli(tx: mpsc::Sender<Actions>, config: Config) -> Result<()> {
     let engine = Engine::new();
+    let _res = tx.send(parse_command_on_start(&config));
+
     // `()` can be used when no completer is required
     let mut rl = Editor::<()>::new()?;
     if rl.load_history(".meta/history.txt").is_err() {
         println!("No previous history.");
     }
-    let interpolated_cmd = interpolate(&config.logic.command_on_start, &config, &engine);
-    let input_list = interpolated_cmd.split(" ").collect();
-    let actions = parse_input(&input_list, &config, &engine);
-    let _res = tx.send(actions);
 
     loop {
         let readline = rl.readline(">> ");
@@ -95,12 +103,21 @@ fn parse_input(input_list: &Vec<&str>, config: &Config, engine: &Engine) -> Acti
         "dump" | "d" => {
             actions.actions = parse_dump(&input_list);
         }
+        "save" => {
+            actions.actions = parse_save(&input_list);
+        }
+        "load" => {
+            actions.actions = parse_load(&input_list);
+        }
+        "log" => {
+            actions.actions = parse_log(&input_list);
+        }
         "run" => actions = run_script(&input_list, config, &engine),
         "gather" => {
             actions.actions = vec![Action::GatherMetrics];
         }
         "sleep" | "sl" => {
-            actions.actions = parse_sleep(&input_list, config);
+            actions.actions = parse_sleep(&input_list);
         }
         "loop" | "l" => {
             actions.actions = parse_loop(&input_list);
@@ -122,9 +139,14 @@ fn parse_input(input_list: &Vec<&str>, config: &Config, engine: &Engine) -> Acti
     actions
 }
 
+// Everything after run is the path, so paths with spaces work as well
 fn run_script(input_list: &Vec<&str>, config: &Config, engine: &Engine) -> Actions {
     let mut actions = Actions::new();
-    let lines_res = read_lines(input_list[1]);
+    if input_list.len() < 2 {
+        println!("Run what??");
+        return actions;
+    }
+    let lines_res = read_lines(input_list[1..].join(" "));
     match lines_res {
         Ok(lines) => {
             for line in lines {
@@ -2,3 +2,46 @@ pub mod action;
 pub mod cli;
 pub mod parsers;
 pub mod proxy;
+pub mod schedule;
+
+use crate::config::structs::Config;
+use crate::control::action::Actions;
+use crate::control::cli::{parse_command_on_start, run_cli};
+use crate::control::proxy::{run_proxy, send_actions, ActionProxy};
+use std::sync::mpsc;
+use std::thread;
+
+// Starts the cli and the action proxy for one simulation, the state reads what they
+// send from the returned receiver.
+pub fn spawn_control(config: &Config) -> mpsc::Receiver<Actions> {
+    let (proxy_tx, proxy_rx) = mpsc::channel();
+    let (tx, rx) = mpsc::channel();
+
+    let config_for_cli = config.clone();
+    thread::spawn(|| {
+        let _res = run_cli(proxy_tx, config_for_cli);
+    });
+
+    thread::spawn(|| {
+        run_proxy(proxy_rx, tx);
+    });
+    rx
+}
+
+// Without a window there is usually no terminal either, so the startup command is the
+// only input. It is queued right away, no cli or proxy thread is needed and every run
+// has a queue of its own. It has to end in kill, otherwise the run never stops.
+pub fn queue_command_on_start(config: &Config) -> mpsc::Receiver<Actions> {
+    let (tx, rx) = mpsc::channel();
+    if config.general.command_on_start.is_empty() {
+        println!("Running headless without command_on_start, there is nothing to run.");
+        return rx;
+    }
+    let mut proxy = ActionProxy::new();
+    let mut actions = proxy
+        .process(parse_command_on_start(config))
+        .unwrap_or_else(Actions::new);
+    actions.actions.extend(proxy.flush().actions);
+    send_actions(&tx, actions);
+    rx
+}
@@ -57,7 +57,34 @@ pub fn parse_dump(input_list: &Vec<&str>) -> Vec<Action> {
                 }
             }
         }
+        "station" | "stations" => {
+            let mut avg = false;
+            if input_list.contains(&"--avg") {
+                avg = true;
+            }
+
+            let mut all = false;
+            if input_list.contains(&"--all") {
+                all = true;
+            }
+
+            if all || avg {
+                actions.push(Action::DumpMetricsStations { all: all, avg: avg });
+                return actions;
+            }
+
+            // else
+            for arg in &input_list[2..] {
+                let ids = parse_id_list_and_ranges(arg);
+                for id in ids {
+                    actions.push(Action::DumpMetricsStation { station_id: id })
+                }
+            }
+        }
+        "trip" | "trips" => actions.push(Action::DumpTrips),
+        "link" | "links" => actions.push(Action::DumpLinks),
         "config" => actions.push(Action::DumpConfig),
+        "gtfs" => actions.push(Action::DumpGtfs),
         _ => {
             println!("Can't dump: {}, not implemented.", input_list[1])
         }
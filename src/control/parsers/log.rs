@@ -0,0 +1,26 @@
+use crate::control::action::Action;
+
+// log events [--csv] and log off
+pub fn parse_log(input_list: &Vec<&str>) -> Vec<Action> {
+    let mut actions: Vec<Action> = vec![];
+    if input_list.len() < 2 {
+        println!("Log what??");
+        return actions;
+    }
+
+    match input_list[1] {
+        "events" => {
+            let mut csv = false;
+            if input_list.contains(&"--csv") {
+                csv = true;
+            }
+            actions.push(Action::LogEvents { csv: csv });
+        }
+        "off" => actions.push(Action::StopLogEvents),
+        _ => {
+            println!("Can't log: {}, not implemented.", input_list[1])
+        }
+    }
+
+    return actions;
+}
@@ -1,10 +1,12 @@
 pub mod block;
 pub mod dump;
 pub mod get;
+pub mod log;
 pub mod loops;
 pub mod make;
 pub mod route;
 pub mod shared;
 pub mod sleep;
 pub mod spawn;
+pub mod state;
 pub mod visualize;
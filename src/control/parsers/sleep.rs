@@ -1,9 +1,8 @@
-use crate::config::structs::Config;
 use crate::control::action::Action;
 use std::str::FromStr;
 use std::time::Duration;
 
-pub fn parse_sleep(input_list: &Vec<&str>, config: &Config) -> Vec<Action> {
+pub fn parse_sleep(input_list: &Vec<&str>) -> Vec<Action> {
     let mut actions: Vec<Action> = vec![];
     if input_list.len() < 3 {
         println!("sleep how long in which mode??");
@@ -20,10 +19,8 @@ pub fn parse_sleep(input_list: &Vec<&str>, config: &Config) -> Vec<Action> {
                     duration: Duration::from_secs(seconds),
                 }];
             } else if timing == "sim" {
-                actions = vec![Action::Sleep {
-                    duration: Duration::from_millis(
-                        (seconds * 1000) / config.logic.speed_multiplier as u64,
-                    ),
+                actions = vec![Action::SleepSim {
+                    seconds: seconds as u32,
                 }];
             }
         }
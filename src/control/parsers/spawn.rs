@@ -4,7 +4,6 @@ use crate::helper::enums::Direction;
 use crate::helper::enums::LineName;
 use crate::helper::functions::parse_str_to_line_and_directions;
 use std::str::FromStr;
-use std::time::Duration;
 
 pub fn parse_spawn(input_list: &Vec<&str>, config: &Config) -> Vec<Action> {
     let mut actions: Vec<Action> = vec![];
@@ -97,10 +96,12 @@ fn generate_actions_to_fill_line(
                 }
 
                 let time_interval = travel_time / n_pods;
-                println!(
-                    "line_name: {:?} - travel_time: {} - ideal_time_interval: {} - n_pods: {} - time_interval: {}",
-                    line_name, travel_time, ideal_time_interval, n_pods, time_interval
-                );
+                if !config.general.quiet {
+                    println!(
+                        "line_name: {:?} - travel_time: {} - ideal_time_interval: {} - n_pods: {} - time_interval: {}",
+                        line_name, travel_time, ideal_time_interval, n_pods, time_interval
+                    );
+                }
 
                 for _ in 0..n_pods {
                     for direction in &directions {
@@ -111,10 +112,8 @@ fn generate_actions_to_fill_line(
                             force: false,
                         })
                     }
-                    actions.push(Action::Sleep {
-                        duration: Duration::from_millis(
-                            (time_interval as u64 * 1000) / config.logic.speed_multiplier as u64,
-                        ),
+                    actions.push(Action::SleepSim {
+                        seconds: time_interval as u32,
                     })
                 }
             } else {
@@ -125,10 +124,12 @@ fn generate_actions_to_fill_line(
                 }
 
                 let time_interval = travel_time / n_pods;
-                println!(
-                    "line_name: {:?} - travel_time: {} - ideal_time_interval: {} - n_pods: {} - time_interval: {}",
-                    line_name, travel_time, ideal_time_interval, n_pods, time_interval
-                );
+                if !config.general.quiet {
+                    println!(
+                        "line_name: {:?} - travel_time: {} - ideal_time_interval: {} - n_pods: {} - time_interval: {}",
+                        line_name, travel_time, ideal_time_interval, n_pods, time_interval
+                    );
+                }
 
                 for _ in 0..n_pods {
                     for direction in &directions {
@@ -145,10 +146,8 @@ fn generate_actions_to_fill_line(
                             force: false,
                         })
                     }
-                    actions.push(Action::Sleep {
-                        duration: Duration::from_millis(
-                            (time_interval as u64 * 1000) / config.logic.speed_multiplier as u64,
-                        ),
+                    actions.push(Action::SleepSim {
+                        seconds: time_interval as u32,
                     })
                 }
             }
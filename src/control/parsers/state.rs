@@ -0,0 +1,27 @@
+use crate::control::action::Action;
+
+// save state <file> and load state <file>
+pub fn parse_save(input_list: &Vec<&str>) -> Vec<Action> {
+    let mut actions: Vec<Action> = vec![];
+    match parse_state_file(input_list) {
+        Some(path) => actions.push(Action::SaveState { path: path }),
+        None => println!("save state to which file??"),
+    }
+    return actions;
+}
+
+pub fn parse_load(input_list: &Vec<&str>) -> Vec<Action> {
+    let mut actions: Vec<Action> = vec![];
+    match parse_state_file(input_list) {
+        Some(path) => actions.push(Action::LoadState { path: path }),
+        None => println!("load state from which file??"),
+    }
+    return actions;
+}
+
+fn parse_state_file(input_list: &Vec<&str>) -> Option<String> {
+    if input_list.len() < 3 || input_list[1] != "state" || input_list[2].is_empty() {
+        return None;
+    }
+    Some(input_list[2].to_string())
+}
@@ -1,104 +1,106 @@
 use crate::control::action::{Action, Actions};
 use std::sync::mpsc;
-use std::thread;
 use std::thread::sleep;
 use std::time::Duration;
 
-pub fn run_proxy(rx: mpsc::Receiver<Actions>, tx: mpsc::Sender<Actions>) {
-    let mut action_buffer = Actions::new();
-    let mut loop_buffer = Actions::new();
-    let mut conc_buffer = Actions::new();
-    let mut in_loop = false;
-    let mut in_conc = false;
-    let mut loop_n = 1;
-    loop {
-        sleep(Duration::from_millis(10)); // This doesn't make a difference in terms of use but saves the thread some work
-        let actions = recv_queries(&rx);
+// Unrolls loops and holds back open blocks until the rest of their input arrived.
+// Used by the proxy thread and directly for the startup command of headless runs.
+#[derive(Debug)]
+pub struct ActionProxy {
+    action_buffer: Actions,
+    loop_buffer: Actions,
+    in_loop: bool,
+    in_conc: bool,
+    loop_n: u32,
+}
+
+impl ActionProxy {
+    pub fn new() -> Self {
+        ActionProxy {
+            action_buffer: Actions::new(),
+            loop_buffer: Actions::new(),
+            in_loop: false,
+            in_conc: false,
+            loop_n: 1,
+        }
+    }
+
+    // Everything received so far is handed out as one message, so sim sleeps keep
+    // applying to the actions behind them. None while a block is still open.
+    pub fn process(&mut self, actions: Actions) -> Option<Actions> {
         for action in actions.actions {
             match action {
                 Action::Loop { n } => {
-                    send_actions(tx.clone(), action_buffer.clone());
-                    action_buffer = Actions::new();
-                    loop_buffer = Actions::new();
-                    in_loop = true;
-                    loop_n = n;
+                    self.loop_buffer = Actions::new();
+                    self.in_loop = true;
+                    self.loop_n = n;
                 }
                 Action::Endloop => {
-                    // let mut new_action_buffer = Actions::new();
-                    if in_conc {
-                        for _ in 0..loop_n {
-                            conc_buffer.actions.extend(loop_buffer.actions.clone());
-                        }
-                    } else {
-                        for _ in 0..loop_n {
-                            action_buffer.actions.extend(loop_buffer.actions.clone());
-                        }
-                        // send_actions(tx.clone(), action_buffer.clone());
-                    }
-                    // println!("conc_buffer endloop: {:?}", &conc_buffer);
-                    // println!("loop_buffer endloop: {:?}", &loop_buffer);
-                    // println!("loop_n: {}", loop_n);
-                    // action_buffer = Actions::new();
-                    loop_buffer = Actions::new();
-                    in_loop = false;
-                    loop_n = 1;
-                }
-                Action::StartConcurency => {
-                    // send_actions(tx.clone(), action_buffer.clone());
-                    action_buffer = Actions::new();
-                    conc_buffer = Actions::new();
-                    in_conc = true;
-                }
-                Action::DoConcurrently => {
-                    if in_conc {
-                        println!("conc_buffer: {:?}", &conc_buffer);
-                        send_actions_conc(tx.clone(), conc_buffer);
-                        conc_buffer = Actions::new();
-                    }
-                }
-                Action::EndConcurency => {
-                    if in_conc {
-                        send_actions_conc(tx.clone(), conc_buffer);
-                        conc_buffer = Actions::new();
-                        in_conc = false;
+                    for _ in 0..self.loop_n {
+                        self.action_buffer
+                            .actions
+                            .extend(self.loop_buffer.actions.clone());
                     }
+                    self.loop_buffer = Actions::new();
+                    self.in_loop = false;
+                    self.loop_n = 1;
                 }
+                // The concurrency markers are forwarded, the action schedule splits
+                // the block into timelines that start at the same simulated second.
                 action => {
-                    if in_loop {
-                        loop_buffer.actions.push(action);
-                    } else if in_conc {
-                        conc_buffer.actions.push(action);
+                    match action {
+                        Action::StartConcurency => self.in_conc = true,
+                        Action::EndConcurency => self.in_conc = false,
+                        _ => {}
+                    }
+                    if self.in_loop {
+                        self.loop_buffer.actions.push(action);
                     } else {
-                        action_buffer.actions.push(action);
+                        self.action_buffer.actions.push(action);
                     }
                 }
             }
         }
-        send_actions(tx.clone(), action_buffer.clone());
-        action_buffer = Actions::new();
+        if self.in_loop || self.in_conc {
+            return None;
+        }
+        Some(std::mem::replace(&mut self.action_buffer, Actions::new()))
+    }
+
+    // What is left of open blocks once no more input can come
+    pub fn flush(self) -> Actions {
+        let mut actions = self.action_buffer;
+        actions.actions.extend(self.loop_buffer.actions);
+        actions
     }
 }
 
-fn send_actions_conc(tx: mpsc::Sender<Actions>, actions: Actions) {
-    thread::spawn(|| send_actions(tx, actions));
+pub fn run_proxy(rx: mpsc::Receiver<Actions>, tx: mpsc::Sender<Actions>) {
+    let mut proxy = ActionProxy::new();
+    loop {
+        sleep(Duration::from_millis(10)); // This doesn't make a difference in terms of use but saves the thread some work
+        let (actions, hung_up) = recv_queries(&rx);
+        if let Some(ready) = proxy.process(actions) {
+            if !send_actions(&tx, ready) {
+                return;
+            }
+        }
+        // Nothing more can come in, the cli ended. What is left is passed on before
+        // the thread ends.
+        if hung_up {
+            send_actions(&tx, proxy.flush());
+            return;
+        }
+    }
 }
 
-fn send_actions(tx: mpsc::Sender<Actions>, actions: Actions) {
+// False if the simulation is gone
+pub fn send_actions(tx: &mpsc::Sender<Actions>, actions: Actions) -> bool {
     let mut action_buffer = Actions::new();
     for action in actions.actions {
         match action {
-            Action::Sleep { duration } => {
-                if !action_buffer.actions.is_empty() {
-                    let _res = tx.send(action_buffer.clone());
-                    action_buffer = Actions::new();
-                }
-
-                println!("commit actions, then sleep for {:?}", duration);
-                sleep(duration);
-            }
             Action::KillSimulation { code: _ } => {
                 action_buffer.actions.push(action);
-                let _res = tx.send(action_buffer.clone());
                 break;
             }
             action => {
@@ -107,28 +109,31 @@ fn send_actions(tx: mpsc::Sender<Actions>, actions: Actions) {
         }
     }
     if !action_buffer.actions.is_empty() {
-        let _res = tx.send(action_buffer.clone());
+        return tx.send(action_buffer).is_ok();
     }
+    true
 }
 
-pub fn recv_queries(rx: &mpsc::Receiver<Actions>) -> Actions {
+// The flag is set once the sender is dropped and everything sent was received
+pub fn recv_queries(rx: &mpsc::Receiver<Actions>) -> (Actions, bool) {
     let maybe_received = rx.try_recv();
     match maybe_received {
         Ok(received) => {
-            return received;
+            return (received, false);
+        }
+        Err(mpsc::TryRecvError::Disconnected) => {
+            return (Actions::new(), true);
         }
-        Err(_) => {}
+        Err(mpsc::TryRecvError::Empty) => {}
     }
-    return Actions::new();
+    return (Actions::new(), false);
 }
 
-pub fn recv_actions(rx: &mpsc::Receiver<Actions>) -> Actions {
-    let maybe_received = rx.try_recv();
-    match maybe_received {
-        Ok(received) => {
-            return received;
-        }
-        Err(_) => {}
+// Every message is scheduled as its own timeline, so they are not merged here.
+pub fn recv_actions(rx: &mpsc::Receiver<Actions>) -> Vec<Actions> {
+    let mut received = vec![];
+    while let Ok(actions) = rx.try_recv() {
+        received.push(actions);
     }
-    return Actions::new();
+    return received;
 }
@@ -0,0 +1,230 @@
+use crate::control::action::{Action, Actions};
+use std::collections::VecDeque;
+use std::time::Instant;
+
+// Every message that reaches the state becomes a timeline. A timeline hands out its
+// actions until it hits a sleep and continues once the sleep is over. Sim sleeps are
+// measured in time_passed, so they hold while the simulation is paused and do not
+// depend on the speed multiplier. Real sleeps are measured with the wall clock.
+#[derive(Debug)]
+struct Timeline {
+    actions: VecDeque<Action>,
+    due_sim: u32,
+    due_real: Option<Instant>,
+}
+
+impl Timeline {
+    fn new(actions: Vec<Action>, time_passed: u32) -> Self {
+        Timeline {
+            actions: VecDeque::from(actions),
+            due_sim: time_passed,
+            due_real: None,
+        }
+    }
+
+    fn is_finished(&self) -> bool {
+        self.actions.is_empty() && self.due_real.is_none()
+    }
+
+    fn advance(&mut self, time_passed: u32, due: &mut Actions, branches: &mut Vec<Timeline>) {
+        loop {
+            if time_passed < self.due_sim {
+                return;
+            }
+            if let Some(due_real) = self.due_real {
+                if Instant::now() < due_real {
+                    return;
+                }
+                self.due_real = None;
+                // Sim sleeps after a real one start when it is over
+                self.due_sim = time_passed;
+            }
+            match self.actions.pop_front() {
+                // From when the timeline was due, so chained sleeps don't drift if a pop is late
+                Some(Action::SleepSim { seconds }) => {
+                    self.due_sim += seconds;
+                }
+                Some(Action::Sleep { duration }) => {
+                    self.due_real = Some(Instant::now() + duration);
+                }
+                Some(Action::StartConcurency) => {
+                    for branch in self.split_concurrent_block() {
+                        branches.push(Timeline::new(branch, self.due_sim));
+                    }
+                }
+                Some(action) => due.actions.push(action),
+                None => return,
+            }
+        }
+    }
+
+    // Takes everything up to the matching EndConcurency out of the timeline. Each part
+    // between <&> starts now, the timeline itself continues right after the block.
+    fn split_concurrent_block(&mut self) -> Vec<Vec<Action>> {
+        let mut branches = vec![];
+        let mut branch = vec![];
+        while let Some(action) = self.actions.pop_front() {
+            match action {
+                Action::DoConcurrently => {
+                    branches.push(branch);
+                    branch = vec![];
+                }
+                Action::EndConcurency => break,
+                action => branch.push(action),
+            }
+        }
+        branches.push(branch);
+        branches
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ActionSchedule {
+    timelines: Vec<Timeline>,
+}
+
+impl ActionSchedule {
+    pub fn new() -> Self {
+        ActionSchedule { timelines: vec![] }
+    }
+
+    pub fn schedule(&mut self, actions: Actions, time_passed: u32) {
+        if !actions.actions.is_empty() {
+            self.timelines
+                .push(Timeline::new(actions.actions, time_passed));
+        }
+    }
+
+    // time_passed jumps when a snapshot is loaded, running sim sleeps keep the time
+    // they have left
+    pub fn shift_sim(&mut self, from: u32, to: u32) {
+        for timeline in &mut self.timelines {
+            timeline.due_sim = to + timeline.due_sim.saturating_sub(from);
+        }
+    }
+
+    pub fn pop_due(&mut self, time_passed: u32) -> Actions {
+        let mut due = Actions::new();
+        let mut i = 0;
+        // Branches started by a concurrent block are appended and advanced in the same pass
+        while i < self.timelines.len() {
+            let mut branches = vec![];
+            self.timelines[i].advance(time_passed, &mut due, &mut branches);
+            self.timelines.extend(branches);
+            i += 1;
+        }
+        self.timelines.retain(|timeline| !timeline.is_finished());
+        due
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn show(id: i32) -> Action {
+        Action::ShowPerson { id: id }
+    }
+
+    fn sleep_sim(seconds: u32) -> Action {
+        Action::SleepSim { seconds: seconds }
+    }
+
+    fn schedule(actions: Vec<Action>, time_passed: u32) -> ActionSchedule {
+        let mut action_schedule = ActionSchedule::new();
+        action_schedule.schedule(Actions { actions: actions }, time_passed);
+        action_schedule
+    }
+
+    #[test]
+    fn sleeps_add_up() {
+        let mut action_schedule = schedule(
+            vec![show(1), sleep_sim(10), show(2), sleep_sim(5), show(3)],
+            0,
+        );
+        assert_eq!(action_schedule.pop_due(0).actions, vec![show(1)]);
+        assert!(action_schedule.pop_due(9).actions.is_empty());
+        assert_eq!(action_schedule.pop_due(10).actions, vec![show(2)]);
+        assert!(action_schedule.pop_due(14).actions.is_empty());
+        assert_eq!(action_schedule.pop_due(15).actions, vec![show(3)]);
+        assert!(action_schedule.timelines.is_empty());
+    }
+
+    #[test]
+    fn sleeps_in_a_block_after_a_sleep() {
+        let mut action_schedule = schedule(
+            vec![
+                sleep_sim(10),
+                Action::StartConcurency,
+                sleep_sim(5),
+                show(1),
+                Action::DoConcurrently,
+                show(2),
+                Action::EndConcurency,
+            ],
+            0,
+        );
+        assert!(action_schedule.pop_due(0).actions.is_empty());
+        assert_eq!(action_schedule.pop_due(10).actions, vec![show(2)]);
+        assert_eq!(action_schedule.pop_due(15).actions, vec![show(1)]);
+    }
+
+    #[test]
+    fn branches_run_in_parallel() {
+        let mut action_schedule = schedule(
+            vec![
+                Action::StartConcurency,
+                show(1),
+                sleep_sim(10),
+                show(2),
+                Action::DoConcurrently,
+                show(3),
+                sleep_sim(5),
+                show(4),
+                Action::EndConcurency,
+                show(5),
+            ],
+            0,
+        );
+        // The timeline goes on right after the block, the branches start with it
+        assert_eq!(
+            action_schedule.pop_due(0).actions,
+            vec![show(5), show(1), show(3)]
+        );
+        assert_eq!(action_schedule.pop_due(5).actions, vec![show(4)]);
+        assert_eq!(action_schedule.pop_due(10).actions, vec![show(2)]);
+        assert!(action_schedule.timelines.is_empty());
+    }
+
+    #[test]
+    fn due_in_the_same_second() {
+        let mut action_schedule = schedule(vec![sleep_sim(3), show(1)], 0);
+        assert!(action_schedule.pop_due(0).actions.is_empty());
+        action_schedule.schedule(
+            Actions {
+                actions: vec![sleep_sim(2), show(2), show(3)],
+            },
+            1,
+        );
+        assert!(action_schedule.pop_due(1).actions.is_empty());
+        assert!(action_schedule.pop_due(2).actions.is_empty());
+        // In the order they were scheduled
+        assert_eq!(
+            action_schedule.pop_due(3).actions,
+            vec![show(1), show(2), show(3)]
+        );
+    }
+
+    #[test]
+    fn late_pop_delivers_everything_due() {
+        let mut action_schedule = schedule(vec![sleep_sim(3), show(1), sleep_sim(3), show(2)], 0);
+        assert!(action_schedule.pop_due(0).actions.is_empty());
+        // The second sleep counts from 3, when the first one was over
+        assert_eq!(action_schedule.pop_due(5).actions, vec![show(1)]);
+        assert_eq!(action_schedule.pop_due(6).actions, vec![show(2)]);
+
+        let mut action_schedule = schedule(vec![sleep_sim(3), show(1), sleep_sim(3), show(2)], 0);
+        assert!(action_schedule.pop_due(0).actions.is_empty());
+        assert_eq!(action_schedule.pop_due(9).actions, vec![show(1), show(2)]);
+    }
+}
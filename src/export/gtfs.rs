@@ -0,0 +1,347 @@
+use crate::config::structs::Config;
+use crate::helper::enums::{Direction, LineName};
+use crate::network::Network;
+use crate::pod::podsbox::PodsBox;
+use crate::pod::stopevent::StopEvent;
+use chrono::Utc;
+use csv::Writer;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+const AGENCY_ID: &str = "publicflow";
+// All trips happen on the day of the run
+const SERVICE_ID: &str = "simulation";
+
+// GTFS times count from the start of the service day and may go past 24:00:00
+fn format_gtfs_time(seconds: u32) -> String {
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        (seconds % 3600) / 60,
+        seconds % 60
+    )
+}
+
+fn get_route_type(line_name: &LineName) -> i32 {
+    match line_name {
+        LineName::T(_) => 0,
+        LineName::U(_) => 1,
+        LineName::S(_) => 2,
+        LineName::Placeholder => 3,
+    }
+}
+
+fn get_direction_id(direction: &Direction) -> i32 {
+    match direction {
+        Direction::Pos => 0,
+        Direction::Neg => 1,
+    }
+}
+
+// A pod runs as one trip until it changes direction or comes by a station of the
+// trip again. Pods change direction when they arrive at the end of the line and pods
+// of a circular line get back to where the round started, that stop ends one trip
+// and starts the next. Pods that turn back stop again at the other platform, which
+// starts the next.
+fn split_into_trips(stop_events: &Vec<StopEvent>) -> Vec<Vec<&StopEvent>> {
+    let mut trips: Vec<Vec<&StopEvent>> = vec![];
+    let mut current: Vec<&StopEvent> = vec![];
+    let mut visited: HashSet<i32> = HashSet::new();
+    for stop_event in stop_events {
+        let mut ends_trip = false;
+        let mut starts_new_trip = visited.contains(&stop_event.station_id);
+        if let Some(first) = current.first() {
+            ends_trip = first.station_id == stop_event.station_id;
+        }
+        if let Some(last) = current.last() {
+            if last.direction != stop_event.direction {
+                ends_trip = last.station_id != stop_event.station_id;
+                starts_new_trip = true;
+            }
+        }
+        if ends_trip {
+            current.push(stop_event);
+        }
+        if starts_new_trip {
+            trips.push(current);
+            current = vec![];
+            visited.clear();
+        }
+        visited.insert(stop_event.station_id);
+        current.push(stop_event);
+    }
+    trips.push(current);
+    // A trip needs at least two stops to be one
+    trips.retain(|trip| trip.len() >= 2);
+    trips
+}
+
+fn write_file(
+    zip: &mut ZipWriter<File>,
+    name: &str,
+    records: Vec<Vec<String>>,
+) -> Result<(), String> {
+    let mut writer = Writer::from_writer(vec![]);
+    for record in records {
+        writer
+            .write_record(&record)
+            .map_err(|e| format!("can't write {}: {}", name, e))?;
+    }
+    let content = writer
+        .into_inner()
+        .map_err(|e| format!("can't write {}: {}", name, e))?;
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    zip.start_file(name, options)
+        .map_err(|e| format!("can't add {}: {}", name, e))?;
+    zip.write_all(&content)
+        .map_err(|e| format!("can't write {}: {}", name, e))?;
+    Ok(())
+}
+
+fn to_record(fields: &[&str]) -> Vec<String> {
+    fields.iter().map(|field| field.to_string()).collect()
+}
+
+// Writes the network and the stops every pod made so far as a GTFS feed, so a run
+// can be replayed in the usual transit tools. Stations become stops, lines become
+// routes and the runs of the pods become trips.
+pub fn write_gtfs(
+    path: &Path,
+    network: &Network,
+    pods_box: &PodsBox,
+    config: &Config,
+) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| format!("can't create {:?}: {}", path, e))?;
+    let mut zip = ZipWriter::new(file);
+
+    let agency = vec![
+        to_record(&["agency_id", "agency_name", "agency_url", "agency_timezone"]),
+        to_record(&[
+            AGENCY_ID,
+            "PublicFlow",
+            "https://github.com/deeliciouscode/publicflow",
+            "Europe/Berlin",
+        ]),
+    ];
+    write_file(&mut zip, "agency.txt", agency)?;
+
+    // The towns store the longitude as lat, see StationConfig
+    let mut stops = vec![to_record(&["stop_id", "stop_name", "stop_lat", "stop_lon"])];
+    for station in &network.stations {
+        stops.push(vec![
+            station.id.to_string(),
+            station.name.clone(),
+            station.coordinates.1.to_string(),
+            station.coordinates.0.to_string(),
+        ]);
+    }
+    write_file(&mut zip, "stops.txt", stops)?;
+
+    let mut routes = vec![to_record(&[
+        "route_id",
+        "agency_id",
+        "route_short_name",
+        "route_type",
+    ])];
+    for line in &network.lines {
+        let route_id = line.name.to_config_string();
+        routes.push(vec![
+            route_id.clone(),
+            AGENCY_ID.to_string(),
+            route_id.to_uppercase(),
+            get_route_type(&line.name).to_string(),
+        ]);
+    }
+    write_file(&mut zip, "routes.txt", routes)?;
+
+    let date = config
+        .timestamp_run
+        .unwrap_or_else(Utc::now)
+        .format("%Y%m%d")
+        .to_string();
+    let calendar = vec![
+        to_record(&[
+            "service_id",
+            "monday",
+            "tuesday",
+            "wednesday",
+            "thursday",
+            "friday",
+            "saturday",
+            "sunday",
+            "start_date",
+            "end_date",
+        ]),
+        to_record(&[SERVICE_ID, "1", "1", "1", "1", "1", "1", "1", &date, &date]),
+    ];
+    write_file(&mut zip, "calendar.txt", calendar)?;
+
+    let mut trips = vec![to_record(&[
+        "route_id",
+        "service_id",
+        "trip_id",
+        "direction_id",
+    ])];
+    let mut stop_times = vec![to_record(&[
+        "trip_id",
+        "arrival_time",
+        "departure_time",
+        "stop_id",
+        "stop_sequence",
+    ])];
+    let mut n_trips = 0;
+    for pod in &pods_box.pods {
+        let route_id = pod.line_state.line.name.to_config_string();
+        for (n, trip) in split_into_trips(&pod.stop_events).iter().enumerate() {
+            let trip_id = format!("pod{}_{}", pod.id, n);
+            trips.push(vec![
+                route_id.clone(),
+                SERVICE_ID.to_string(),
+                trip_id.clone(),
+                get_direction_id(&trip[0].direction).to_string(),
+            ]);
+            for (sequence, stop_event) in trip.iter().enumerate() {
+                // A pod that is still in the station or retired there never departed
+                let departure = stop_event.departure.unwrap_or(stop_event.arrival);
+                stop_times.push(vec![
+                    trip_id.clone(),
+                    format_gtfs_time(config.logic.start_time + stop_event.arrival),
+                    format_gtfs_time(config.logic.start_time + departure),
+                    stop_event.station_id.to_string(),
+                    sequence.to_string(),
+                ]);
+            }
+            n_trips += 1;
+        }
+    }
+    write_file(&mut zip, "trips.txt", trips)?;
+    write_file(&mut zip, "stop_times.txt", stop_times)?;
+
+    zip.finish()
+        .map_err(|e| format!("can't finish {:?}: {}", path, e))?;
+    if !config.general.quiet {
+        println!("exported {} trips", n_trips);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Stops 10 seconds apart, the pod stays 5 seconds in each
+    fn get_stop_events(stops: &[(i32, Direction)]) -> Vec<StopEvent> {
+        stops
+            .iter()
+            .enumerate()
+            .map(|(i, (station_id, direction))| StopEvent {
+                station_id: *station_id,
+                direction: direction.clone(),
+                arrival: i as u32 * 10,
+                departure: Some(i as u32 * 10 + 5),
+            })
+            .collect()
+    }
+
+    // The stations and the arrivals of each trip
+    fn get_trips(stop_events: &Vec<StopEvent>) -> Vec<Vec<(i32, u32)>> {
+        split_into_trips(stop_events)
+            .iter()
+            .map(|trip| {
+                trip.iter()
+                    .map(|stop_event| (stop_event.station_id, stop_event.arrival))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn ends_of_the_line() {
+        // The pod already heads back when it arrives at an end
+        let stop_events = get_stop_events(&[
+            (1, Direction::Pos),
+            (2, Direction::Pos),
+            (3, Direction::Neg),
+            (2, Direction::Neg),
+            (1, Direction::Pos),
+            (2, Direction::Pos),
+        ]);
+        assert_eq!(
+            get_trips(&stop_events),
+            vec![
+                vec![(1, 0), (2, 10), (3, 20)],
+                vec![(3, 20), (2, 30), (1, 40)],
+                vec![(1, 40), (2, 50)],
+            ]
+        );
+        let directions: Vec<i32> = split_into_trips(&stop_events)
+            .iter()
+            .map(|trip| get_direction_id(&trip[0].direction))
+            .collect();
+        assert_eq!(directions, vec![0, 1, 0]);
+    }
+
+    #[test]
+    fn turn_back() {
+        // The connection after 3 is blocked, the pod stops at the other platform of 3
+        let stop_events = get_stop_events(&[
+            (1, Direction::Pos),
+            (2, Direction::Pos),
+            (3, Direction::Pos),
+            (3, Direction::Neg),
+            (2, Direction::Neg),
+            (1, Direction::Pos),
+        ]);
+        assert_eq!(
+            get_trips(&stop_events),
+            vec![
+                vec![(1, 0), (2, 10), (3, 20)],
+                vec![(3, 30), (2, 40), (1, 50)],
+            ]
+        );
+    }
+
+    #[test]
+    fn circular_line() {
+        let stop_events = get_stop_events(&[
+            (1, Direction::Pos),
+            (2, Direction::Pos),
+            (3, Direction::Pos),
+            (1, Direction::Pos),
+            (2, Direction::Pos),
+            (3, Direction::Pos),
+            (1, Direction::Pos),
+        ]);
+        assert_eq!(
+            get_trips(&stop_events),
+            vec![
+                vec![(1, 0), (2, 10), (3, 20), (1, 30)],
+                vec![(1, 30), (2, 40), (3, 50), (1, 60)],
+            ]
+        );
+    }
+
+    #[test]
+    fn open_departures() {
+        // Still in the station it was spawned in, there is no trip yet
+        let mut stop_events = get_stop_events(&[(1, Direction::Pos)]);
+        stop_events[0].departure = None;
+        assert_eq!(get_trips(&stop_events), Vec::<Vec<(i32, u32)>>::new());
+
+        // Retired at the end of the line, the last stop has no departure
+        let mut stop_events = get_stop_events(&[
+            (1, Direction::Pos),
+            (2, Direction::Pos),
+            (3, Direction::Neg),
+        ]);
+        stop_events[2].departure = None;
+        let trips = split_into_trips(&stop_events);
+        assert_eq!(trips.len(), 1);
+        assert_eq!(trips[0].len(), 3);
+        assert_eq!(trips[0][2].departure, None);
+    }
+}
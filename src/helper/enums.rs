@@ -1,4 +1,9 @@
-#[derive(Debug, Clone, Hash)]
+use crate::helper::functions::try_transform_line_name_to_enum;
+use serde::{Deserialize, Serialize};
+
+// Written as in the yamls and scripts, e.g. u1
+#[derive(Debug, Clone, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
 pub enum LineName {
     Placeholder,
     U(i32),
@@ -20,6 +25,23 @@ impl PartialEq for LineName {
 
 impl Eq for LineName {}
 
+impl TryFrom<String> for LineName {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        match try_transform_line_name_to_enum(&name) {
+            Some(line_name) => Ok(line_name),
+            None => Err(format!("{} is no line name like u1, t12 or s8", name)),
+        }
+    }
+}
+
+impl From<LineName> for String {
+    fn from(line_name: LineName) -> Self {
+        line_name.to_config_string()
+    }
+}
+
 impl LineName {
     pub fn in_same_line_class(&self, other: &Self) -> bool {
         match (self, other) {
@@ -30,9 +52,31 @@ impl LineName {
             _ => false,
         }
     }
+
+    // The name as it is written in the yamls and scripts, e.g. u1
+    pub fn to_config_string(&self) -> String {
+        match self {
+            LineName::U(id) => format!("u{}", id),
+            LineName::T(id) => format!("t{}", id),
+            LineName::S(id) => format!("s{}", id),
+            LineName::Placeholder => String::from("placeholder"),
+        }
+    }
+}
+
+// What pods of a line do when the connection ahead is blocked. Wait keeps them in the
+// station until it is unblocked, TurnBack lets them reverse and serve the part of the
+// line that is still reachable.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub enum OnBlock {
+    #[default]
+    #[serde(rename = "wait")]
+    Wait,
+    #[serde(rename = "turn")]
+    TurnBack,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Direction {
     Pos,
     Neg,
@@ -1,11 +1,9 @@
 use crate::config::structs::Config;
-use crate::connection::YieldTriple;
 use crate::helper::enums::{Direction, LineName};
-use crate::line::line::Line;
 use crate::network::Network;
 use geoutils::Location;
-use petgraph::graph::UnGraph;
 use rand::Rng;
+use rand_chacha::ChaCha8Rng;
 use rhai::Engine;
 use std::fs::File;
 use std::io::{self, BufRead};
@@ -71,6 +69,30 @@ pub fn format_seconds(seconds: u32) -> String {
     return format!("{}:{}:{}", hours, minutes, seconds);
 }
 
+// Accepts "HH:MM" and "HH:MM:SS", returns the seconds since midnight.
+pub fn parse_time_of_day(time: &str) -> Option<u32> {
+    let parts: Vec<&str> = time.trim().split(':').collect();
+    if parts.len() < 2 || parts.len() > 3 {
+        return None;
+    }
+    let mut seconds = 0;
+    for (part, factor) in parts.iter().zip([3600, 60, 1]) {
+        let value: u32 = FromStr::from_str(part).ok()?;
+        seconds += value * factor;
+    }
+    Some(seconds)
+}
+
+pub fn get_time_of_day(config: &Config, time_passed: u32) -> u32 {
+    (config.logic.start_time + time_passed) % 86400
+}
+
+// Whether the time series of each person and pod get a timestamp in this second
+pub fn is_metrics_sample(config: &Config, time_passed: u32) -> bool {
+    // No remainder for an interval of 0, it keeps no timestamps
+    time_passed.checked_rem(config.logic.metrics_interval) == Some(0)
+}
+
 pub fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
 where
     P: AsRef<Path>,
@@ -79,11 +101,54 @@ where
     Ok(io::BufReader::new(file).lines())
 }
 
-pub fn get_random_station_id(config: &Config) -> u32 {
-    let mut rng = rand::thread_rng();
-    let station_ids: Vec<&i32> = config.network.coordinates_map_stations.keys().collect();
+pub fn get_random_station_id(config: &Config, rng: &mut ChaCha8Rng) -> u32 {
+    let station_ids = get_sorted_station_ids(config);
     let end_ix = rng.gen_range(0..station_ids.len());
-    *station_ids[end_ix] as u32
+    station_ids[end_ix] as u32
+}
+
+// Samples a whole trip from the demand matrix that is active at this time of day.
+// Without a demand config start and destination are drawn uniformly.
+pub fn get_random_trip(config: &Config, time_passed: u32, rng: &mut ChaCha8Rng) -> (u32, u32) {
+    if let Some(demand) = &config.demand {
+        let od = demand.get_active_od(get_time_of_day(config, time_passed));
+        if let Some((from, to)) = od.try_sample_trip(rng) {
+            return (from as u32, to as u32);
+        }
+    }
+    let start = get_random_station_id(config, rng);
+    let end = get_random_station_id(config, rng);
+    (start, end)
+}
+
+// Samples the next destination of someone standing at origin. Origins the matrix
+// knows nothing about fall back to a uniformly drawn station.
+pub fn get_random_destination(
+    config: &Config,
+    origin: u32,
+    time_passed: u32,
+    rng: &mut ChaCha8Rng,
+) -> u32 {
+    if let Some(demand) = &config.demand {
+        let od = demand.get_active_od(get_time_of_day(config, time_passed));
+        if let Some(to) = od.try_sample_destination(origin as i32, rng) {
+            return to as u32;
+        }
+    }
+    get_random_station_id(config, rng)
+}
+
+// HashMap iteration order changes from process to process, the ids have to be
+// sorted before indexing into them, otherwise the same seed yields different stations.
+pub fn get_sorted_station_ids(config: &Config) -> Vec<i32> {
+    let mut station_ids: Vec<i32> = config
+        .network
+        .coordinates_map_stations
+        .keys()
+        .copied()
+        .collect();
+    station_ids.sort();
+    station_ids
 }
 
 pub fn get_air_travel_time(start: u32, end: u32, network: &Network, config: &Config) -> u32 {
@@ -149,6 +214,18 @@ pub fn transform_line_name_to_enum(line_name: &str) -> LineName {
     }
 }
 
+// Like transform_line_name_to_enum, but for names that may be wrong, e.g. in the configs
+pub fn try_transform_line_name_to_enum(line_name: &str) -> Option<LineName> {
+    let mode = line_name.chars().next()?;
+    let id: i32 = line_name.get(1..)?.parse().ok()?;
+    match mode {
+        'U' | 'u' => Some(LineName::U(id)),
+        'T' | 't' => Some(LineName::T(id)),
+        'S' | 's' => Some(LineName::S(id)),
+        _ => None,
+    }
+}
+
 pub fn parse_str_to_line_and_directions(line_and_direction: &str) -> (LineName, Vec<Direction>) {
     let line_name_str = line_and_direction.replace(&['+', '-'][..], "");
     let line_name = transform_line_name_to_enum(&line_name_str);
@@ -174,20 +251,6 @@ pub fn parse_str_to_line_and_directions(line_and_direction: &str) -> (LineName,
     return (line_name, directions);
 }
 
-pub fn calc_graph(lines: &Vec<Line>) -> UnGraph<u32, u32> {
-    let mut edges: Vec<(u32, u32, u32)> = vec![];
-
-    for line in lines {
-        for connection in &line.connections {
-            if !connection.is_blocked {
-                edges.push(connection.yield_triple())
-            }
-        }
-    }
-    let graph = UnGraph::from_edges(edges);
-    graph
-}
-
 pub fn interpolate(command: &String, config: &Config, engine: &Engine) -> String {
     let command = command.trim().to_string();
     let mut command_without_comment = command;
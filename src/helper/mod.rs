@@ -1,3 +1,5 @@
 pub mod enums;
 pub mod functions;
 pub mod printer;
+#[cfg(test)]
+pub mod testing;
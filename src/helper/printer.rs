@@ -39,6 +39,7 @@ pub fn print_get_person(people_box: &PeopleBox, id: i32) {
             println!("Id: {}", person.id);
             println!("Coordinates: {:?}", person.real_coordinates);
             println!("Path: {:?}", person.path_state.path);
+            println!("Legs: {:?}", person.path_state.legs);
             println!("----------------------");
         }
         None => {
@@ -64,6 +65,13 @@ pub fn print_get_pod(pods_box: &PodsBox, id: i32) {
                 "Next Station: {:?}",
                 pod.line_state.line.stations[pod.line_state.next_ix as usize]
             );
+            if let Some(trip) = &pod.trip {
+                println!("Trip: {} | Stops left: {}", trip.id, trip.stops.len());
+                println!("Lateness: {}", pod.metrics.lateness);
+            }
+            if pod.retired {
+                println!("Retired: true");
+            }
             println!("----------------------");
         }
         None => {
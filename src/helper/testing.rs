@@ -0,0 +1,90 @@
+use crate::config::processing::build_network_config;
+use crate::config::structs::{
+    Config, GeneralConfig, LineConfig, LogicConfig, StationConfig, VisualConfig,
+};
+use crate::control::action::{Action, Actions};
+use crate::helper::enums::Direction;
+use crate::helper::enums::OnBlock;
+use crate::helper::functions::transform_line_name_to_enum;
+use crate::network::Network;
+use crate::state::{build_stations, State};
+use std::sync::mpsc;
+
+// Small towns for the tests, built the same way parse_config and State::new build a town.
+// Stations lie 1 apart, so with a distance_factor of 1000 the air distance is never
+// longer than the 2000 m between two stations of a line.
+
+pub fn get_station(id: i32, lat: f32, lon: f32) -> StationConfig {
+    StationConfig {
+        id: id,
+        name: String::new(),
+        city: String::new(),
+        lat: lat,
+        lon: lon,
+        entrypoint_for: vec![],
+    }
+}
+
+pub fn get_line(name: &str, stations: Vec<i32>, on_block: OnBlock) -> LineConfig {
+    LineConfig {
+        name: transform_line_name_to_enum(name),
+        distances: vec![2000; stations.len() - 1],
+        stations: stations,
+        circular: false,
+        on_block: on_block,
+    }
+}
+
+pub fn get_config(station_configs: Vec<StationConfig>, line_configs: Vec<LineConfig>) -> Config {
+    let (network_config, number_of_pods) = build_network_config(&station_configs, &line_configs);
+    let logic = LogicConfig {
+        number_of_pods: number_of_pods,
+        pod_capacity: 10,
+        transition_time: 10,
+        pod_in_station_seconds: 5,
+        line_pods_per_hour: 60,
+        station_pods_per_hour: 60,
+        seed: Some(7),
+        ..LogicConfig::default()
+    };
+    Config {
+        timestamp_run: None,
+        output_dir: None,
+        general: GeneralConfig::default(),
+        network: network_config,
+        logic: logic,
+        visual: VisualConfig::default(),
+        demand: None,
+        timetable: None,
+    }
+}
+
+pub fn get_network(config: &Config) -> Network {
+    Network::new(build_stations(config), config)
+}
+
+// A run without a cli or window, tests hand it their actions directly
+pub fn get_state(mut config: Config) -> State {
+    config.general.quiet = true;
+    let (_tx, rx) = mpsc::channel();
+    State::new(config, rx).add_people()
+}
+
+// Pods in both directions from both ends of each line
+pub fn get_spawn_pods(config: &Config) -> Actions {
+    let mut actions = Actions::new();
+    for line in &config.network.lines {
+        for (station_id, direction) in [
+            (line.stations[0], Direction::Pos),
+            (*line.stations.last().unwrap(), Direction::Neg),
+        ] {
+            actions.actions.push(Action::SpawnPod {
+                station_id: station_id,
+                line_name: line.name.clone(),
+                direction: direction,
+                force: true,
+            });
+        }
+    }
+    actions
+}
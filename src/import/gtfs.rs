@@ -0,0 +1,556 @@
+use crate::config::structs::{LineConfig, StationConfig};
+use crate::helper::enums::{LineName, OnBlock};
+use crate::import::{collect_entrypoints, haversine_meters, read_records, Columns};
+use csv::StringRecord;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::path::Path;
+use zip::ZipArchive;
+
+// A GTFS feed, either a zip file or a folder with the unpacked txt files
+struct GtfsFeed {
+    path: String,
+    archive: Option<ZipArchive<File>>,
+}
+
+impl GtfsFeed {
+    fn open(path: &str) -> Result<Self, String> {
+        if Path::new(path).is_dir() {
+            return Ok(GtfsFeed {
+                path: path.to_string(),
+                archive: None,
+            });
+        }
+        let file = File::open(path).map_err(|e| format!("can't open {}: {}", path, e))?;
+        let archive = ZipArchive::new(file).map_err(|e| format!("can't read {}: {}", path, e))?;
+        Ok(GtfsFeed {
+            path: path.to_string(),
+            archive: Some(archive),
+        })
+    }
+
+    fn has_file(&mut self, name: &str) -> bool {
+        match &mut self.archive {
+            Some(archive) => archive.by_name(name).is_ok(),
+            None => Path::new(&self.path).join(name).exists(),
+        }
+    }
+
+    fn for_each_record(
+        &mut self,
+        name: &str,
+        mut f: impl FnMut(&Columns, &StringRecord),
+    ) -> Result<(), String> {
+        match &mut self.archive {
+            Some(archive) => {
+                let file = archive
+                    .by_name(name)
+                    .map_err(|e| format!("{} is missing in the feed: {}", name, e))?;
+                read_records(file, name, b',', &mut f)
+            }
+            None => {
+                let file = File::open(Path::new(&self.path).join(name))
+                    .map_err(|e| format!("{} is missing in the feed: {}", name, e))?;
+                read_records(file, name, b',', &mut f)
+            }
+        }
+    }
+}
+
+struct GtfsStop {
+    name: String,
+    lat: f64,
+    lon: f64,
+    parent_station: String,
+}
+
+struct GtfsTrip {
+    route_id: String,
+    direction_id: String,
+    shape_id: String,
+}
+
+// A stop of a trip with the distance along the shape if the feed has it
+struct GtfsStopTime {
+    sequence: u32,
+    stop_id: String,
+    shape_dist_traveled: Option<f64>,
+}
+
+struct GtfsShapePoint {
+    sequence: u32,
+    lat: f64,
+    lon: f64,
+}
+
+// The model only knows subways, trams and suburban trains. GTFS route types are
+// mapped onto them, everything else (busses, ferries, ...) is skipped.
+fn try_make_line_name(route_type: &str, short_name: &str, long_name: &str) -> Option<LineName> {
+    let route_type: i32 = route_type.parse().ok()?;
+    let name = if short_name.is_empty() {
+        long_name
+    } else {
+        short_name
+    };
+    let digits: String = name
+        .chars()
+        .skip_while(|c| !c.is_ascii_digit())
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    let id: i32 = digits.parse().ok()?;
+    match route_type {
+        0 | 900..=906 => Some(LineName::T(id)),
+        1 | 400..=405 => Some(LineName::U(id)),
+        2 | 100..=117 => Some(LineName::S(id)),
+        _ => None,
+    }
+}
+
+// Distances between consecutive stops of a trip. The feed's own shape_dist_traveled is
+// used if every stop has it, otherwise the stops are placed on the shape, otherwise the
+// great-circle distance is used. shape_dist_traveled has no fixed unit, feeds that use
+// kilometers are recognized by comparing with the great-circle distance.
+fn calc_distances(
+    stop_times: &Vec<&GtfsStopTime>,
+    stops: &HashMap<String, GtfsStop>,
+    shape: Option<&Vec<GtfsShapePoint>>,
+) -> Vec<f64> {
+    let coordinates: Vec<(f64, f64)> = stop_times
+        .iter()
+        .map(|stop_time| {
+            let stop = &stops[&stop_time.stop_id];
+            (stop.lat, stop.lon)
+        })
+        .collect();
+    let great_circle: Vec<f64> = coordinates
+        .windows(2)
+        .map(|pair| haversine_meters(pair[0], pair[1]))
+        .collect();
+
+    if stop_times
+        .iter()
+        .all(|stop_time| stop_time.shape_dist_traveled.is_some())
+    {
+        let along: Vec<f64> = stop_times
+            .windows(2)
+            .map(|pair| pair[1].shape_dist_traveled.unwrap() - pair[0].shape_dist_traveled.unwrap())
+            .collect();
+        let along_sum: f64 = along.iter().sum();
+        let great_circle_sum: f64 = great_circle.iter().sum();
+        if along_sum > 0. {
+            let factor = if great_circle_sum / along_sum > 100. {
+                1000.
+            } else {
+                1.
+            };
+            return along.iter().map(|distance| distance * factor).collect();
+        }
+    }
+
+    if let Some(shape) = shape {
+        if shape.len() >= 2 {
+            let mut cumulative = vec![0.];
+            for pair in shape.windows(2) {
+                let last = *cumulative.last().unwrap();
+                cumulative.push(
+                    last + haversine_meters((pair[0].lat, pair[0].lon), (pair[1].lat, pair[1].lon)),
+                );
+            }
+            // Every stop is put onto the closest shape point that is not before the previous stop
+            let mut positions = vec![];
+            let mut from_ix = 0;
+            for coordinate in &coordinates {
+                let mut best_ix = from_ix;
+                let mut best_distance = f64::MAX;
+                for (ix, point) in shape.iter().enumerate().skip(from_ix) {
+                    let distance = haversine_meters(*coordinate, (point.lat, point.lon));
+                    if distance < best_distance {
+                        best_distance = distance;
+                        best_ix = ix;
+                    }
+                }
+                positions.push(cumulative[best_ix]);
+                from_ix = best_ix;
+            }
+            return positions.windows(2).map(|pair| pair[1] - pair[0]).collect();
+        }
+    }
+
+    great_circle
+}
+
+// Builds stations and lines from the rail, subway and tram routes of a GTFS feed.
+// Every route becomes one line that follows its longest trip, stops that share a
+// parent station become one station.
+pub fn import_gtfs(
+    path: &str,
+    city: &Option<String>,
+) -> Result<(Vec<StationConfig>, Vec<LineConfig>), String> {
+    let mut feed = GtfsFeed::open(path)?;
+
+    let mut stops: HashMap<String, GtfsStop> = HashMap::new();
+    feed.for_each_record("stops.txt", |columns, record| {
+        let lat: Option<f64> = columns.get(record, "stop_lat").parse().ok();
+        let lon: Option<f64> = columns.get(record, "stop_lon").parse().ok();
+        if let (Some(lat), Some(lon)) = (lat, lon) {
+            stops.insert(
+                columns.get(record, "stop_id").to_string(),
+                GtfsStop {
+                    name: columns.get(record, "stop_name").to_string(),
+                    lat: lat,
+                    lon: lon,
+                    parent_station: columns.get(record, "parent_station").to_string(),
+                },
+            );
+        }
+    })?;
+
+    let mut agency_name = String::from("placeholder");
+    if feed.has_file("agency.txt") {
+        feed.for_each_record("agency.txt", |columns, record| {
+            agency_name = columns.get(record, "agency_name").to_string();
+        })?;
+    }
+    let city = match city {
+        Some(city) => city.clone(),
+        None => agency_name,
+    };
+
+    let mut route_line_names: HashMap<String, LineName> = HashMap::new();
+    let mut taken_line_names: HashSet<LineName> = HashSet::new();
+    let mut n_skipped_routes = 0;
+    feed.for_each_record("routes.txt", |columns, record| {
+        let route_id = columns.get(record, "route_id");
+        let short_name = columns.get(record, "route_short_name");
+        let maybe_line_name = try_make_line_name(
+            columns.get(record, "route_type"),
+            short_name,
+            columns.get(record, "route_long_name"),
+        );
+        match maybe_line_name {
+            Some(line_name) if !taken_line_names.contains(&line_name) => {
+                taken_line_names.insert(line_name.clone());
+                route_line_names.insert(route_id.to_string(), line_name);
+            }
+            Some(line_name) => println!(
+                "route {} ({}) would be {} again, skipping it.",
+                route_id,
+                short_name,
+                line_name.to_config_string()
+            ),
+            None => n_skipped_routes += 1,
+        }
+    })?;
+    println!(
+        "{} routes are no rail, subway or tram line or have no number, skipping them.",
+        n_skipped_routes
+    );
+
+    let mut trips: HashMap<String, GtfsTrip> = HashMap::new();
+    feed.for_each_record("trips.txt", |columns, record| {
+        let route_id = columns.get(record, "route_id");
+        if route_line_names.contains_key(route_id) {
+            trips.insert(
+                columns.get(record, "trip_id").to_string(),
+                GtfsTrip {
+                    route_id: route_id.to_string(),
+                    direction_id: columns.get(record, "direction_id").to_string(),
+                    shape_id: columns.get(record, "shape_id").to_string(),
+                },
+            );
+        }
+    })?;
+
+    let mut stop_times: HashMap<String, Vec<GtfsStopTime>> = HashMap::new();
+    feed.for_each_record("stop_times.txt", |columns, record| {
+        let trip_id = columns.get(record, "trip_id");
+        let stop_id = columns.get(record, "stop_id");
+        if !trips.contains_key(trip_id) || !stops.contains_key(stop_id) {
+            return;
+        }
+        if let Ok(sequence) = columns.get(record, "stop_sequence").parse() {
+            stop_times
+                .entry(trip_id.to_string())
+                .or_default()
+                .push(GtfsStopTime {
+                    sequence: sequence,
+                    stop_id: stop_id.to_string(),
+                    shape_dist_traveled: columns.get(record, "shape_dist_traveled").parse().ok(),
+                });
+        }
+    })?;
+    for trip_stop_times in stop_times.values_mut() {
+        trip_stop_times.sort_by_key(|stop_time| stop_time.sequence);
+    }
+
+    // The longest trip of a route, trips in direction 0 and lower ids win ties
+    let mut route_trips: HashMap<String, (usize, bool, String)> = HashMap::new();
+    for (trip_id, trip_stop_times) in &stop_times {
+        let trip = &trips[trip_id];
+        let candidate = (
+            trip_stop_times.len(),
+            trip.direction_id != "1",
+            trip_id.clone(),
+        );
+        let is_better = match route_trips.get(&trip.route_id) {
+            Some((n, direction_0, best_id)) => {
+                (candidate.0, candidate.1) > (*n, *direction_0)
+                    || ((candidate.0, candidate.1) == (*n, *direction_0) && &candidate.2 < best_id)
+            }
+            None => true,
+        };
+        if is_better {
+            route_trips.insert(trip.route_id.clone(), candidate);
+        }
+    }
+
+    let needed_shapes: HashSet<String> = route_trips
+        .values()
+        .map(|(_, _, trip_id)| trips[trip_id].shape_id.clone())
+        .filter(|shape_id| !shape_id.is_empty())
+        .collect();
+    let mut shapes: HashMap<String, Vec<GtfsShapePoint>> = HashMap::new();
+    if !needed_shapes.is_empty() && feed.has_file("shapes.txt") {
+        feed.for_each_record("shapes.txt", |columns, record| {
+            let shape_id = columns.get(record, "shape_id");
+            if !needed_shapes.contains(shape_id) {
+                return;
+            }
+            let sequence: Option<u32> = columns.get(record, "shape_pt_sequence").parse().ok();
+            let lat: Option<f64> = columns.get(record, "shape_pt_lat").parse().ok();
+            let lon: Option<f64> = columns.get(record, "shape_pt_lon").parse().ok();
+            if let (Some(sequence), Some(lat), Some(lon)) = (sequence, lat, lon) {
+                shapes
+                    .entry(shape_id.to_string())
+                    .or_default()
+                    .push(GtfsShapePoint {
+                        sequence: sequence,
+                        lat: lat,
+                        lon: lon,
+                    });
+            }
+        })?;
+        for shape in shapes.values_mut() {
+            shape.sort_by_key(|point| point.sequence);
+        }
+    }
+
+    let get_station_key = |stop_id: &str| -> String {
+        let stop = &stops[stop_id];
+        if !stop.parent_station.is_empty() && stops.contains_key(&stop.parent_station) {
+            stop.parent_station.clone()
+        } else {
+            stop_id.to_string()
+        }
+    };
+
+    // Lines are sorted by name, so the station ids follow the lines
+    let mut routes: Vec<(&String, &LineName)> = route_line_names
+        .iter()
+        .filter(|(route_id, _)| route_trips.contains_key(*route_id))
+        .collect();
+    routes.sort_by_key(|(_, line_name)| line_name.to_config_string());
+
+    let mut station_ids: HashMap<String, i32> = HashMap::new();
+    let mut station_keys: Vec<String> = vec![];
+    let mut line_configs: Vec<LineConfig> = vec![];
+    for (route_id, line_name) in routes {
+        let (_, _, trip_id) = &route_trips[route_id];
+
+        // Platforms of the same station that follow each other are one stop
+        let mut line_stop_times: Vec<&GtfsStopTime> = vec![];
+        let mut line_station_keys: Vec<String> = vec![];
+        for stop_time in &stop_times[trip_id] {
+            let station_key = get_station_key(&stop_time.stop_id);
+            if line_station_keys.last() != Some(&station_key) {
+                line_station_keys.push(station_key);
+                line_stop_times.push(stop_time);
+            }
+        }
+        if line_station_keys.len() < 2 {
+            println!(
+                "{} has less than two stations, skipping it.",
+                line_name.to_config_string()
+            );
+            continue;
+        }
+
+        let shape = shapes.get(&trips[trip_id].shape_id);
+        let mut distances: Vec<i32> = calc_distances(&line_stop_times, &stops, shape)
+            .iter()
+            .map(|distance| (distance.round() as i32).max(1))
+            .collect();
+
+        // A trip that ends where it started is a circular line, the last distance closes it
+        let circular =
+            line_station_keys.len() > 2 && line_station_keys.first() == line_station_keys.last();
+        if circular {
+            line_station_keys.pop();
+        }
+        // Lines that visit a station twice otherwise can't be modeled, only the first visit is kept
+        let mut seen: HashSet<String> = HashSet::new();
+        if line_station_keys
+            .iter()
+            .any(|key| !seen.insert(key.clone()))
+        {
+            println!(
+                "{} visits a station more than once, skipping it.",
+                line_name.to_config_string()
+            );
+            continue;
+        }
+        if !circular && distances.len() + 1 != line_station_keys.len() {
+            distances.truncate(line_station_keys.len() - 1);
+        }
+
+        let mut line_station_ids: Vec<i32> = vec![];
+        for station_key in &line_station_keys {
+            let id = *station_ids.entry(station_key.clone()).or_insert_with(|| {
+                station_keys.push(station_key.clone());
+                station_keys.len() as i32 - 1
+            });
+            line_station_ids.push(id);
+        }
+
+        line_configs.push(LineConfig {
+            name: line_name.clone(),
+            stations: line_station_ids,
+            distances: distances,
+            circular: circular,
+            on_block: OnBlock::Wait,
+        });
+    }
+
+    let mut entrypoints = collect_entrypoints(&line_configs);
+    let station_configs: Vec<StationConfig> = station_keys
+        .iter()
+        .enumerate()
+        .map(|(id, station_key)| {
+            let stop = &stops[station_key];
+            StationConfig {
+                id: id as i32,
+                name: stop.name.clone(),
+                city: city.clone(),
+                // The towns use lat as the horizontal axis
+                lat: stop.lon as f32,
+                lon: stop.lat as f32,
+                entrypoint_for: entrypoints.remove(&(id as i32)).unwrap_or_default(),
+            }
+        })
+        .collect();
+
+    Ok((station_configs, line_configs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    // Platforms A1 and A2 of station A, a subway with its distances in km, a tram
+    // without any and a bus that is not imported
+    const FEED: &[(&str, &str)] = &[
+        ("agency.txt", "agency_id,agency_name\n1,MVG\n"),
+        (
+            "stops.txt",
+            "stop_id,stop_name,stop_lat,stop_lon,parent_station
+A,A,48.10,11.50,
+A1,A Gleis 1,48.10,11.50,A
+A2,A Gleis 2,48.10,11.50,A
+B,B,48.11,11.50,
+C,C,48.12,11.50,
+D,D,48.12,11.52,
+",
+        ),
+        (
+            "routes.txt",
+            "route_id,route_short_name,route_long_name,route_type
+r1,U1,,1
+r2,12,,900
+r3,100,,3
+",
+        ),
+        (
+            "trips.txt",
+            "route_id,trip_id,direction_id,shape_id
+r1,t1,0,
+r1,t2,1,
+r2,t3,0,
+r3,t4,0,
+",
+        ),
+        (
+            "stop_times.txt",
+            "trip_id,stop_id,stop_sequence,shape_dist_traveled
+t1,A1,1,0
+t1,A2,2,0
+t1,B,3,1.2
+t1,C,4,2.4
+t2,C,1,0
+t2,B,2,1.2
+t2,A1,3,2.4
+t3,C,1,
+t3,D,2,
+t4,A,1,
+t4,B,2,
+",
+        ),
+    ];
+
+    #[test]
+    fn subway_and_tram_from_a_feed() {
+        let feed_root =
+            std::env::temp_dir().join(format!("publicflow_gtfs_{}", std::process::id()));
+        fs::create_dir_all(&feed_root).unwrap();
+        for (name, content) in FEED {
+            fs::write(feed_root.join(name), content).unwrap();
+        }
+        let imported = import_gtfs(feed_root.to_str().unwrap(), &None);
+        fs::remove_dir_all(&feed_root).unwrap();
+        let (station_configs, line_configs) = imported.unwrap();
+
+        // Lines are sorted by name, t12 comes first and numbers its stations first
+        let lines: Vec<(String, Vec<i32>, Vec<i32>, bool)> = line_configs
+            .iter()
+            .map(|line| {
+                (
+                    line.name.to_config_string(),
+                    line.stations.clone(),
+                    line.distances.clone(),
+                    line.circular,
+                )
+            })
+            .collect();
+        let tram_distance = haversine_meters((48.12, 11.50), (48.12, 11.52)).round() as i32;
+        assert_eq!(
+            lines,
+            vec![
+                (String::from("t12"), vec![0, 1], vec![tram_distance], false),
+                (String::from("u1"), vec![2, 3, 0], vec![1200, 1200], false),
+            ]
+        );
+
+        let stations: Vec<_> = station_configs
+            .iter()
+            .map(|station| {
+                (
+                    station.id,
+                    station.name.as_str(),
+                    station.city.as_str(),
+                    station.lat,
+                    station.lon,
+                    station.entrypoint_for.iter().map(|e| e.as_str()).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            stations,
+            vec![
+                (0, "C", "MVG", 11.50, 48.12, vec!["t12+", "u1-"]),
+                (1, "D", "MVG", 11.52, 48.12, vec!["t12-"]),
+                (2, "A", "MVG", 11.50, 48.10, vec!["u1+"]),
+                (3, "B", "MVG", 11.50, 48.11, vec![]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,352 @@
+pub mod gtfs;
+pub mod wiki;
+
+use crate::config::constants::{GENERAL_CONFIG_NAME, LINES_CONFIG_NAME, STATIONS_CONFIG_NAME};
+use crate::config::processing::build_network_config;
+use crate::config::structs::{ImportConfig, LineConfig, StationConfig};
+use crate::import::gtfs::import_gtfs;
+use crate::import::wiki::import_wiki;
+use csv::{ReaderBuilder, StringRecord};
+use geoutils::Location;
+use std::collections::HashMap;
+use std::fs::{create_dir_all, File};
+use std::io::prelude::*;
+use std::path::Path;
+
+// Reads the source, builds the network from it to make sure it can be simulated and
+// writes the town to config/<town>/ so it can be reviewed and run.
+pub fn run_import(import_config: &ImportConfig, config_root: &str) {
+    if import_config.town.is_empty() {
+        println!("import needs a town to write to.");
+        return;
+    }
+    let town_root = format!("{}{}/", config_root, import_config.town);
+    if Path::new(&format!("{}{}", town_root, STATIONS_CONFIG_NAME)).exists()
+        && !import_config.overwrite
+    {
+        println!(
+            "{} already exists, set import.overwrite to replace it.",
+            town_root
+        );
+        return;
+    }
+
+    let imported = match (&import_config.gtfs, &import_config.wiki) {
+        (Some(gtfs_path), None) => import_gtfs(gtfs_path, &import_config.city),
+        (None, Some(wiki)) => import_wiki(&wiki.lines, &wiki.stations, &import_config.city),
+        (Some(_), Some(_)) => Err(String::from(
+            "import reads either a gtfs feed or the wiki files, not both.",
+        )),
+        (None, None) => Err(String::from(
+            "import needs a gtfs feed or the wiki files to read from.",
+        )),
+    };
+    let (station_configs, line_configs) = match imported {
+        Ok(configs) => configs,
+        Err(e) => {
+            println!("import failed: {}", e);
+            return;
+        }
+    };
+
+    let (network_config, _) = build_network_config(&station_configs, &line_configs);
+    println!(
+        "imported {} stations and {} lines",
+        network_config.n_stations,
+        network_config.lines.len()
+    );
+
+    match write_town(&town_root, &station_configs, &line_configs) {
+        Ok(_) => println!("written town to {}", town_root),
+        Err(e) => println!("error writing town: {}", e),
+    }
+}
+
+// Column indices by name, the csv files of the sources can have their columns in any order
+pub struct Columns {
+    indices: HashMap<String, usize>,
+}
+
+impl Columns {
+    pub fn new(headers: &StringRecord) -> Self {
+        let mut indices = HashMap::new();
+        for (i, header) in headers.iter().enumerate() {
+            // Some feeds start their files with a byte order mark
+            indices.insert(header.trim_start_matches('\u{feff}').trim().to_string(), i);
+        }
+        Columns { indices: indices }
+    }
+
+    // Empty if the column does not exist
+    pub fn get<'a>(&self, record: &'a StringRecord, name: &str) -> &'a str {
+        match self.indices.get(name) {
+            Some(i) => record.get(*i).unwrap_or("").trim(),
+            None => "",
+        }
+    }
+}
+
+pub fn read_records<R: Read>(
+    reader: R,
+    name: &str,
+    delimiter: u8,
+    f: &mut impl FnMut(&Columns, &StringRecord),
+) -> Result<(), String> {
+    let mut csv_reader = ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_reader(reader);
+    let headers = csv_reader
+        .headers()
+        .map_err(|e| format!("can't read the header of {}: {}", name, e))?
+        .clone();
+    let columns = Columns::new(&headers);
+    for result in csv_reader.records() {
+        match result {
+            Ok(record) => f(&columns, &record),
+            Err(e) => println!("skipping a broken row in {}: {}", name, e),
+        }
+    }
+    Ok(())
+}
+
+pub fn haversine_meters(from: (f64, f64), to: (f64, f64)) -> f64 {
+    Location::new(from.0, from.1)
+        .haversine_distance_to(&Location::new(to.0, to.1))
+        .meters()
+}
+
+// Pods of a line start at its ends, circular lines start at their first station
+pub fn collect_entrypoints(line_configs: &Vec<LineConfig>) -> HashMap<i32, Vec<String>> {
+    let mut entrypoints: HashMap<i32, Vec<String>> = HashMap::new();
+    for line_config in line_configs {
+        let name = line_config.name.to_config_string();
+        let first = line_config.stations[0];
+        let last = *line_config.stations.last().unwrap();
+        if line_config.circular {
+            entrypoints
+                .entry(first)
+                .or_default()
+                .push(format!("{}+-", name));
+        } else {
+            entrypoints
+                .entry(first)
+                .or_default()
+                .push(format!("{}+", name));
+            entrypoints
+                .entry(last)
+                .or_default()
+                .push(format!("{}-", name));
+        }
+    }
+    entrypoints
+}
+
+pub fn write_town(
+    town_root: &str,
+    station_configs: &Vec<StationConfig>,
+    line_configs: &Vec<LineConfig>,
+) -> std::io::Result<()> {
+    create_dir_all(town_root)?;
+    let files = [
+        (STATIONS_CONFIG_NAME, format_stations_yaml(station_configs)),
+        (LINES_CONFIG_NAME, format_lines_yaml(line_configs)),
+        (GENERAL_CONFIG_NAME, format_general_yaml(station_configs)),
+    ];
+    for (name, content) in files {
+        let mut file = File::create(format!("{}{}", town_root, name))?;
+        file.write_all(content.as_bytes())?;
+    }
+    Ok(())
+}
+
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn format_list<T: ToString>(values: &[T]) -> String {
+    let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+    format!("[{}]", values.join(", "))
+}
+
+fn format_stations_yaml(station_configs: &Vec<StationConfig>) -> String {
+    let mut txt = String::new();
+    for station_config in station_configs {
+        txt.push_str(&format!("- city: {}\n", quote(&station_config.city)));
+        txt.push_str(&format!("  id: {}\n", station_config.id));
+        txt.push_str(&format!("  lat: {}\n", station_config.lat));
+        txt.push_str(&format!("  lon: {}\n", station_config.lon));
+        txt.push_str(&format!("  name: {}\n", quote(&station_config.name)));
+        if !station_config.entrypoint_for.is_empty() {
+            let entrypoint_for: Vec<String> = station_config
+                .entrypoint_for
+                .iter()
+                .map(|entrypoint| quote(entrypoint))
+                .collect();
+            txt.push_str(&format!(
+                "  entrypoint_for: {}\n",
+                format_list(&entrypoint_for)
+            ));
+        }
+    }
+    txt
+}
+
+fn format_lines_yaml(line_configs: &Vec<LineConfig>) -> String {
+    let mut txt = String::new();
+    for line_config in line_configs {
+        txt.push_str(&format!("- circular: {}\n", line_config.circular));
+        txt.push_str(&format!(
+            "  distances: {}\n",
+            format_list(&line_config.distances)
+        ));
+        txt.push_str(&format!(
+            "  name: {}\n",
+            line_config.name.to_config_string()
+        ));
+        txt.push_str(&format!(
+            "  stations: {}\n",
+            format_list(&line_config.stations)
+        ));
+    }
+    txt
+}
+
+// The screen shows the bounding box of all stations, the logic values are the ones of
+// the existing towns and can be overridden in config.yaml
+fn format_general_yaml(station_configs: &Vec<StationConfig>) -> String {
+    let mut lat_range = (f32::MAX, f32::MIN);
+    let mut lon_range = (f32::MAX, f32::MIN);
+    for station_config in station_configs {
+        lat_range = (
+            lat_range.0.min(station_config.lat),
+            lat_range.1.max(station_config.lat),
+        );
+        lon_range = (
+            lon_range.0.min(station_config.lon),
+            lon_range.1.max(station_config.lon),
+        );
+    }
+
+    let mut txt = String::new();
+    txt.push_str("visual:\n");
+    txt.push_str("  screen_size:\n    x: 1920.0\n    y: 1150.0\n");
+    txt.push_str(&format!(
+        "  latitude:\n    min: {}\n    max: {}\n",
+        lat_range.0, lat_range.1
+    ));
+    txt.push_str(&format!(
+        "  longitude:\n    min: {}\n    max: {}\n",
+        lon_range.0, lon_range.1
+    ));
+    txt.push_str("  screen_offset: 100.0\n");
+    txt.push_str("  radius_station: 10.0\n");
+    txt.push_str("  radius_pod: 5.0\n");
+    txt.push_str("  width_line: 2.0\n");
+    txt.push_str("  vsync: true\n");
+    txt.push_str("logic:\n");
+    txt.push_str("  number_of_people: 1000\n");
+    txt.push_str("  pod_capacity: 90\n");
+    txt.push_str("  transition_time: 60\n");
+    txt.push_str("  line_pods_per_hour: 20\n");
+    txt.push_str("  station_pods_per_hour: 60\n");
+    txt.push_str("  pod_in_station_seconds: 40\n");
+    txt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::processing::parse_config;
+    use crate::helper::enums::{LineName, OnBlock};
+    use serde_yaml::{Mapping, Value};
+    use std::fs;
+
+    fn get_station(id: i32, name: &str, lat: f32, lon: f32) -> StationConfig {
+        StationConfig {
+            id: id,
+            name: name.to_string(),
+            city: String::from("München"),
+            lat: lat,
+            lon: lon,
+            entrypoint_for: vec![],
+        }
+    }
+
+    #[test]
+    fn written_town_parses_back() {
+        let line_configs = vec![
+            LineConfig {
+                name: LineName::U(1),
+                stations: vec![0, 1, 2],
+                distances: vec![1200, 800],
+                circular: false,
+                on_block: OnBlock::Wait,
+            },
+            LineConfig {
+                name: LineName::T(12),
+                stations: vec![2, 3, 4],
+                distances: vec![500, 600, 700],
+                circular: true,
+                on_block: OnBlock::Wait,
+            },
+        ];
+        let mut station_configs = vec![
+            get_station(0, "Marienplatz", 11.57, 48.13),
+            get_station(1, "\"Sendlinger\" Tor", 11.56, 48.13),
+            get_station(2, "Odeonsplatz \\ Nord", 11.57, 48.14),
+            get_station(3, "Giselastraße", 11.58, 48.15),
+            get_station(4, "Münchner Freiheit", 11.58, 48.16),
+        ];
+        let mut entrypoints = collect_entrypoints(&line_configs);
+        for station_config in &mut station_configs {
+            station_config.entrypoint_for =
+                entrypoints.remove(&station_config.id).unwrap_or_default();
+        }
+
+        let config_root =
+            std::env::temp_dir().join(format!("publicflow_import_{}", std::process::id()));
+        let config_root = format!("{}/", config_root.display());
+        write_town(
+            &format!("{}importedtown/", config_root),
+            &station_configs,
+            &line_configs,
+        )
+        .unwrap();
+        let raw_config: Value =
+            serde_yaml::from_str("general:\n  town: importedtown\n  quiet: true\n").unwrap();
+        // Stops the test with a report if the written files are not valid
+        let config = parse_config(
+            &config_root,
+            "config.yaml",
+            &raw_config,
+            &Value::Mapping(Mapping::new()),
+        );
+        fs::remove_dir_all(&config_root).unwrap();
+
+        assert_eq!(config.network.n_stations, 5);
+        assert_eq!(config.logic.pod_capacity, 90);
+        assert_eq!(config.visual.latitude_range_bounds, (11.56, 11.58));
+        for station_config in &station_configs {
+            let (name, entrypoints, city, coordinates) =
+                &config.network.coordinates_map_stations[&station_config.id];
+            assert_eq!(name, &station_config.name);
+            assert_eq!(entrypoints, &station_config.entrypoint_for);
+            assert_eq!(city, &station_config.city);
+            assert_eq!(*coordinates, (station_config.lat, station_config.lon));
+        }
+        let lines: Vec<(LineName, Vec<i32>, bool)> = config
+            .network
+            .lines
+            .iter()
+            .map(|line| (line.name.clone(), line.stations.clone(), line.circular))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                (LineName::U(1), vec![0, 1, 2], false),
+                (LineName::T(12), vec![2, 3, 4], true),
+            ]
+        );
+    }
+}
@@ -0,0 +1,399 @@
+use crate::config::structs::{LineConfig, StationConfig};
+use crate::helper::enums::OnBlock;
+use crate::helper::functions::try_transform_line_name_to_enum;
+use crate::import::{collect_entrypoints, haversine_meters, read_records};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use strsim::jaro_winkler;
+
+// Names that are this similar after normalizing count as the same station
+const MIN_NAME_SIMILARITY: f64 = 0.9;
+
+// A row of the cleansed stations csv. Its lat column is the horizontal axis like in the
+// towns, so it holds the longitude and lon holds the latitude.
+struct WikiStation {
+    name: String,
+    city: String,
+    lat: f64,
+    lon: f64,
+}
+
+// A station of a line as written in the wiki and the distance to the one before it, if given
+struct WikiStop {
+    name: String,
+    distance: Option<i32>,
+}
+
+// Lowercase, without punctuation and with the usual abbreviation of Straße, so that
+// "St.-Quirin-Platz" matches "St. Quirin Platz" and "Lothstraße" matches "Lothstr."
+fn normalize_name(name: &str) -> String {
+    name.to_lowercase()
+        .replace("straße", "str")
+        .replace("strasse", "str")
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect()
+}
+
+struct StationMatcher {
+    stations: Vec<WikiStation>,
+    normalized_names: Vec<String>,
+    city: Option<String>,
+}
+
+impl StationMatcher {
+    fn new(stations: Vec<WikiStation>, city: &Option<String>) -> Self {
+        let normalized_names = stations
+            .iter()
+            .map(|station| normalize_name(&station.name))
+            .collect();
+        StationMatcher {
+            stations: stations,
+            normalized_names: normalized_names,
+            city: city.clone(),
+        }
+    }
+
+    // Stations of the configured city win over ones of the same name elsewhere
+    fn pick(&self, candidates: Vec<usize>) -> Option<usize> {
+        if let Some(city) = &self.city {
+            if let Some(ix) = candidates
+                .iter()
+                .find(|ix| &self.stations[**ix].city == city)
+            {
+                return Some(*ix);
+            }
+        }
+        candidates.first().copied()
+    }
+
+    // The exact name, then the normalized name, then the most similar normalized name
+    fn try_match(&self, name: &str) -> Option<usize> {
+        let exact: Vec<usize> = (0..self.stations.len())
+            .filter(|ix| self.stations[*ix].name == name)
+            .collect();
+        if !exact.is_empty() {
+            return self.pick(exact);
+        }
+
+        let normalized = normalize_name(name);
+        let same_normalized: Vec<usize> = (0..self.stations.len())
+            .filter(|ix| self.normalized_names[*ix] == normalized)
+            .collect();
+        if !same_normalized.is_empty() {
+            return self.pick(same_normalized);
+        }
+
+        let mut best: Option<(usize, f64)> = None;
+        for (ix, candidate) in self.normalized_names.iter().enumerate() {
+            if let Some(city) = &self.city {
+                if &self.stations[ix].city != city {
+                    continue;
+                }
+            }
+            let similarity = jaro_winkler(&normalized, candidate);
+            let is_better = match best {
+                Some((_, best_similarity)) => similarity > best_similarity,
+                None => true,
+            };
+            if similarity >= MIN_NAME_SIMILARITY && is_better {
+                best = Some((ix, similarity));
+            }
+        }
+        if let Some((ix, similarity)) = best {
+            println!(
+                "matched \"{}\" to \"{}\" ({:.2})",
+                name, self.stations[ix].name, similarity
+            );
+            return Some(ix);
+        }
+        None
+    }
+
+    fn distance_between(&self, from_ix: usize, to_ix: usize) -> i32 {
+        let from = &self.stations[from_ix];
+        let to = &self.stations[to_ix];
+        let meters = haversine_meters((from.lon, from.lat), (to.lon, to.lat));
+        (meters.round() as i32).max(1)
+    }
+}
+
+fn load_stations(stations_path: &str) -> Result<Vec<WikiStation>, String> {
+    let file =
+        File::open(stations_path).map_err(|e| format!("can't open {}: {}", stations_path, e))?;
+    let mut stations = vec![];
+    read_records(file, stations_path, b',', &mut |columns, record| {
+        let lat: Option<f64> = columns.get(record, "lat").parse().ok();
+        let lon: Option<f64> = columns.get(record, "lon").parse().ok();
+        if let (Some(lat), Some(lon)) = (lat, lon) {
+            stations.push(WikiStation {
+                name: columns.get(record, "name").to_string(),
+                city: columns.get(record, "city").to_string(),
+                lat: lat,
+                lon: lon,
+            });
+        }
+    })?;
+    Ok(stations)
+}
+
+// "Station – (625m) – Station – Station", distances are optional
+fn parse_stops(description: &str) -> Vec<WikiStop> {
+    let mut stops: Vec<WikiStop> = vec![];
+    let mut distance: Option<i32> = None;
+    for part in description.split('–').map(|part| part.trim()) {
+        if part.is_empty() {
+            continue;
+        }
+        if part.starts_with('(') && part.ends_with("m)") {
+            distance = part[1..part.len() - 2].trim().parse().ok();
+            continue;
+        }
+        stops.push(WikiStop {
+            name: part.to_string(),
+            distance: distance.take(),
+        });
+    }
+    stops
+}
+
+// Builds stations and lines from the semicolon separated line files taken from the
+// wiki. Station names are matched against the stations csv, only stations that are
+// part of a line end up in the town.
+pub fn import_wiki(
+    lines_paths: &Vec<String>,
+    stations_path: &str,
+    city: &Option<String>,
+) -> Result<(Vec<StationConfig>, Vec<LineConfig>), String> {
+    if lines_paths.is_empty() {
+        return Err(String::from("import.wiki needs at least one lines file."));
+    }
+    let matcher = StationMatcher::new(load_stations(stations_path)?, city);
+
+    let mut wiki_lines: Vec<(String, String)> = vec![];
+    for lines_path in lines_paths {
+        let file =
+            File::open(lines_path).map_err(|e| format!("can't open {}: {}", lines_path, e))?;
+        read_records(file, lines_path, b';', &mut |_, record| {
+            let name = record.get(0).unwrap_or("").trim();
+            let description = record.get(1).unwrap_or("").trim();
+            wiki_lines.push((name.to_string(), description.to_string()));
+        })?;
+    }
+
+    let mut station_ids: HashMap<usize, i32> = HashMap::new();
+    let mut station_ixs: Vec<usize> = vec![];
+    let mut taken_names: HashSet<String> = HashSet::new();
+    let mut line_configs: Vec<LineConfig> = vec![];
+    let mut n_unmatched = 0;
+    for (name, description) in wiki_lines {
+        let line_name = match try_transform_line_name_to_enum(&name) {
+            Some(line_name) => line_name,
+            None => {
+                println!("{} is no subway, tram or suburban line, skipping it.", name);
+                continue;
+            }
+        };
+        if !taken_names.insert(name.to_lowercase()) {
+            println!("{} is described twice, skipping the second one.", name);
+            continue;
+        }
+
+        // Stations that can't be matched are left out, their neighbours get connected
+        // with the great-circle distance
+        let mut line_ixs: Vec<usize> = vec![];
+        let mut distances: Vec<i32> = vec![];
+        let mut skipped_before = false;
+        for stop in parse_stops(&description) {
+            let ix = match matcher.try_match(&stop.name) {
+                Some(ix) => ix,
+                None => {
+                    println!("no station matches \"{}\" of {}.", stop.name, name);
+                    n_unmatched += 1;
+                    skipped_before = true;
+                    continue;
+                }
+            };
+            if let Some(last_ix) = line_ixs.last() {
+                if *last_ix == ix {
+                    continue;
+                }
+                let distance = match stop.distance {
+                    Some(distance) if !skipped_before => distance,
+                    _ => matcher.distance_between(*last_ix, ix),
+                };
+                distances.push(distance);
+            }
+            line_ixs.push(ix);
+            skipped_before = false;
+        }
+
+        let circular = line_ixs.len() > 2 && line_ixs.first() == line_ixs.last();
+        if circular {
+            line_ixs.pop();
+        }
+        if line_ixs.len() < 2 {
+            println!("{} has less than two stations, skipping it.", name);
+            continue;
+        }
+        let mut seen: HashSet<usize> = HashSet::new();
+        if line_ixs.iter().any(|ix| !seen.insert(*ix)) {
+            println!("{} visits a station more than once, skipping it.", name);
+            continue;
+        }
+
+        let mut line_station_ids: Vec<i32> = vec![];
+        for ix in line_ixs {
+            let id = *station_ids.entry(ix).or_insert_with(|| {
+                station_ixs.push(ix);
+                station_ixs.len() as i32 - 1
+            });
+            line_station_ids.push(id);
+        }
+
+        line_configs.push(LineConfig {
+            name: line_name,
+            stations: line_station_ids,
+            distances: distances,
+            circular: circular,
+            on_block: OnBlock::Wait,
+        });
+    }
+    if n_unmatched > 0 {
+        println!(
+            "{} stations of the lines could not be matched.",
+            n_unmatched
+        );
+    }
+
+    let mut entrypoints = collect_entrypoints(&line_configs);
+    let station_configs: Vec<StationConfig> = station_ixs
+        .iter()
+        .enumerate()
+        .map(|(id, ix)| {
+            let station = &matcher.stations[*ix];
+            StationConfig {
+                id: id as i32,
+                name: station.name.clone(),
+                city: station.city.clone(),
+                lat: station.lat as f32,
+                lon: station.lon as f32,
+                entrypoint_for: entrypoints.remove(&(id as i32)).unwrap_or_default(),
+            }
+        })
+        .collect();
+
+    Ok((station_configs, line_configs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper::enums::LineName;
+    use std::fs;
+
+    // Marienplatz is also in Augsburg, the configured city decides
+    const STATIONS_CSV: &str = "name,city,lat,lon
+Marienplatz,Augsburg,10.898,48.369
+Marienplatz,München,11.575,48.137
+Sendlinger Tor,München,11.566,48.134
+Goetheplatz,München,11.557,48.129
+Lothstraße,München,11.550,48.154
+St. Quirin Platz,München,11.564,48.091
+";
+
+    const LINES_CSV: &str = "name;stations
+U1;Marienplatz – (625m) – Sendlinger Tor – (700m) – Goetheplatz
+U2;Lothstr. – (900m) – Nowhere – (800m) – Sendlinger Tor
+X9;Marienplatz – Goetheplatz
+u1;Goetheplatz – Lothstraße
+T16;St.-Quirin-Platz – Goetheplatz – Sendlinger Tor – St.-Quirin-Platz
+";
+
+    // Between two rows of STATIONS_CSV, lat is the longitude there
+    fn get_meters(from: (f64, f64), to: (f64, f64)) -> i32 {
+        haversine_meters((from.1, from.0), (to.1, to.0)).round() as i32
+    }
+
+    #[test]
+    fn lines_from_the_wiki() {
+        let root = std::env::temp_dir().join(format!("publicflow_wiki_{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let stations_path = root.join("stations.csv");
+        let lines_path = root.join("lines.csv");
+        fs::write(&stations_path, STATIONS_CSV).unwrap();
+        fs::write(&lines_path, LINES_CSV).unwrap();
+        let imported = import_wiki(
+            &vec![lines_path.to_str().unwrap().to_string()],
+            stations_path.to_str().unwrap(),
+            &Some(String::from("München")),
+        );
+        fs::remove_dir_all(&root).unwrap();
+        let (station_configs, line_configs) = imported.unwrap();
+
+        let stations: Vec<(i32, &str, &str, Vec<&str>)> = station_configs
+            .iter()
+            .map(|station| {
+                (
+                    station.id,
+                    station.name.as_str(),
+                    station.city.as_str(),
+                    station.entrypoint_for.iter().map(|e| e.as_str()).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            stations,
+            vec![
+                (0, "Marienplatz", "München", vec!["u1+"]),
+                (1, "Sendlinger Tor", "München", vec!["u2-"]),
+                (2, "Goetheplatz", "München", vec!["u1-"]),
+                (3, "Lothstraße", "München", vec!["u2+"]),
+                (4, "St. Quirin Platz", "München", vec!["t16+-"]),
+            ]
+        );
+        assert_eq!(
+            (station_configs[0].lat, station_configs[0].lon),
+            (11.575, 48.137)
+        );
+
+        let sendlinger_tor = (11.566, 48.134);
+        let goetheplatz = (11.557, 48.129);
+        let lothstrasse = (11.550, 48.154);
+        let st_quirin_platz = (11.564, 48.091);
+        let lines: Vec<(LineName, Vec<i32>, Vec<i32>, bool)> = line_configs
+            .iter()
+            .map(|line| {
+                (
+                    line.name.clone(),
+                    line.stations.clone(),
+                    line.distances.clone(),
+                    line.circular,
+                )
+            })
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                (LineName::U(1), vec![0, 1, 2], vec![625, 700], false),
+                // The stop that is not matched is left out, its distances with it
+                (
+                    LineName::U(2),
+                    vec![3, 1],
+                    vec![get_meters(lothstrasse, sendlinger_tor)],
+                    false
+                ),
+                (
+                    LineName::T(16),
+                    vec![4, 2, 1],
+                    vec![
+                        get_meters(st_quirin_platz, goetheplatz),
+                        get_meters(goetheplatz, sendlinger_tor),
+                        get_meters(sendlinger_tor, st_quirin_platz),
+                    ],
+                    true
+                ),
+            ]
+        );
+    }
+}
@@ -1,18 +1,20 @@
 use crate::config::structs::Config;
 use crate::connection::{Connection, YieldTuple};
-use crate::helper::enums::LineName;
+use crate::helper::enums::{LineName, OnBlock};
 use crate::helper::functions::get_screen_coordinates;
 use crate::network::Network;
 use ggez::{graphics, Context, GameResult};
+use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Line {
     pub name: LineName,
     pub stations: Vec<i32>,
     pub distances: Vec<i32>,
     pub circular: bool,
     pub connections: Vec<Connection>,
+    pub on_block: OnBlock,
 }
 
 impl Line {
@@ -64,6 +66,33 @@ impl Line {
         res
     }
 
+    // Whether a pod in the station at ix that drives in direction turns back there. Only
+    // worth it if the line is configured for it and the way back is not blocked as well.
+    pub fn turns_back_at(&self, ix: usize, direction: i32) -> bool {
+        self.on_block == OnBlock::TurnBack
+            && self.is_blocked_after(ix, direction)
+            && !self.is_blocked_after(ix, -direction)
+    }
+
+    // Whether a pod in the station at ix that drives in direction can't go on
+    pub fn is_blocked_after(&self, ix: usize, direction: i32) -> bool {
+        let n_stations = self.stations.len() as i32;
+        let mut next_ix = ix as i32 + direction;
+        if next_ix < 0 || next_ix >= n_stations {
+            if !self.circular {
+                return false;
+            }
+            next_ix = (next_ix + n_stations) % n_stations;
+        }
+        let station_ids = HashSet::from([self.stations[ix], self.stations[next_ix as usize]]);
+        for connection in &self.connections {
+            if connection.station_ids == station_ids {
+                return connection.is_blocked;
+            }
+        }
+        false
+    }
+
     pub fn block_connection(&mut self, ids: &HashSet<i32>) {
         for connection in &mut self.connections {
             if &connection.station_ids == ids {
@@ -84,3 +113,40 @@ impl Line {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::helper::enums::OnBlock;
+    use crate::helper::testing::{get_config, get_line, get_station};
+    use std::collections::HashSet;
+
+    #[test]
+    fn turns_back_only_if_the_way_back_is_open() {
+        let config = get_config(
+            (1..=4).map(|id| get_station(id, id as f32, 0.)).collect(),
+            vec![get_line("u1", vec![1, 2, 3, 4], OnBlock::TurnBack)],
+        );
+        let mut line = config.network.lines[0].clone();
+        assert!(!line.turns_back_at(1, 1));
+
+        line.block_connection(&HashSet::from([2, 3]));
+        assert!(line.turns_back_at(1, 1));
+        assert!(line.turns_back_at(2, -1));
+        assert!(!line.turns_back_at(1, -1));
+
+        // Blocked on both sides the pod waits in 2
+        line.block_connection(&HashSet::from([1, 2]));
+        assert!(!line.turns_back_at(1, 1));
+    }
+
+    #[test]
+    fn waiting_lines_never_turn_back() {
+        let config = get_config(
+            (1..=3).map(|id| get_station(id, id as f32, 0.)).collect(),
+            vec![get_line("u1", vec![1, 2, 3], OnBlock::Wait)],
+        );
+        let mut line = config.network.lines[0].clone();
+        line.block_connection(&HashSet::from([2, 3]));
+        assert!(!line.turns_back_at(1, 1));
+    }
+}
@@ -1,12 +1,13 @@
 use crate::connection::Connection;
 use crate::helper::enums::Direction;
 use crate::line::line::Line;
+use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 
 // block connection 650-641-631-611
 // block connections 5-44 5-0
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct LineState {
     pub line: Line,
     pub line_ix: i32,
@@ -43,6 +44,22 @@ impl LineState {
         }
     }
 
+    pub fn is_blocked_ahead(&self) -> bool {
+        self.line
+            .is_blocked_after(self.line_ix as usize, self.direction)
+    }
+
+    // Reverse in the current station, the next station is the one the pod came from
+    pub fn turn_back(&mut self) {
+        self.direction *= -1;
+        self.set_next_station_ix();
+    }
+
+    pub fn should_turn_back(&self) -> bool {
+        self.line
+            .turns_back_at(self.line_ix as usize, self.direction)
+    }
+
     pub fn update_line_ix(&mut self) {
         self.line_ix = self.next_ix;
     }
@@ -1,62 +1,95 @@
 mod config;
 mod connection;
 mod control;
+mod export;
 mod helper;
+mod import;
 mod line;
 mod metrics;
 mod network;
 mod pathstate;
 mod person;
 mod pod;
+mod routing;
+mod snapshot;
 mod state;
 mod station;
+mod sweep;
 
-use crate::config::constants::{CONFIG_NAME, CONFIG_ROOT};
-use crate::config::processing::{load_yaml, parse_config};
-use crate::control::cli::run_cli;
-use crate::control::proxy::run_proxy;
+use crate::config::args::Args;
+use crate::config::processing::{load_yaml, parse_config, parse_import_config, parse_sweep_config};
+use crate::config::structs::ExecutionMode;
+use crate::control::{queue_command_on_start, spawn_control};
+use crate::import::run_import;
 use crate::state::State;
+use crate::sweep::run_sweep;
 use chrono::DateTime;
+use clap::Parser;
 use ggez::event::{self};
 use ggez::graphics::set_window_title;
 use ggez::ContextBuilder;
-use std::sync::mpsc;
-use std::thread;
+use std::process::exit;
 use std::time::SystemTime;
 
 fn main() {
+    let args = Args::parse();
     println!("start simulation...");
-    let config_yaml = load_yaml(CONFIG_ROOT, CONFIG_NAME);
-    let mut config = parse_config(&config_yaml);
+    let raw_overrides = match args.to_overrides() {
+        Ok(raw_overrides) => raw_overrides,
+        Err(e) => {
+            println!("{}", e);
+            exit(1);
+        }
+    };
+    let config_root = args.get_config_root();
+    let config_name = args.get_config_name();
+    let config_yaml = load_yaml(&config_root, &config_name);
+    // Importing only writes a town and needs none of the town's config files
+    let config_file = format!("{}{}", config_root, config_name);
+    if let Some(import_config) = parse_import_config(&config_file, &config_yaml, &raw_overrides) {
+        run_import(&import_config, &config_root);
+        return;
+    }
+    if let Some(sweep_config) = parse_sweep_config(&config_file, &config_yaml, &raw_overrides) {
+        let code = run_sweep(
+            &sweep_config,
+            &config_root,
+            &config_name,
+            &config_yaml,
+            &raw_overrides,
+        );
+        exit(code);
+    }
+    let mut config = parse_config(&config_root, &config_name, &config_yaml, &raw_overrides);
     config.add_timestamp_run(DateTime::from(SystemTime::now()));
 
-    let (proxy_tx, proxy_rx) = mpsc::channel();
-    let (tx, rx) = mpsc::channel();
+    match config.general.mode {
+        ExecutionMode::Headless => {
+            let rx = queue_command_on_start(&config);
+            let mut state = State::new(config, rx).add_people();
+            exit(state.run_headless());
+        }
+        ExecutionMode::Visual => {
+            // Make a Context.
+            let (ctx, event_loop) = ContextBuilder::new("PublicFlow", "David Schmider")
+                .window_setup(ggez::conf::WindowSetup::default().title("PublicFlow Simulation"))
+                .window_setup(ggez::conf::WindowSetup::default().vsync(config.visual.vsync)) // sync fps to screen refresh rate
+                .window_mode(
+                    ggez::conf::WindowMode::default()
+                        .dimensions(config.visual.screen_size.0, config.visual.screen_size.1),
+                )
+                .build()
+                .expect("aieee, could not create ggez context!");
 
-    let config_for_cli = config.clone();
-    thread::spawn(|| {
-        let _res = run_cli(proxy_tx, config_for_cli);
-    });
+            set_window_title(&ctx, "PublicFlow");
 
-    thread::spawn(|| {
-        run_proxy(proxy_rx, tx);
-    });
+            let rx = spawn_control(&config);
+            // let state = State::new(config, rx).add_pods().add_people();
+            let state = State::new(config, rx).add_people();
 
-    // Make a Context.
-    let (ctx, event_loop) = ContextBuilder::new("PublicFlow", "David Schmider")
-        .window_setup(ggez::conf::WindowSetup::default().title("PublicFlow Simulation"))
-        .window_setup(ggez::conf::WindowSetup::default().vsync(config.visual.vsync)) // sync fps to screen refresh rate
-        .window_mode(
-            ggez::conf::WindowMode::default()
-                .dimensions(config.visual.screen_size.0, config.visual.screen_size.1),
-        )
-        .build()
-        .expect("aieee, could not create ggez context!");
-
-    set_window_title(&ctx, "PublicFlow");
-
-    // let state = State::new(config, rx).add_pods().add_people();
-    let state = State::new(config, rx).add_people();
-
-    event::run(ctx, event_loop, state);
+            event::run(ctx, event_loop, state);
+        }
+        // Already handled above, before the town is loaded
+        ExecutionMode::Import | ExecutionMode::Sweep => {}
+    }
 }
@@ -0,0 +1,135 @@
+use crate::helper::enums::{Direction, LineName};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+pub const LINK_HEADER: &[&str] = &[
+    "station_from",
+    "station_to",
+    "line",
+    "direction",
+    "hour",
+    "pods",
+    "pax",
+    "capacity",
+    "load_factor",
+];
+
+// The pods of one line and direction that drove over a connection in one hour of the
+// day, with the people in them and the places they offered. The hour is the one the
+// pod departed in, runs over several days add up the same hour of every day.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LinkLoad {
+    pub station_from: i32,
+    pub station_to: i32,
+    pub line_name: LineName,
+    pub direction: Direction,
+    pub hour: u32,
+    pub pods: u32,
+    pub pax: u32,
+    pub capacity: u32,
+}
+
+impl LinkLoad {
+    pub fn new(
+        station_from: i32,
+        station_to: i32,
+        line_name: LineName,
+        direction: Direction,
+        hour: u32,
+    ) -> LinkLoad {
+        LinkLoad {
+            station_from: station_from,
+            station_to: station_to,
+            line_name: line_name,
+            direction: direction,
+            hour: hour,
+            pods: 0,
+            pax: 0,
+            capacity: 0,
+        }
+    }
+
+    pub fn add_pod(&mut self, pax: u32, capacity: u32) {
+        self.pods += 1;
+        self.pax += pax;
+        self.capacity += capacity;
+    }
+
+    pub fn get_load_factor(&self) -> f32 {
+        if self.capacity == 0 {
+            return 0.;
+        }
+        self.pax as f32 / self.capacity as f32
+    }
+
+    fn get_key(&self) -> LinkKey {
+        (
+            self.station_from,
+            self.station_to,
+            self.line_name.clone(),
+            self.direction.clone(),
+            self.hour,
+        )
+    }
+
+    fn get_direction_str(&self) -> &'static str {
+        match self.direction {
+            Direction::Pos => "+",
+            Direction::Neg => "-",
+        }
+    }
+
+    // In the order of LINK_HEADER
+    pub fn to_record(&self) -> Vec<String> {
+        vec![
+            self.station_from.to_string(),
+            self.station_to.to_string(),
+            self.line_name.to_config_string(),
+            self.get_direction_str().to_string(),
+            self.hour.to_string(),
+            self.pods.to_string(),
+            self.pax.to_string(),
+            self.capacity.to_string(),
+            self.get_load_factor().to_string(),
+        ]
+    }
+}
+
+type LinkKey = (i32, i32, LineName, Direction, u32);
+
+// Every pod that leaves a station is counted on the connection to the next one
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct LinkLoads {
+    loads: HashMap<LinkKey, LinkLoad>,
+}
+
+impl LinkLoads {
+    pub fn new() -> LinkLoads {
+        LinkLoads {
+            loads: HashMap::new(),
+        }
+    }
+
+    // The link is new or one that has the same stations, line, direction and hour
+    pub fn add_pod(&mut self, link: LinkLoad, pax: u32, capacity: u32) {
+        self.loads
+            .entry(link.get_key())
+            .or_insert(link)
+            .add_pod(pax, capacity);
+    }
+
+    // Sorted by line, direction and hour, the connections of one hour by their stations
+    pub fn get_sorted(&self) -> Vec<&LinkLoad> {
+        let mut loads: Vec<&LinkLoad> = self.loads.values().collect();
+        loads.sort_by_key(|load| {
+            (
+                load.line_name.to_config_string(),
+                load.get_direction_str(),
+                load.hour,
+                load.station_from,
+                load.station_to,
+            )
+        });
+        loads
+    }
+}
@@ -1,2 +1,6 @@
+pub mod link;
 pub mod person;
+pub mod platform;
 pub mod pod;
+pub mod station;
+pub mod trip;
@@ -1,6 +1,7 @@
 use crate::metrics::traits::Metrics;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct PersonMetrics {
     pub number_of_pods: f32,
     pub time_in_station: f32,
@@ -0,0 +1,61 @@
+use serde::{Deserialize, Serialize};
+
+pub const PLATFORM_HEADER: &str = "station_id,lines,direction,pods_served,pods_per_hour,station_pods_per_hour,load,avg_dwell,avg_queue_length,max_queue_length\n";
+
+// Only the totals are kept for a platform, there are too many of them for a time series
+// that would tell more than the one of their station.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PlatformMetrics {
+    pub seconds_gathered: f32,
+    pub pods_served: f32,
+    // Seconds pods stood at the platform and seconds pods waited in its queue
+    pub dwell_time: f32,
+    pub queue_time: f32,
+    pub max_queue_length: f32,
+}
+
+// values should be a float to calculate averages more accurately
+
+impl PlatformMetrics {
+    pub fn new() -> PlatformMetrics {
+        PlatformMetrics {
+            seconds_gathered: 0.,
+            pods_served: 0.,
+            dwell_time: 0.,
+            queue_time: 0.,
+            max_queue_length: 0.,
+        }
+    }
+
+    pub fn increase_pods_served(&mut self) {
+        self.pods_served += 1.;
+    }
+
+    pub fn add_second(&mut self, pods_at_platform: f32, queue_length: f32) {
+        self.seconds_gathered += 1.;
+        self.dwell_time += pods_at_platform;
+        self.queue_time += queue_length;
+        self.max_queue_length = self.max_queue_length.max(queue_length);
+    }
+
+    pub fn get_pods_per_hour(&self) -> f32 {
+        if self.seconds_gathered == 0. {
+            return 0.;
+        }
+        self.pods_served * 3600. / self.seconds_gathered
+    }
+
+    pub fn get_avg_dwell(&self) -> f32 {
+        if self.pods_served == 0. {
+            return 0.;
+        }
+        self.dwell_time / self.pods_served
+    }
+
+    pub fn get_avg_queue_length(&self) -> f32 {
+        if self.seconds_gathered == 0. {
+            return 0.;
+        }
+        self.queue_time / self.seconds_gathered
+    }
+}
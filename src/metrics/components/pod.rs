@@ -1,12 +1,15 @@
 use crate::metrics::traits::Metrics;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct PodMetrics {
     pub utilization: f32,
     pub time_in_station: f32,
     pub time_in_queue: f32,
     pub time_driving: f32,
     pub meters_traveled: f32,
+    // Seconds behind the timetable in the last stop, negative if early
+    pub lateness: f32,
 }
 
 // values should be a float to calculate averages more accurately
@@ -19,6 +22,7 @@ impl PodMetrics {
             time_in_queue: 0.,
             time_driving: 0.,
             meters_traveled: 0.,
+            lateness: 0.,
         }
     }
 
@@ -41,6 +45,10 @@ impl PodMetrics {
     pub fn increase_meters_traveled(&mut self, meters: f32) {
         self.meters_traveled += meters;
     }
+
+    pub fn set_lateness(&mut self, lateness: f32) {
+        self.lateness = lateness;
+    }
 }
 
 impl Metrics for PodMetrics {
@@ -50,6 +58,7 @@ impl Metrics for PodMetrics {
         self.time_in_queue += other.time_in_queue;
         self.time_driving += other.time_driving;
         self.meters_traveled += other.meters_traveled;
+        self.lateness += other.lateness;
     }
 
     fn normalize_by(&mut self, number_of_pods: u32) {
@@ -58,16 +67,18 @@ impl Metrics for PodMetrics {
         self.time_in_queue /= number_of_pods as f32;
         self.time_driving /= number_of_pods as f32;
         self.meters_traveled /= number_of_pods as f32;
+        self.lateness /= number_of_pods as f32;
     }
 
     fn format_to_string(&self) -> String {
         format!(
-            "{},{},{},{},{}",
+            "{},{},{},{},{},{}",
             self.utilization,
             self.time_in_station,
             self.time_in_queue,
             self.time_driving,
-            self.meters_traveled
+            self.meters_traveled,
+            self.lateness
         )
     }
 }
@@ -0,0 +1,75 @@
+use crate::metrics::traits::Metrics;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct StationMetrics {
+    // How many people are in the station and how many pods queue at its platforms right now
+    pub people_in_station: f32,
+    pub queue_length: f32,
+    pub pods_served: f32,
+    pub boardings: f32,
+    pub alightings: f32,
+}
+
+// values should be a float to calculate averages more accurately
+
+impl StationMetrics {
+    pub fn new() -> StationMetrics {
+        StationMetrics {
+            people_in_station: 0.,
+            queue_length: 0.,
+            pods_served: 0.,
+            boardings: 0.,
+            alightings: 0.,
+        }
+    }
+
+    pub fn set_people_in_station(&mut self, people_in_station: f32) {
+        self.people_in_station = people_in_station;
+    }
+
+    pub fn set_queue_length(&mut self, queue_length: f32) {
+        self.queue_length = queue_length;
+    }
+
+    pub fn set_pods_served(&mut self, pods_served: f32) {
+        self.pods_served = pods_served;
+    }
+
+    pub fn increase_boardings(&mut self) {
+        self.boardings += 1.;
+    }
+
+    pub fn increase_alightings(&mut self) {
+        self.alightings += 1.;
+    }
+}
+
+impl Metrics for StationMetrics {
+    fn add_metrics(&mut self, other: &StationMetrics) {
+        self.people_in_station += other.people_in_station;
+        self.queue_length += other.queue_length;
+        self.pods_served += other.pods_served;
+        self.boardings += other.boardings;
+        self.alightings += other.alightings;
+    }
+
+    fn normalize_by(&mut self, number_of_stations: u32) {
+        self.people_in_station /= number_of_stations as f32;
+        self.queue_length /= number_of_stations as f32;
+        self.pods_served /= number_of_stations as f32;
+        self.boardings /= number_of_stations as f32;
+        self.alightings /= number_of_stations as f32;
+    }
+
+    fn format_to_string(&self) -> String {
+        format!(
+            "{},{},{},{},{}",
+            self.people_in_station,
+            self.queue_length,
+            self.pods_served,
+            self.boardings,
+            self.alightings
+        )
+    }
+}
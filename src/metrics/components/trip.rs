@@ -0,0 +1,138 @@
+use serde::{Deserialize, Serialize};
+
+pub const TRIP_HEADER: &[&str] = &[
+    "person_id",
+    "origin",
+    "destination",
+    "start",
+    "end",
+    "travel_time",
+    "initial_wait",
+    "transfer_wait",
+    "in_vehicle_time",
+    "number_of_transfers",
+    "shortest_time",
+    "air_time",
+    "detour_ratio",
+    "aborted",
+];
+
+// One trip of a person from the station it starts in to the one it ends in. Waiting
+// is the time in stations, before the first pod or between two of them, that includes
+// the transition time. shortest_time is the least time the trip can take on the routing
+// graph when it started or was planned again, without waiting for pods, so the detour
+// ratio is never below 1. air_time is the time for the beeline. Trips that started
+// before metrics were gathered get shortest_time when they end. A trip is aborted if
+// the person was left without a route to where it was headed, it ends where they got stuck.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct TripMetrics {
+    pub person_id: i32,
+    pub origin: i32,
+    pub destination: i32,
+    pub start: u32,
+    pub end: u32,
+    pub initial_wait: u32,
+    pub transfer_wait: u32,
+    pub in_vehicle_time: u32,
+    pub number_of_transfers: u32,
+    pub shortest_time: Option<u32>,
+    pub air_time: u32,
+    pub aborted: bool,
+    number_of_pods: u32,
+    waiting_since: u32,
+    riding_since: u32,
+}
+
+impl TripMetrics {
+    pub fn new(
+        person_id: i32,
+        origin: i32,
+        destination: i32,
+        start: u32,
+        shortest_time: Option<u32>,
+        air_time: u32,
+    ) -> TripMetrics {
+        TripMetrics {
+            person_id: person_id,
+            origin: origin,
+            destination: destination,
+            start: start,
+            end: start,
+            initial_wait: 0,
+            transfer_wait: 0,
+            in_vehicle_time: 0,
+            number_of_transfers: 0,
+            shortest_time: shortest_time,
+            air_time: air_time,
+            aborted: false,
+            number_of_pods: 0,
+            waiting_since: start,
+            riding_since: start,
+        }
+    }
+
+    pub fn board(&mut self, time_passed: u32) {
+        let wait = time_passed - self.waiting_since;
+        if self.number_of_pods == 0 {
+            self.initial_wait += wait;
+        } else {
+            self.transfer_wait += wait;
+            self.number_of_transfers += 1;
+        }
+        self.number_of_pods += 1;
+        self.riding_since = time_passed;
+    }
+
+    pub fn alight(&mut self, time_passed: u32) {
+        self.in_vehicle_time += time_passed - self.riding_since;
+        self.waiting_since = time_passed;
+    }
+
+    // Where the trip ends can differ from where it was headed when it was routed elsewhere
+    pub fn complete(&mut self, station_id: i32, time_passed: u32) {
+        self.alight(time_passed);
+        self.destination = station_id;
+        self.end = time_passed;
+    }
+
+    // The person already got off, the time since then is only part of the travel time
+    pub fn end_in_station(&mut self, station_id: i32, time_passed: u32) {
+        self.destination = station_id;
+        self.end = time_passed;
+    }
+
+    pub fn get_travel_time(&self) -> u32 {
+        self.end - self.start
+    }
+
+    // How much longer the trip took than the fastest route, None without a route
+    pub fn get_detour_ratio(&self) -> Option<f32> {
+        match self.shortest_time {
+            Some(shortest_time) if shortest_time > 0 => {
+                Some(self.get_travel_time() as f32 / shortest_time as f32)
+            }
+            _ => None,
+        }
+    }
+
+    // In the order of TRIP_HEADER
+    pub fn to_record(&self) -> Vec<String> {
+        let format_option = |value: Option<String>| value.unwrap_or_default();
+        vec![
+            self.person_id.to_string(),
+            self.origin.to_string(),
+            self.destination.to_string(),
+            self.start.to_string(),
+            self.end.to_string(),
+            self.get_travel_time().to_string(),
+            self.initial_wait.to_string(),
+            self.transfer_wait.to_string(),
+            self.in_vehicle_time.to_string(),
+            self.number_of_transfers.to_string(),
+            format_option(self.shortest_time.map(|time| time.to_string())),
+            self.air_time.to_string(),
+            format_option(self.get_detour_ratio().map(|ratio| ratio.to_string())),
+            self.aborted.to_string(),
+        ]
+    }
+}
@@ -0,0 +1,191 @@
+use crate::helper::enums::{Direction, LineName};
+use csv::Writer;
+use serde::{Serialize, Serializer};
+use std::fs::{create_dir_all, File};
+use std::io::{BufWriter, Write};
+
+pub const EVENT_HEADER: &[&str] = &[
+    "time_passed",
+    "event",
+    "pod_id",
+    "person_id",
+    "station_id",
+    "to_station_id",
+    "line_name",
+    "direction",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    // Pods
+    PodSpawn,
+    Arrive,
+    Queue,
+    Pass,
+    Depart,
+    TurnBack,
+    Retire,
+    // People
+    Spawn,
+    Board,
+    Alight,
+    Transfer,
+    TripComplete,
+    Exit,
+    // Network
+    PlatformOperational,
+    PlatformPassable,
+    PlatformQueueable,
+    Block,
+    Unblock,
+}
+
+impl EventKind {
+    // The snake_case name serde writes into the jsonl, so the csv uses the same one
+    pub fn get_name(&self) -> String {
+        match serde_json::to_value(self) {
+            Ok(serde_json::Value::String(name)) => name,
+            _ => String::new(),
+        }
+    }
+}
+
+// One state transition. Which ids are set depends on the kind: pod events have the pod,
+// its line and direction, people events the person and the pod they got in or out of,
+// network events the station or both ends of the connection.
+#[derive(Debug, Clone, Serialize)]
+pub struct Event {
+    pub time_passed: u32,
+    pub event: EventKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pod_id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub person_id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub station_id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to_station_id: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line_name: Option<LineName>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_direction"
+    )]
+    pub direction: Option<Direction>,
+}
+
+// Written as in the scripts, + or -
+fn format_direction(direction: &Direction) -> &'static str {
+    match direction {
+        Direction::Pos => "+",
+        Direction::Neg => "-",
+    }
+}
+
+fn serialize_direction<S: Serializer>(
+    direction: &Option<Direction>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match direction {
+        Some(direction) => serializer.serialize_str(format_direction(direction)),
+        None => serializer.serialize_none(),
+    }
+}
+
+impl Event {
+    pub fn new(time_passed: u32, event: EventKind) -> Self {
+        Event {
+            time_passed: time_passed,
+            event: event,
+            pod_id: None,
+            person_id: None,
+            station_id: None,
+            to_station_id: None,
+            line_name: None,
+            direction: None,
+        }
+    }
+
+    // In the order of EVENT_HEADER, ids that are not set stay empty
+    pub fn to_record(&self) -> Vec<String> {
+        let format_id = |id: Option<i32>| match id {
+            Some(id) => id.to_string(),
+            None => String::new(),
+        };
+        vec![
+            self.time_passed.to_string(),
+            self.event.get_name(),
+            format_id(self.pod_id),
+            format_id(self.person_id),
+            format_id(self.station_id),
+            format_id(self.to_station_id),
+            match &self.line_name {
+                Some(line_name) => line_name.to_config_string(),
+                None => String::new(),
+            },
+            match &self.direction {
+                Some(direction) => format_direction(direction).to_string(),
+                None => String::new(),
+            },
+        ]
+    }
+}
+
+#[derive(Debug)]
+enum EventWriter {
+    JsonLines(BufWriter<File>),
+    Csv(Box<Writer<File>>),
+}
+
+// Writes the events of a run to <output_dir>/events.jsonl or events.csv as they happen
+#[derive(Debug)]
+pub struct EventLog {
+    writer: EventWriter,
+    pub path: String,
+}
+
+impl EventLog {
+    pub fn create(output_dir: &str, csv: bool) -> Result<EventLog, String> {
+        let _res = create_dir_all(output_dir);
+        let path = match csv {
+            true => format!("{}/{}.csv", output_dir, "events"),
+            false => format!("{}/{}.jsonl", output_dir, "events"),
+        };
+        let file = File::create(&path).map_err(|e| format!("can't create {}: {}", path, e))?;
+        let writer = match csv {
+            true => {
+                let mut writer = Writer::from_writer(file);
+                writer
+                    .write_record(EVENT_HEADER)
+                    .map_err(|e| format!("can't write {}: {}", path, e))?;
+                EventWriter::Csv(Box::new(writer))
+            }
+            false => EventWriter::JsonLines(BufWriter::new(file)),
+        };
+        Ok(EventLog {
+            writer: writer,
+            path: path,
+        })
+    }
+
+    pub fn write(&mut self, event: &Event) -> Result<(), String> {
+        let res = match &mut self.writer {
+            EventWriter::JsonLines(writer) => serde_json::to_writer(&mut *writer, event)
+                .map_err(|e| e.to_string())
+                .and_then(|_| writer.write_all(b"\n").map_err(|e| e.to_string())),
+            EventWriter::Csv(writer) => writer
+                .write_record(event.to_record())
+                .map_err(|e| e.to_string()),
+        };
+        res.map_err(|e| format!("can't write {}: {}", self.path, e))
+    }
+
+    pub fn flush(&mut self) -> Result<(), String> {
+        let res = match &mut self.writer {
+            EventWriter::JsonLines(writer) => writer.flush(),
+            EventWriter::Csv(writer) => writer.flush(),
+        };
+        res.map_err(|e| format!("can't write {}: {}", self.path, e))
+    }
+}
@@ -0,0 +1,157 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub const DISTRIBUTION_HEADER: &str = "metric,count,mean,min,p50,p90,p95,p99,max\n";
+
+// Counts values in bins of a fixed width, so percentiles can be told without keeping
+// the values. Only bins that got a value are stored, a percentile is the lower edge of
+// its bin, so it is up to one bin width too low.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Histogram {
+    bin_width: f32,
+    bins: BTreeMap<i64, u64>,
+    count: u64,
+    sum: f64,
+    min: f32,
+    max: f32,
+}
+
+impl Histogram {
+    pub fn new(bin_width: f32) -> Histogram {
+        Histogram {
+            bin_width: bin_width,
+            bins: BTreeMap::new(),
+            count: 0,
+            sum: 0.,
+            min: f32::MAX,
+            max: f32::MIN,
+        }
+    }
+
+    pub fn add(&mut self, value: f32) {
+        let bin = (value / self.bin_width).floor() as i64;
+        *self.bins.entry(bin).or_insert(0) += 1;
+        self.count += 1;
+        self.sum += value as f64;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    pub fn mean(&self) -> f32 {
+        if self.count == 0 {
+            return 0.;
+        }
+        (self.sum / self.count as f64) as f32
+    }
+
+    // p between 0 and 100
+    pub fn percentile(&self, p: f32) -> f32 {
+        if self.count == 0 {
+            return 0.;
+        }
+        let rank = ((p / 100. * self.count as f32).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bin, count) in &self.bins {
+            seen += count;
+            if seen >= rank {
+                let lower_edge = *bin as f32 * self.bin_width;
+                return lower_edge.max(self.min).min(self.max);
+            }
+        }
+        self.max
+    }
+
+    // A row under DISTRIBUTION_HEADER
+    pub fn format_to_string(&self, name: &str) -> String {
+        if self.count == 0 {
+            return format!("{},0,,,,,,,", name);
+        }
+        format!(
+            "{},{},{},{},{},{},{},{},{}",
+            name,
+            self.count,
+            self.mean(),
+            self.min,
+            self.percentile(50.),
+            self.percentile(90.),
+            self.percentile(95.),
+            self.percentile(99.),
+            self.max
+        )
+    }
+}
+
+// The rows of a dist.txt, one per metric
+pub fn format_distributions(histograms: &[(&str, Histogram)]) -> String {
+    let mut txt = String::from(DISTRIBUTION_HEADER);
+    for (name, histogram) in histograms {
+        txt.push_str(&format!("{}\n", histogram.format_to_string(name)));
+    }
+    txt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_histogram() {
+        let histogram = Histogram::new(1.);
+        assert_eq!(histogram.mean(), 0.);
+        assert_eq!(histogram.percentile(50.), 0.);
+        assert_eq!(histogram.format_to_string("empty"), "empty,0,,,,,,,");
+    }
+
+    #[test]
+    fn single_sample() {
+        let mut histogram = Histogram::new(10.);
+        histogram.add(23.);
+        assert_eq!(histogram.mean(), 23.);
+        // The lower edge of the bin would be 20, but nothing is below the smallest value
+        for p in [0., 50., 99., 100.] {
+            assert_eq!(histogram.percentile(p), 23.);
+        }
+        assert_eq!(
+            histogram.format_to_string("one"),
+            "one,1,23,23,23,23,23,23,23"
+        );
+    }
+
+    #[test]
+    fn percentiles_are_lower_bin_edges() {
+        let mut histogram = Histogram::new(1.);
+        for value in 1..=100 {
+            histogram.add(value as f32 + 0.5);
+        }
+        assert_eq!(histogram.percentile(50.), 50.);
+        assert_eq!(histogram.percentile(90.), 90.);
+        assert_eq!(histogram.percentile(99.), 99.);
+        // Clamped to the smallest value in the first bin
+        assert_eq!(histogram.percentile(0.), 1.5);
+        assert_eq!(histogram.percentile(100.), 100.);
+    }
+
+    #[test]
+    fn negative_values() {
+        let mut histogram = Histogram::new(10.);
+        histogram.add(-5.);
+        histogram.add(-3.);
+        // Both are in the bin from -10 to 0
+        assert_eq!(histogram.percentile(50.), -5.);
+        assert_eq!(histogram.percentile(100.), -5.);
+        histogram.add(-12.);
+        assert_eq!(histogram.percentile(0.), -12.);
+        assert_eq!(histogram.percentile(100.), -10.);
+    }
+
+    #[test]
+    fn distributions_have_a_row_per_metric() {
+        let mut histogram = Histogram::new(1.);
+        histogram.add(2.);
+        let txt = format_distributions(&[("a", histogram), ("b", Histogram::new(1.))]);
+        assert_eq!(
+            txt,
+            format!("{}a,1,2,2,2,2,2,2,2\nb,0,,,,,,,\n", DISTRIBUTION_HEADER)
+        );
+    }
+}
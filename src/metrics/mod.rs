@@ -1,4 +1,7 @@
 pub mod components;
+pub mod events;
+pub mod histogram;
+pub mod summary;
 pub mod timeseries;
 pub mod timestamp;
 pub mod traits;
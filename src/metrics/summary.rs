@@ -0,0 +1,78 @@
+use crate::metrics::components::person::PersonMetrics;
+use crate::metrics::components::pod::PodMetrics;
+use crate::metrics::timeseries::TimeSeries;
+
+pub const SUMMARY_HEADER: &[&str] = &[
+    "m_avg_per_s_ppl",
+    "m_avg_per_s_pods",
+    "mrs_people_pods",
+    "m_abs_avg_people",
+    "m_abs_avg_pods",
+    "wr_avg",
+    "dr_avg",
+    "ur_avg",
+];
+
+// The key figures of a run, computed from the avg time series of people and pods the
+// same way calc_metrics in analysis/evaluate_metrics.ipynb does it.
+#[derive(Clone, Debug, Default)]
+pub struct RunSummary {
+    pub m_avg_per_s_ppl: f32,
+    pub m_avg_per_s_pods: f32,
+    pub mrs_people_pods: f32,
+    pub m_abs_avg_people: f32,
+    pub m_abs_avg_pods: f32,
+    pub wr_avg: f32,
+    pub dr_avg: f32,
+    pub ur_avg: f32,
+}
+
+impl RunSummary {
+    // None if metrics were never gathered
+    pub fn from(
+        people_avg: &TimeSeries<PersonMetrics>,
+        pods_avg: &TimeSeries<PodMetrics>,
+    ) -> Option<RunSummary> {
+        let len = people_avg.time_series.len().min(pods_avg.time_series.len());
+        if len == 0 {
+            return None;
+        }
+        let last_people = &people_avg.time_series[len - 1].metrics;
+        let last_pods = &pods_avg.time_series[len - 1].metrics;
+        let mut utilization_sum = 0.;
+        for ts in &pods_avg.time_series[..len] {
+            utilization_sum += ts.metrics.utilization;
+        }
+
+        let m_avg_per_s_ppl = last_people.meters_traveled / len as f32;
+        let m_avg_per_s_pods = last_pods.meters_traveled / len as f32;
+        let wr_avg = last_people.time_in_station / len as f32;
+        Some(RunSummary {
+            m_avg_per_s_ppl: m_avg_per_s_ppl,
+            m_avg_per_s_pods: m_avg_per_s_pods,
+            mrs_people_pods: m_avg_per_s_ppl / m_avg_per_s_pods,
+            m_abs_avg_people: last_people.meters_traveled,
+            m_abs_avg_pods: last_pods.meters_traveled,
+            wr_avg: wr_avg,
+            dr_avg: 1. - wr_avg,
+            ur_avg: utilization_sum / len as f32,
+        })
+    }
+
+    // In the order of SUMMARY_HEADER
+    pub fn to_record(&self) -> Vec<String> {
+        [
+            self.m_avg_per_s_ppl,
+            self.m_avg_per_s_pods,
+            self.mrs_people_pods,
+            self.m_abs_avg_people,
+            self.m_abs_avg_pods,
+            self.wr_avg,
+            self.dr_avg,
+            self.ur_avg,
+        ]
+        .iter()
+        .map(|value| value.to_string())
+        .collect()
+    }
+}
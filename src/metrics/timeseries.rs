@@ -1,8 +1,9 @@
 use crate::metrics::timestamp::Timestamp;
 use crate::metrics::traits::Metrics;
 use crate::metrics::traits::Series;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct TimeSeries<T: Metrics> {
     pub time_series: Vec<Timestamp<T>>,
 }
@@ -17,7 +18,7 @@ impl<T: Metrics + std::fmt::Debug> TimeSeries<T> {
     pub fn _from(time_passed: u32) -> TimeSeries<T> {
         let mut initial_vec = vec![];
         for i in 1..time_passed + 1 {
-            initial_vec.push(Timestamp::_dummy(i));
+            initial_vec.push(Timestamp::dummy(i));
         }
         // println!("time_passed: {} -> {:?}", time_passed, initial_vec);
         TimeSeries {
@@ -28,6 +29,14 @@ impl<T: Metrics + std::fmt::Debug> TimeSeries<T> {
     pub fn add_timestamp(&mut self, ts: u32, metrics: T) {
         self.time_series.push(Timestamp::new(ts, metrics))
     }
+
+    // Adds up the metrics of everything that is gathered in the same second
+    pub fn add_to_timestamp(&mut self, ts: u32, metrics: &T) {
+        match self.time_series.last_mut() {
+            Some(last) if last.ts == ts => last.metrics.add_metrics(metrics),
+            _ => self.add_timestamp(ts, metrics.clone()),
+        }
+    }
 }
 
 impl<T: Metrics> Series for TimeSeries<T> {
@@ -39,25 +48,6 @@ impl<T: Metrics> Series for TimeSeries<T> {
         txt
     }
 
-    fn add_layer(&mut self, other: &Self) {
-        if self.time_series.is_empty() {
-            self.time_series = other.time_series.clone();
-        } else {
-            for i in 0..self.time_series.len() {
-                let self_elem = &mut self.time_series[i];
-                let other_elem = &other.time_series[i];
-                if self_elem.ts != other_elem.ts {
-                    println!(
-                        "self_elem.ts: {} | other_elem.ts: {}",
-                        self_elem.ts, other_elem.ts
-                    );
-                    panic!("Elements in same spot in vector have different ts - this should never be the case.")
-                }
-                self_elem.add_metrics(other_elem);
-            }
-        }
-    }
-
     fn normalize_by(&mut self, n: u32) {
         for ts in &mut self.time_series {
             ts.normalize_by(n)
@@ -1,6 +1,7 @@
 use crate::metrics::traits::Metrics;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct Timestamp<T: Metrics> {
     pub ts: u32,
     pub metrics: T,
@@ -14,7 +15,7 @@ impl<T: Metrics> Timestamp<T> {
         }
     }
 
-    pub fn _dummy(ts: u32) -> Timestamp<T> {
+    pub fn dummy(ts: u32) -> Timestamp<T> {
         Timestamp {
             ts: ts,
             metrics: T::default(),
@@ -5,7 +5,6 @@ pub trait Metrics: Clone + Default {
 }
 
 pub trait Series {
-    fn add_layer(&mut self, other: &Self);
     fn normalize_by(&mut self, n: u32);
     fn format_to_file(&self, header: String) -> String;
 }
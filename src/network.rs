@@ -1,40 +1,77 @@
 use crate::config::structs::Config;
 use crate::helper::enums::Direction;
 use crate::helper::enums::LineName;
-use crate::helper::functions::{calc_graph, get_screen_coordinates};
+use crate::helper::functions::get_screen_coordinates;
 use crate::line::line::Line;
+use crate::metrics::components::platform::PLATFORM_HEADER;
+use crate::metrics::components::station::StationMetrics;
+use crate::metrics::timeseries::TimeSeries;
+use crate::metrics::traits::Series;
 use crate::pod::podsbox::PodsBox;
+use crate::routing::RoutingGraph;
 use crate::station::platform::Platform;
 use crate::station::station::Station;
 use ggez::Context;
 use petgraph::dot::{Config as PetConfig, Dot};
-use petgraph::graph::UnGraph;
+use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
+use std::fs::*;
+use std::io::prelude::*;
+use std::path::Path;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Network {
     pub stations: Vec<Station>,
-    pub graph: UnGraph<u32, u32>,
+    pub routing_graph: RoutingGraph,
     pub lines: Vec<Line>,
+    gather_metrics: bool,
+    // The metrics of all stations added up for every second since gathering started
+    sums: TimeSeries<StationMetrics>,
 }
 
 impl Network {
     pub fn new(stations: Vec<Station>, config: &Config) -> Self {
         let lines = config.network.lines.clone();
-        let network = Network {
+        let mut network = Network {
             stations: stations,
-            graph: calc_graph(&lines),
+            routing_graph: RoutingGraph::default(),
             lines: lines,
+            gather_metrics: false,
+            sums: TimeSeries::new(),
         };
+        network.recalculate_graph(config);
         network
     }
 
+    // Has to be called whenever connections or platform states change
+    pub fn recalculate_graph(&mut self, config: &Config) {
+        self.routing_graph = RoutingGraph::new(&self.lines, &self.stations, config);
+    }
+
     pub fn update(&mut self) {
         for station in &mut self.stations {
             station.update();
         }
     }
 
+    pub fn start_gather_metrics(&mut self) {
+        self.gather_metrics = true;
+        for station in &mut self.stations {
+            station.start_gather_metrics();
+        }
+    }
+
+    pub fn do_gather_metrics(&mut self, config: &Config, time_passed: u32) {
+        if !self.gather_metrics {
+            return;
+        }
+        self.sums
+            .add_to_timestamp(time_passed, &StationMetrics::default());
+        for station in &mut self.stations {
+            station.do_gather_metrics(config, time_passed, &mut self.sums);
+        }
+    }
+
     pub fn apply_show_station(&mut self, id: i32) {
         for station in &mut self.stations {
             if station.id == id {
@@ -197,7 +234,7 @@ impl Network {
         }
         println!(
             "{:?}",
-            Dot::with_config(&self.graph, &[PetConfig::NodeIndexLabel])
+            Dot::with_config(&self.routing_graph.graph, &[PetConfig::NodeIndexLabel])
         );
     }
 
@@ -210,4 +247,110 @@ impl Network {
             let _res = station.draw(ctx, config); // TODO: handle result error case
         }
     }
+
+    pub fn dump_metrics(&self, station_id: i32, config: &Config) {
+        let maybe_station = self.try_get_station_by_id_unmut(station_id);
+        match maybe_station {
+            Some(station) => {
+                if let Some(output_dir) = config.get_output_dir() {
+                    let path_str =
+                        format!("{}/{}/{}/{}.txt", output_dir, "stations", "ids", station_id);
+                    let path = Path::new(&path_str);
+                    let parent = path.parent().unwrap();
+                    let _res = create_dir_all(parent);
+                    let res = File::create(path);
+                    match res {
+                        Ok(mut file) => {
+                            let txt = station.time_series.format_to_file(String::from(
+                                "ts,people_in_station,queue_length,pods_served,boardings,alightings\n",
+                            ));
+                            let res = file.write_all(txt.as_bytes());
+                            match res {
+                                Ok(_) => {
+                                    if !config.general.quiet {
+                                        println!("written file");
+                                    }
+                                }
+                                Err(e) => {
+                                    println!("error writing file: {}", e);
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            println!("error opening file: {}", e);
+                        }
+                    }
+                }
+            }
+            None => {}
+        }
+    }
+
+    pub fn dump_all_metrics(&self, config: &Config) {
+        for station in &self.stations {
+            self.dump_metrics(station.id, config)
+        }
+    }
+
+    pub fn get_avg_time_series(&self) -> TimeSeries<StationMetrics> {
+        if self.stations.is_empty() {
+            return TimeSeries::new();
+        }
+        let mut timeseries_accumulator = self.sums.clone();
+        timeseries_accumulator.normalize_by(self.stations.len() as u32);
+        timeseries_accumulator
+    }
+
+    // The average over all stations and a row per platform with its throughput, dwell
+    // and queue, the platforms of the bottlenecks have a high load or a long queue
+    pub fn dump_avg_metrics(&self, config: &Config) {
+        let timeseries_accumulator = self.get_avg_time_series();
+        if let Some(output_dir) = config.get_output_dir() {
+            let path_str = format!("{}/{}/{}.txt", output_dir, "stations", "avg");
+            let path = Path::new(&path_str);
+            let parent = path.parent().unwrap();
+            let _res = create_dir_all(parent);
+            let res = File::create(path);
+            match res {
+                Ok(mut file) => {
+                    let txt = timeseries_accumulator.format_to_file(String::from(
+                        "ts,people_in_station,queue_length,pods_served,boardings,alightings\n",
+                    ));
+                    let res = file.write_all(txt.as_bytes());
+                    match res {
+                        Ok(_) => {
+                            if !config.general.quiet {
+                                println!("written file");
+                            }
+                        }
+                        Err(e) => {
+                            println!("error writing file: {}", e);
+                        }
+                    }
+                }
+                Err(e) => {
+                    println!("error opening file: {}", e);
+                }
+            }
+
+            let path_str = format!("{}/{}/{}.txt", output_dir, "stations", "platforms");
+            let mut txt = String::from(PLATFORM_HEADER);
+            for station in &self.stations {
+                for platform in &station.platforms {
+                    txt.push_str(&platform.format_metrics(config));
+                    txt.push('\n');
+                }
+            }
+            match write(&path_str, txt) {
+                Ok(_) => {
+                    if !config.general.quiet {
+                        println!("written file");
+                    }
+                }
+                Err(e) => {
+                    println!("error writing file: {}", e);
+                }
+            }
+        }
+    }
 }
@@ -1,40 +1,36 @@
 use crate::config::structs::Config;
-use crate::helper::functions::get_air_travel_time;
+use crate::helper::enums::LineName;
 use crate::network::Network;
-use petgraph::algo::astar;
-use petgraph::graph::{NodeIndex, UnGraph};
+use petgraph::graph::NodeIndex;
+use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
 
-#[derive(Clone, Debug, Default)]
+// path holds the stations still ahead including the current one, legs holds the
+// line that is taken from each of them to the next, so it is one shorter.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct PathState {
     pub path: VecDeque<NodeIndex<u32>>,
+    pub legs: VecDeque<LineName>,
     pub current: NodeIndex<u32>,
 }
 
 impl PathState {
-    pub fn new(
-        graph: &UnGraph<u32, u32>,
-        start: u32,
-        end: u32,
-        network: &Network,
-        config: &Config,
-    ) -> Self {
-        // println!("{:?}", graph);
-        // println!("start: {}, end: {}", start, end);
-        // println!("air_travel_time: {}", get_air_travel_time(start, end, network, config));
-        let maybe_path = astar(
-            graph,
-            NodeIndex::new(start as usize),
-            |finish| finish == NodeIndex::new(end as usize),
-            |e| *e.weight(),
-            |_| get_air_travel_time(start, end, network, config), // use air distance as heuristik
-        );
+    pub fn new(start: u32, end: u32, network: &Network, config: &Config) -> Self {
+        let maybe_route =
+            network
+                .routing_graph
+                .try_find_route(start as i32, end as i32, network, config);
 
-        match maybe_path {
-            Some((_, path)) => {
+        match maybe_route {
+            Some(route) => {
                 // println!("There is a connection between {} and {}", start, end);
                 let path_state = PathState {
-                    path: VecDeque::from(path),
+                    path: route
+                        .stations
+                        .iter()
+                        .map(|station_id| NodeIndex::new(*station_id as usize))
+                        .collect(),
+                    legs: VecDeque::from(route.legs),
                     current: NodeIndex::new(start as usize),
                 };
                 return path_state;
@@ -44,6 +40,7 @@ impl PathState {
                 // println!("No connection between {} and {}", start, end);
                 let path_state = PathState {
                     path: VecDeque::from([NodeIndex::new(start as usize)]),
+                    legs: VecDeque::new(),
                     current: NodeIndex::new(start as usize),
                 };
                 return path_state;
@@ -71,7 +68,24 @@ impl PathState {
         }
     }
 
+    pub fn try_get_next_line_name(&self) -> Option<&LineName> {
+        self.legs.front()
+    }
+
+    // Whether a pod of this line that drives to this station next takes the person
+    // further along the path. Parallel lines share stations, so both have to match.
+    pub fn continues_with(&self, next_station_id: i32, line_name: &LineName) -> bool {
+        if self.try_get_next_station_id() != Some(next_station_id as u32) {
+            return false;
+        }
+        match self.try_get_next_line_name() {
+            Some(next_line_name) => next_line_name == line_name,
+            None => true,
+        }
+    }
+
     pub fn arrive(&mut self) {
         self.path.pop_front();
+        self.legs.pop_front();
     }
 }
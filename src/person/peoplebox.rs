@@ -1,13 +1,22 @@
 use crate::config::structs::Config;
 use crate::control::action::Action;
+use crate::helper::functions::{get_random_trip, get_time_of_day};
+use crate::metrics::events::Event;
+use crate::metrics::histogram::{format_distributions, Histogram};
 use crate::network::Network;
 use crate::person::person::Person;
+use crate::person::personstate::PersonState;
 use crate::pod::podsbox::PodsBox;
+use csv::Writer;
 use ggez::Context;
-use rand::rngs::mock::StepRng;
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
 // use shuffle::irs::Irs; // Turned out to slow down execution too much
+use crate::metrics::components::person::PersonMetrics;
+use crate::metrics::components::trip::{TripMetrics, TRIP_HEADER};
 use crate::metrics::timeseries::TimeSeries;
-use crate::metrics::traits::Series;
+use crate::metrics::traits::{Metrics, Series};
+use serde::{Deserialize, Serialize};
 use shuffle::fy::FisherYates;
 use shuffle::shuffler::Shuffler;
 use std::fs::File;
@@ -18,14 +27,86 @@ use std::path::Path;
 // use crate::person::person::Person;
 
 // TODO: implement destinations
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PeopleBox {
     pub people: Vec<Person>,
+    next_person_id: i32,
+    scheduled_until: u32,
+    gather_metrics_since: Option<u32>,
+    // The metrics of all people added up for every second since gathering started, the
+    // averages don't need the time series of each person
+    sums: TimeSeries<PersonMetrics>,
+    // How many people were in the network in each second of sums
+    counts: Vec<u32>,
+    // People that left the network, they are only kept for their time series and trips
+    // if metrics are gathered
+    exited: Vec<Person>,
+    #[serde(skip)]
+    log_events: bool,
+    // The events of people that exited since the last time the event log took them
+    #[serde(skip)]
+    events: Vec<Event>,
 }
 
 impl PeopleBox {
+    pub fn new(people: Vec<Person>) -> Self {
+        PeopleBox {
+            next_person_id: people.len() as i32,
+            people: people,
+            scheduled_until: 0,
+            gather_metrics_since: None,
+            sums: TimeSeries::new(),
+            counts: vec![],
+            exited: vec![],
+            log_events: false,
+            events: vec![],
+        }
+    }
+
+    // Trips are generated one hour of the day at a time, the number of trips is taken
+    // from hourly_trips and the origin destination pair from the demand that is
+    // active at the time the trip starts.
+    fn schedule_trips(
+        &mut self,
+        hourly_trips: &[u32],
+        config: &Config,
+        time_passed: u32,
+        rng: &mut ChaCha8Rng,
+    ) {
+        while self.scheduled_until <= time_passed {
+            let hour_start = self.scheduled_until;
+            let time_of_day = get_time_of_day(config, hour_start);
+            let hour_end = hour_start + 3600 - time_of_day % 3600;
+            let trips_in_hour = hourly_trips[(time_of_day / 3600) as usize];
+            let n_trips = (trips_in_hour * (hour_end - hour_start) + 1800) / 3600;
+
+            let mut spawn_times: Vec<u32> = (0..n_trips)
+                .map(|_| rng.gen_range(hour_start..hour_end))
+                .collect();
+            spawn_times.sort();
+
+            for spawn_at in spawn_times {
+                let (start, finish) = get_random_trip(config, spawn_at, rng);
+                let mut person = Person::new_scheduled(
+                    self.next_person_id,
+                    config.logic.transition_time,
+                    start as i32,
+                    finish as i32,
+                    spawn_at,
+                );
+                if let Some(since) = self.gather_metrics_since {
+                    person.start_gather_metrics_since(since, time_passed, config);
+                }
+                person.log_events = self.log_events;
+                self.people.push(person);
+                self.next_person_id += 1;
+            }
+            self.scheduled_until = hour_end;
+        }
+    }
+
     pub fn try_get_person_by_id_unmut(&self, id: i32) -> Option<&Person> {
-        for person in &self.people {
+        for person in self.people.iter().chain(self.exited.iter()) {
             if person.id == id {
                 return Some(person);
             }
@@ -39,30 +120,93 @@ impl PeopleBox {
         network: &mut Network,
         config: &Config,
         time_passed: u32,
+        rng: &mut ChaCha8Rng,
     ) {
+        if let Some(demand) = &config.demand {
+            if let Some(hourly_trips) = &demand.hourly_trips {
+                self.schedule_trips(hourly_trips, config, time_passed, rng);
+            }
+        }
+
         for person in &mut self.people {
-            person.get_out_if_needed(pods_box, network, config);
+            person.get_out_if_needed(pods_box, network, config, time_passed, rng);
         }
 
         // Only FisherYates is acceptable in terms of speed
         // Inverse Riffle Shuffeling would be more random but so much slower
         if config.logic.shuffle_people {
-            let mut rng = StepRng::new(2, 10);
             let mut fy = FisherYates::default();
-            let _res = fy.shuffle(&mut self.people, &mut rng);
+            let _res = fy.shuffle(&mut self.people, rng);
         }
 
+        if self.gather_metrics_since.is_some() {
+            // Every second gets a timestamp, even one without people
+            self.sums
+                .add_to_timestamp(time_passed, &PersonMetrics::default());
+            let n_people = self
+                .people
+                .iter()
+                .filter(|person| person.gather_metrics && person.is_in_network())
+                .count();
+            self.counts.push(n_people as u32);
+        }
         for person in &mut self.people {
-            person.update(pods_box, network, config, time_passed);
+            person.update(pods_box, network, config, time_passed, rng, &mut self.sums);
         }
+        self.remove_exited();
     }
 
-    pub fn start_gather_metrics(&mut self) {
+    // Keeps the people that left the network out of the loops every second
+    fn remove_exited(&mut self) {
+        if !self
+            .people
+            .iter()
+            .any(|person| matches!(person.state, PersonState::Exited { .. }))
+        {
+            return;
+        }
+        let (exited, people): (Vec<Person>, Vec<Person>) = self
+            .people
+            .drain(..)
+            .partition(|person| matches!(person.state, PersonState::Exited { .. }));
+        self.people = people;
+        for mut person in exited {
+            self.events.append(&mut person.events);
+            if person.gather_metrics {
+                self.exited.push(person);
+            }
+        }
+    }
+
+    pub fn start_gather_metrics(&mut self, time_passed: u32) {
+        // The first timestamp is written in the update after this one
+        self.gather_metrics_since = Some(time_passed + 1);
         for person in &mut self.people {
             person.start_gather_metrics();
         }
     }
 
+    pub fn set_log_events(&mut self, log_events: bool) {
+        self.log_events = log_events;
+        for person in &mut self.people {
+            person.log_events = log_events;
+            person.events.clear();
+        }
+        self.events.clear();
+    }
+
+    // The events of all people since the last call, in the order of the people
+    pub fn take_events(&mut self) -> Vec<Event> {
+        let mut events = vec![];
+        if self.log_events {
+            events.append(&mut self.events);
+            for person in &mut self.people {
+                events.append(&mut person.events);
+            }
+        }
+        events
+    }
+
     // TODO: differentiate between follow and not
     pub fn apply_show_person(&mut self, id: i32) {
         for person in &mut self.people {
@@ -88,6 +232,42 @@ impl PeopleBox {
         }
     }
 
+    // Called after the routing graph changed. Only people whose remaining path can no
+    // longer be traveled plan again, they do so on their next update. Returns how many.
+    pub fn apply_graph_change(&mut self, network: &Network) -> i32 {
+        let mut n_affected = 0;
+        for person in &mut self.people {
+            let in_pod = match person.state {
+                PersonState::NotYetSpawned {
+                    station_id: _,
+                    destination_id: _,
+                    spawn_at: _,
+                }
+                | PersonState::Exited { station_id: _ } => continue,
+                PersonState::RidingPod {
+                    pod_id: _,
+                    just_got_in: _,
+                }
+                | PersonState::JustArrived {
+                    pod_id: _,
+                    station_id: _,
+                } => true,
+                _ => false,
+            };
+            if person.path_state.finished_journey() {
+                continue;
+            }
+            if !network
+                .routing_graph
+                .is_path_available(&person.path_state, in_pod)
+            {
+                person.needs_new_path = true;
+                n_affected += 1;
+            }
+        }
+        n_affected
+    }
+
     pub fn draw(&self, ctx: &mut Context) {
         for person in &self.people {
             if person.visualize {
@@ -100,18 +280,9 @@ impl PeopleBox {
         let maybe_person = self.try_get_person_by_id_unmut(person_id);
         match maybe_person {
             Some(person) => {
-                if let Some(timestamp_run) = config.timestamp_run {
-                    let timestamp = timestamp_run
-                        .naive_utc()
-                        .format("%Y.%m.%d %H:%M")
-                        .to_string()
-                        .replace(" ", "_");
-                    println!("timestamp_run: {:?}", timestamp);
-
-                    let path_str = format!(
-                        "{}/{}/{}/{}/{}/{}.txt",
-                        "metrics", config.environment, timestamp, "people", "ids", person_id
-                    );
+                if let Some(output_dir) = config.get_output_dir() {
+                    let path_str =
+                        format!("{}/{}/{}/{}.txt", output_dir, "people", "ids", person_id);
                     let path = Path::new(&path_str);
                     let parent = path.parent().unwrap();
                     let _res = create_dir_all(parent);
@@ -124,7 +295,9 @@ impl PeopleBox {
                             let res = file.write_all(txt.as_bytes());
                             match res {
                                 Ok(_) => {
-                                    println!("written file");
+                                    if !config.general.quiet {
+                                        println!("written file");
+                                    }
                                 }
                                 Err(e) => {
                                     println!("error writing file: {}", e);
@@ -142,31 +315,58 @@ impl PeopleBox {
     }
 
     pub fn dump_all_metrics(&self, config: &Config) {
-        for person in &self.people {
+        for person in self.people.iter().chain(self.exited.iter()) {
             self.dump_metrics(person.id, config)
         }
     }
 
-    pub fn dump_avg_metrics(&self, config: &Config) {
-        let mut timeseries_accumulator = TimeSeries::new();
-        for person in &self.people {
-            timeseries_accumulator.add_layer(&person.time_series);
+    // Every second is averaged over the people that were in the network in it
+    pub fn get_avg_time_series(&self) -> TimeSeries<PersonMetrics> {
+        let mut timeseries_accumulator = self.sums.clone();
+        for (timestamp, n_people) in timeseries_accumulator
+            .time_series
+            .iter_mut()
+            .zip(&self.counts)
+        {
+            if *n_people > 0 {
+                timestamp.normalize_by(*n_people);
+            }
         }
+        timeseries_accumulator
+    }
 
-        timeseries_accumulator.normalize_by(config.logic.number_of_people as u32);
-
-        if let Some(timestamp_run) = config.timestamp_run {
-            let timestamp = timestamp_run
-                .naive_utc()
-                .format("%Y.%m.%d %H:%M")
-                .to_string()
-                .replace(" ", "_");
-            println!("timestamp_run: {:?}", timestamp);
+    // Over the people that entered the network so far
+    pub fn get_distributions(&self) -> Vec<(&'static str, Histogram)> {
+        let mut number_of_pods = Histogram::new(1.);
+        let mut time_in_station = Histogram::new(1.);
+        let mut time_in_pods = Histogram::new(1.);
+        let mut meters_traveled = Histogram::new(10.);
+        for person in self.people.iter().chain(self.exited.iter()) {
+            if let PersonState::NotYetSpawned {
+                station_id: _,
+                destination_id: _,
+                spawn_at: _,
+            } = person.state
+            {
+                continue;
+            }
+            number_of_pods.add(person.metrics.number_of_pods);
+            time_in_station.add(person.metrics.time_in_station);
+            time_in_pods.add(person.metrics.time_in_pods);
+            meters_traveled.add(person.metrics.meters_traveled);
+        }
+        vec![
+            ("number_of_pods", number_of_pods),
+            ("time_in_station", time_in_station),
+            ("time_in_pods", time_in_pods),
+            ("meters_traveled", meters_traveled),
+        ]
+    }
 
-            let path_str = format!(
-                "{}/{}/{}/{}/{}.txt",
-                "metrics", config.environment, timestamp, "people", "avg"
-            );
+    pub fn dump_avg_metrics(&self, config: &Config) {
+        let timeseries_accumulator = self.get_avg_time_series();
+        if let Some(output_dir) = config.get_output_dir() {
+            let path_str = format!("{}/{}/{}.txt", output_dir, "people", "avg");
             let path = Path::new(&path_str);
             let parent = path.parent().unwrap();
             let _res = create_dir_all(parent);
@@ -179,7 +379,9 @@ impl PeopleBox {
                     let res = file.write_all(txt.as_bytes());
                     match res {
                         Ok(_) => {
-                            println!("written file");
+                            if !config.general.quiet {
+                                println!("written file");
+                            }
                         }
                         Err(e) => {
                             println!("error writing file: {}", e);
@@ -190,6 +392,66 @@ impl PeopleBox {
                     println!("error opening file: {}", e);
                 }
             }
+
+            let path_str = format!("{}/{}/{}.txt", output_dir, "people", "dist");
+            let txt = format_distributions(&self.get_distributions());
+            match write(&path_str, txt) {
+                Ok(_) => {
+                    if !config.general.quiet {
+                        println!("written file");
+                    }
+                }
+                Err(e) => {
+                    println!("error writing file: {}", e);
+                }
+            }
+        }
+    }
+
+    // Every trip that was completed while metrics were gathered, in the order they ended
+    // The recorded trips of everyone, in the order they ended
+    pub fn get_trips(&self) -> Vec<&TripMetrics> {
+        let mut trips: Vec<&TripMetrics> = self
+            .people
+            .iter()
+            .chain(self.exited.iter())
+            .flat_map(|person| person.trips.iter())
+            .collect();
+        trips.sort_by_key(|trip| (trip.end, trip.person_id));
+        trips
+    }
+
+    pub fn dump_trips(&self, config: &Config) {
+        let trips = self.get_trips();
+
+        if let Some(output_dir) = config.get_output_dir() {
+            let path_str = format!("{}/{}.csv", output_dir, "trips");
+            let _res = create_dir_all(&output_dir);
+            let mut writer = match Writer::from_path(&path_str) {
+                Ok(writer) => writer,
+                Err(e) => {
+                    println!("error opening file: {}", e);
+                    return;
+                }
+            };
+            if let Err(e) = writer.write_record(TRIP_HEADER) {
+                println!("error writing file: {}", e);
+                return;
+            }
+            for trip in trips {
+                if let Err(e) = writer.write_record(trip.to_record()) {
+                    println!("error writing file: {}", e);
+                    return;
+                }
+            }
+            match writer.flush() {
+                Ok(_) => {
+                    if !config.general.quiet {
+                        println!("written file");
+                    }
+                }
+                Err(e) => println!("error writing file: {}", e),
+            }
         }
     }
 }
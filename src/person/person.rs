@@ -1,17 +1,26 @@
 use crate::config::structs::Config;
 use crate::control::action::Action;
-use crate::helper::functions::{get_random_station_id, get_screen_coordinates};
+use crate::helper::functions::{
+    get_air_travel_time, get_random_destination, get_random_station_id, get_screen_coordinates,
+    is_metrics_sample,
+};
 use crate::metrics::components::person::PersonMetrics;
+use crate::metrics::components::trip::TripMetrics;
+use crate::metrics::events::{Event, EventKind};
 use crate::metrics::timeseries::TimeSeries;
+use crate::metrics::timestamp::Timestamp;
 use crate::network::Network;
 use crate::pathstate::PathState;
 use crate::person::personstate::PersonState;
 use crate::pod::pod::Pod;
 use crate::pod::podsbox::PodsBox;
+use crate::pod::podstate::PodState;
 use ggez::{graphics, Context, GameResult};
-use petgraph::graph::UnGraph;
+use petgraph::graph::NodeIndex;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct Person {
     pub id: i32,
     pub visualize: bool,
@@ -24,6 +33,16 @@ pub struct Person {
     pub stay_at_station_id: Option<u32>,
     pub path_state: PathState,
     pub action_to_process: Option<Action>,
+    pub exit_at_destination: bool,
+    pub needs_new_path: bool,
+    // The trip the person is on and the ones completed while metrics were gathered
+    pub trip: Option<TripMetrics>,
+    pub trips: Vec<TripMetrics>,
+    // The transitions since the last time the event log took them
+    #[serde(skip)]
+    pub log_events: bool,
+    #[serde(skip)]
+    pub events: Vec<Event>,
 }
 
 impl Person {
@@ -34,6 +53,7 @@ impl Person {
         start: i32,
         finish: i32,
         config: &Config,
+        time_passed: u32,
     ) -> Self {
         let mut person = Person {
             id: id,
@@ -49,15 +69,16 @@ impl Person {
                 time_in_station: transition_time - 1,
             },
             stay_at_station_id: None,
-            path_state: PathState::new(
-                &network.graph,
-                start as u32,
-                finish as u32,
-                network,
-                config,
-            ),
+            path_state: PathState::new(start as u32, finish as u32, network, config),
             action_to_process: None,
+            exit_at_destination: false,
+            needs_new_path: false,
+            trip: None,
+            trips: vec![],
+            log_events: false,
+            events: vec![],
         };
+        person.start_trip(start, finish, time_passed, network, config);
         person.set_coordinates_of_station(
             person.path_state.try_get_current_station_id().unwrap() as i32,
             network,
@@ -67,16 +88,70 @@ impl Person {
         person
     }
 
+    // A person that waits outside of the network until spawn_at and leaves it again
+    // once the destination is reached. The path is only calculated on spawn, so it
+    // takes the network as it is at that time into account.
+    pub fn new_scheduled(
+        id: i32,
+        transition_time: i32,
+        start: i32,
+        finish: i32,
+        spawn_at: u32,
+    ) -> Self {
+        Person {
+            id: id,
+            visualize: false,
+            gather_metrics: false,
+            transition_time: transition_time,
+            time_series: TimeSeries::new(),
+            metrics: PersonMetrics::new(),
+            real_coordinates: (0., 0.),
+            state: PersonState::NotYetSpawned {
+                station_id: start,
+                destination_id: finish,
+                spawn_at: spawn_at,
+            },
+            stay_at_station_id: None,
+            path_state: PathState::default(),
+            action_to_process: None,
+            exit_at_destination: true,
+            needs_new_path: false,
+            trip: None,
+            trips: vec![],
+            log_events: false,
+            events: vec![],
+        }
+    }
+
+    fn spawn(&mut self, network: &mut Network, config: &Config, time_passed: u32) {
+        if let PersonState::NotYetSpawned {
+            station_id,
+            destination_id,
+            spawn_at: _,
+        } = self.state
+        {
+            self.new_path(station_id as u32, destination_id as u32, network, config);
+            self.start_trip(station_id, destination_id, time_passed, network, config);
+            self.state = self.state.to_spawned();
+            let station = network.try_get_station_by_id(station_id).unwrap();
+            station.register_person(self.id);
+            self.set_coordinates_of_station(station_id, network, config);
+            self.log_event(EventKind::Spawn, time_passed, station_id, None);
+        }
+    }
+
     pub fn update(
         &mut self,
         pods_box: &mut PodsBox,
         network: &mut Network,
         config: &Config,
         time_passed: u32,
+        rng: &mut ChaCha8Rng,
+        sums: &mut TimeSeries<PersonMetrics>,
     ) {
         if self.gather_metrics {
             // println!("gather shit");
-            self.do_gather_metrics(time_passed)
+            self.do_gather_metrics(config, time_passed, sums)
         }
         // println!("person state: {:?}", self.state);
         match &self.state {
@@ -85,7 +160,14 @@ impl Person {
                 // Assign first instead of using directly because:
                 // https://github.com/rust-lang/rust/issues/59159
                 let station_id_deref = *station_id;
-                self.try_to_take_next_pod(pods_box, network, station_id_deref, config);
+                self.try_to_take_next_pod(
+                    pods_box,
+                    network,
+                    station_id_deref,
+                    config,
+                    time_passed,
+                    rng,
+                );
             }
             PersonState::RidingPod {
                 pod_id,
@@ -93,7 +175,7 @@ impl Person {
             } => {
                 // println!("person in riding state");
                 let pod_id_deref = *pod_id;
-                self.ride_pod(pods_box, pod_id_deref);
+                self.ride_pod(pods_box, network, config, pod_id_deref);
             }
             PersonState::JustArrived {
                 pod_id: _,
@@ -112,6 +194,16 @@ impl Person {
                     self.state = self.state.to_ready();
                 }
             }
+            PersonState::NotYetSpawned {
+                station_id: _,
+                destination_id: _,
+                spawn_at,
+            } => {
+                if time_passed >= *spawn_at {
+                    self.spawn(network, config, time_passed);
+                }
+            }
+            PersonState::Exited { station_id: _ } => {}
         }
     }
 
@@ -119,7 +211,23 @@ impl Person {
         self.gather_metrics = true;
     }
 
-    pub fn do_gather_metrics(&mut self, time_passed: u32) {
+    // People that are created while metrics are gathered get empty timestamps for the
+    // time before they existed, so their series line up with everyone else's.
+    pub fn start_gather_metrics_since(&mut self, since: u32, time_passed: u32, config: &Config) {
+        self.gather_metrics = true;
+        for ts in since..time_passed {
+            if is_metrics_sample(config, ts) {
+                self.time_series.time_series.push(Timestamp::dummy(ts));
+            }
+        }
+    }
+
+    pub fn do_gather_metrics(
+        &mut self,
+        config: &Config,
+        time_passed: u32,
+        sums: &mut TimeSeries<PersonMetrics>,
+    ) {
         match &self.state {
             PersonState::ReadyToTakePod { station_id: _ } => {
                 self.metrics.increase_time_in_station();
@@ -145,20 +253,26 @@ impl Person {
             } => {
                 self.metrics.increase_time_in_station();
             }
+            PersonState::NotYetSpawned {
+                station_id: _,
+                destination_id: _,
+                spawn_at: _,
+            }
+            | PersonState::Exited { station_id: _ } => {}
+        }
+        // The sums of the people in the network get the metrics every second, the own
+        // time series only every metrics_interval seconds
+        if self.is_in_network() {
+            sums.add_to_timestamp(time_passed, &self.metrics);
+        }
+        if is_metrics_sample(config, time_passed) {
+            self.time_series
+                .add_timestamp(time_passed, self.metrics.clone());
         }
-        self.time_series
-            .add_timestamp(time_passed, self.metrics.clone());
     }
 
-    pub fn new_path(
-        &mut self,
-        graph: &UnGraph<u32, u32>,
-        start: u32,
-        finish: u32,
-        network: &Network,
-        config: &Config,
-    ) {
-        self.path_state = PathState::new(graph, start, finish, network, config);
+    pub fn new_path(&mut self, start: u32, finish: u32, network: &Network, config: &Config) {
+        self.path_state = PathState::new(start, finish, network, config);
         // println!("{:?}", self.path_state);
     }
 
@@ -198,6 +312,7 @@ impl Person {
         current_station_id: u32,
         network: &mut Network,
         config: &Config,
+        rng: &mut ChaCha8Rng,
     ) {
         match &mut self.action_to_process {
             Some(action) => match action {
@@ -209,14 +324,9 @@ impl Person {
                 } => {
                     if *random_station {
                         self.stay_at_station_id = None;
-                        let random_station_id = get_random_station_id(config);
-                        self.new_path(
-                            &network.graph,
-                            current_station_id,
-                            random_station_id,
-                            network,
-                            config,
-                        )
+                        let random_station_id = get_random_station_id(config, rng);
+                        self.new_path(current_station_id, random_station_id, network, config);
+                        self.reroute_trip(random_station_id, network, config);
                     } else {
                         if *stay_there {
                             self.stay_at_station_id = Some(*station_id);
@@ -224,13 +334,8 @@ impl Person {
                             self.stay_at_station_id = None;
                         }
                         let station_id_finish = *station_id;
-                        self.new_path(
-                            &network.graph,
-                            current_station_id,
-                            station_id_finish,
-                            network,
-                            config,
-                        );
+                        self.new_path(current_station_id, station_id_finish, network, config);
+                        self.reroute_trip(station_id_finish, network, config);
                     }
                     self.action_to_process = None;
                 }
@@ -245,6 +350,8 @@ impl Person {
         pods_box: &mut PodsBox,
         network: &mut Network,
         config: &Config,
+        time_passed: u32,
+        rng: &mut ChaCha8Rng,
     ) {
         match &self.state {
             PersonState::JustArrived {
@@ -252,7 +359,7 @@ impl Person {
                 station_id: _,
             } => {
                 let pod_id_deref = *pod_id;
-                self.decide_on_arrival(pods_box, network, pod_id_deref, config);
+                self.decide_on_arrival(pods_box, network, pod_id_deref, config, time_passed, rng);
                 let maybe_station_id = self.try_get_station_id();
                 match maybe_station_id {
                     Some(station_id) => {
@@ -271,17 +378,26 @@ impl Person {
         network: &mut Network,
         station_id: i32,
         config: &Config,
+        time_passed: u32,
+        rng: &mut ChaCha8Rng,
     ) {
         if let Some(station_id_stay) = self.stay_at_station_id {
             if station_id as u32 == station_id_stay {
-                self.try_process_action(station_id_stay, network, config);
+                self.try_process_action(station_id_stay, network, config, rng);
                 return;
             }
         }
 
+        if self.needs_new_path {
+            let destination = self.path_state.path.back().unwrap().index() as u32;
+            self.new_path(station_id as u32, destination, network, config);
+            self.reroute_trip(destination, network, config);
+            self.needs_new_path = false;
+        }
+
         let maybe_next_station_id = self.path_state.try_get_next_station_id();
         match maybe_next_station_id {
-            Some(next_station_id) => {
+            Some(_) => {
                 let station = network.try_get_station_by_id(station_id).unwrap();
                 let maybe_pod_ids: Option<Vec<i32>> = station.try_get_pod_ids_in_station_as_vec();
                 // println!("maybe_pod_ids: {:?}", maybe_pod_ids);
@@ -300,7 +416,12 @@ impl Person {
                             //     pod.line_state.get_next_station_id(),
                             //     next_station_id
                             // );
-                            if pod.line_state.get_next_station_id() == next_station_id as i32 {
+                            if !pod.is_out_of_service()
+                                && self.path_state.continues_with(
+                                    pod.line_state.get_next_station_id(),
+                                    &pod.line_state.line.name,
+                                )
+                            {
                                 let got_in = pod.try_register_person(self.id);
                                 // println!("got_in: {}", got_in);
                                 if got_in {
@@ -309,6 +430,16 @@ impl Person {
                                     let station =
                                         network.try_get_station_by_id(station_id).unwrap();
                                     station.deregister_person(self.id);
+                                    station.record_boarding();
+                                    if let Some(trip) = &mut self.trip {
+                                        trip.board(time_passed);
+                                    }
+                                    self.log_event(
+                                        EventKind::Board,
+                                        time_passed,
+                                        station_id,
+                                        Some(&*pod),
+                                    );
                                     break;
                                 }
                             }
@@ -318,8 +449,21 @@ impl Person {
                 }
             }
             None => {
-                let finish = get_random_station_id(config);
-                self.new_path(&network.graph, station_id as u32, finish, network, config);
+                // Still on a trip after getting off, it was planned again without a route
+                // or sent here and ends in this station
+                if self.trip.is_some() {
+                    self.end_trip_in_station(station_id, time_passed, network, config);
+                }
+                if self.exit_at_destination {
+                    let station = network.try_get_station_by_id(station_id).unwrap();
+                    station.deregister_person(self.id);
+                    self.state = self.state.to_exited();
+                    self.log_event(EventKind::Exit, time_passed, station_id, None);
+                    return;
+                }
+                let finish = get_random_destination(config, station_id as u32, time_passed, rng);
+                self.new_path(station_id as u32, finish, network, config);
+                self.start_trip(station_id, finish as i32, time_passed, network, config);
                 // println!(
                 //     "person {} is at {} and will go to {} next, taking path {:?}.",
                 //     self.id,
@@ -332,7 +476,13 @@ impl Person {
         }
     }
 
-    fn ride_pod(&mut self, pods_box: &mut PodsBox, pod_id: i32) {
+    fn ride_pod(
+        &mut self,
+        pods_box: &mut PodsBox,
+        network: &Network,
+        config: &Config,
+        pod_id: i32,
+    ) {
         let maybe_pod = pods_box.try_get_pod_by_id_mut(pod_id);
         match maybe_pod {
             Some(pod) => {
@@ -346,37 +496,134 @@ impl Person {
                             .increase_meters_traveled(pod.state.get_distance_travelled() as f32);
                     }
                     self.state = self.state.to_just_arrived(pod.get_station_id());
+                } else if pod.is_passing_station() {
+                    self.ride_through_station(pod, network, config);
+                } else if self.needs_new_path {
+                    self.replan_in_pod(pod, network, config);
                 }
             }
             None => panic!("Pod with id: {} does not exist.", pod_id),
         }
     }
 
+    // The pod drives through the station without stopping. Whoever wanted to stay in
+    // the pod just moves on in the path. Whoever wanted to get off here can't, so they
+    // ride to the next stop and plan again from there. If this station was the
+    // destination the journey ends at the next stop instead.
+    fn ride_through_station(&mut self, pod: &Pod, network: &Network, config: &Config) {
+        let station_id = pod.get_station_id() as u32;
+        // A pod that waits for a blocked connection stays in the passing state for a while
+        if self.path_state.try_get_next_station_id() != Some(station_id) {
+            return;
+        }
+        if self.gather_metrics {
+            self.metrics
+                .increase_meters_traveled(pod.state.get_distance_travelled() as f32);
+        }
+
+        self.path_state.arrive();
+        let next_stop = pod.line_state.get_next_station_id() as u32;
+        if self
+            .path_state
+            .continues_with(next_stop as i32, &pod.line_state.line.name)
+        {
+            return;
+        }
+
+        let destination = self.path_state.path.back().unwrap().index() as u32;
+        let mut path_state = if destination == station_id {
+            PathState::new(next_stop, next_stop, network, config)
+        } else {
+            PathState::new(next_stop, destination, network, config)
+        };
+        path_state
+            .path
+            .push_front(NodeIndex::new(station_id as usize));
+        path_state.legs.push_front(pod.line_state.line.name.clone());
+        self.path_state = path_state;
+    }
+
+    // The path ahead can't be traveled anymore. A pod that stands in a station is
+    // handled like an arrival there, the rest plan again from the stop the pod drives to.
+    fn replan_in_pod(&mut self, pod: &Pod, network: &Network, config: &Config) {
+        let next_stop = match pod.state {
+            PodState::InStation {
+                station_id,
+                time_in_station: _,
+            } => {
+                self.state = self.state.to_just_arrived(station_id);
+                return;
+            }
+            PodState::BetweenStations {
+                station_id_from: _,
+                station_id_to,
+                time_to_next_station: _,
+                distance_between: _,
+            } => station_id_to as u32,
+            PodState::InQueue {
+                station_id,
+                traveled_distance: _,
+            } => station_id as u32,
+            _ => return,
+        };
+
+        let current = self.path_state.try_get_current_station_id().unwrap();
+        let destination = self.path_state.path.back().unwrap().index() as u32;
+        let mut path_state = PathState::new(next_stop, destination, network, config);
+        path_state.path.push_front(NodeIndex::new(current as usize));
+        path_state.legs.push_front(pod.line_state.line.name.clone());
+        self.path_state = path_state;
+        self.reroute_trip(destination, network, config);
+        self.needs_new_path = false;
+    }
+
     fn decide_on_arrival(
         &mut self,
         pods_box: &mut PodsBox,
         network: &mut Network,
         pod_id: i32,
         config: &Config,
+        time_passed: u32,
+        rng: &mut ChaCha8Rng,
     ) {
-        self.path_state.arrive();
-        // println!("self.path_state: {:?}", self.path_state);
         let pod = pods_box.try_get_pod_by_id_mut(pod_id).unwrap();
+        // A person that has to plan again arrives a second time in the same station
+        if self.path_state.try_get_next_station_id() == Some(pod.get_station_id() as u32) {
+            self.path_state.arrive();
+        }
+        // println!("self.path_state: {:?}", self.path_state);
+        if self.needs_new_path && !pod.is_blocked_ahead() {
+            let station_id = pod.get_station_id() as u32;
+            let destination = self.path_state.path.back().unwrap().index() as u32;
+            self.new_path(station_id, destination, network, config);
+            self.reroute_trip(destination, network, config);
+            self.needs_new_path = false;
+        }
         let line_next_station_id = pod.line_state.get_next_station_id();
         let maybe_next_station_id = self.path_state.try_get_next_station_id();
         match maybe_next_station_id {
-            Some(desired_next_station_id) => {
-                if line_next_station_id != desired_next_station_id as i32
+            Some(_) => {
+                if !self
+                    .path_state
+                    .continues_with(line_next_station_id, &pod.line_state.line.name)
                     || self.action_to_process.is_some()
+                    || self.needs_new_path
+                    || pod.is_out_of_service()
                 {
                     self.state = self.state.to_transitioning();
                     let station = network
                         .try_get_station_by_id(pod.line_state.get_station_id())
                         .unwrap();
                     station.register_person(self.id);
+                    station.record_alighting();
                     let pod = pods_box.try_get_pod_by_id_mut(pod_id).unwrap();
                     pod.deregister_person(&self.id);
-                    self.try_process_action(station.id as u32, network, config);
+                    self.log_event(EventKind::Alight, time_passed, station.id, Some(&*pod));
+                    self.log_event(EventKind::Transfer, time_passed, station.id, None);
+                    if let Some(trip) = &mut self.trip {
+                        trip.alight(time_passed);
+                    }
+                    self.try_process_action(station.id as u32, network, config, rng);
                 } else {
                     self.state = self.state.to_riding(pod_id);
                 }
@@ -392,14 +639,130 @@ impl Person {
                         .try_get_station_by_id(pod.line_state.get_station_id())
                         .unwrap();
                     station.register_person(self.id);
+                    station.record_alighting();
                     let pod = pods_box.try_get_pod_by_id_mut(pod_id).unwrap();
                     pod.deregister_person(&self.id);
-                    self.try_process_action(station.id as u32, network, config);
+                    let station_id = station.id;
+                    self.log_event(EventKind::Alight, time_passed, station_id, Some(&*pod));
+                    self.log_event(EventKind::TripComplete, time_passed, station_id, None);
+                    self.complete_trip(station_id, time_passed, network, config);
+                    self.try_process_action(station_id as u32, network, config, rng);
                 }
             }
         }
     }
 
+    // The reference times are taken from the network as it is when the trip starts
+    fn start_trip(
+        &mut self,
+        origin: i32,
+        destination: i32,
+        time_passed: u32,
+        network: &Network,
+        config: &Config,
+    ) {
+        let air_time = get_air_travel_time(origin as u32, destination as u32, network, config);
+        self.trip = Some(TripMetrics::new(
+            self.id,
+            origin,
+            destination,
+            time_passed,
+            None,
+            air_time,
+        ));
+        self.set_shortest_time(network, config);
+    }
+
+    // Routing without crowding costs as much as planning the path, so it is only done
+    // for trips that are recorded
+    fn set_shortest_time(&mut self, network: &Network, config: &Config) {
+        if !self.gather_metrics {
+            return;
+        }
+        if let Some(trip) = &mut self.trip {
+            trip.shortest_time = network.routing_graph.try_get_shortest_time(
+                trip.origin,
+                trip.destination,
+                network,
+                config,
+            );
+        }
+    }
+
+    // A person that is sent elsewhere or planned again is still on the same trip, only
+    // the references change. Without a route the path and the trip end where they are
+    // and the trip doesn't get to where it was headed, it is aborted.
+    fn reroute_trip(&mut self, headed_to: u32, network: &Network, config: &Config) {
+        let destination = self.path_state.path.back().unwrap().index() as i32;
+        if let Some(trip) = &mut self.trip {
+            trip.aborted |= destination != headed_to as i32;
+            trip.destination = destination;
+            trip.air_time =
+                get_air_travel_time(trip.origin as u32, destination as u32, network, config);
+        }
+        self.set_shortest_time(network, config);
+    }
+
+    fn complete_trip(
+        &mut self,
+        station_id: i32,
+        time_passed: u32,
+        network: &Network,
+        config: &Config,
+    ) {
+        if let Some(trip) = &mut self.trip {
+            trip.complete(station_id, time_passed);
+        }
+        self.record_trip(network, config);
+    }
+
+    fn end_trip_in_station(
+        &mut self,
+        station_id: i32,
+        time_passed: u32,
+        network: &Network,
+        config: &Config,
+    ) {
+        if let Some(trip) = &mut self.trip {
+            trip.end_in_station(station_id, time_passed);
+        }
+        self.record_trip(network, config);
+    }
+
+    fn record_trip(&mut self, network: &Network, config: &Config) {
+        // Trips that started before gathering get their shortest time now
+        if matches!(&self.trip, Some(trip) if trip.shortest_time.is_none()) {
+            self.set_shortest_time(network, config);
+        }
+        if let Some(trip) = self.trip.take() {
+            if self.gather_metrics {
+                self.trips.push(trip);
+            }
+        }
+    }
+
+    // Events of getting in or out of a pod also name the pod, its line and direction
+    fn log_event(
+        &mut self,
+        event: EventKind,
+        time_passed: u32,
+        station_id: i32,
+        maybe_pod: Option<&Pod>,
+    ) {
+        if !self.log_events {
+            return;
+        }
+        let mut event = Event::new(time_passed, event);
+        event.person_id = Some(self.id);
+        event.station_id = Some(station_id);
+        if let Some(pod) = maybe_pod {
+            event.pod_id = Some(pod.id);
+            event.line_name = Some(pod.line_state.line.name.clone());
+            event.direction = Some(pod.line_state.get_direction());
+        }
+        self.events.push(event);
+    }
+
     fn set_coordinates_of_station(&mut self, station_id: i32, network: &Network, config: &Config) {
         // println!("set real coords");
         let station = network.try_get_station_by_id_unmut(station_id).unwrap();
@@ -416,4 +779,12 @@ impl Person {
     pub fn try_get_station_id(&self) -> Option<i32> {
         self.state.try_get_station_id()
     }
+
+    // Scheduled people are only in the network between spawning and exiting
+    pub fn is_in_network(&self) -> bool {
+        !matches!(
+            self.state,
+            PersonState::NotYetSpawned { .. } | PersonState::Exited { .. }
+        )
+    }
 }
@@ -1,13 +1,20 @@
+use serde::{Deserialize, Serialize};
+
 // Person State Machine:
 //      +-------------------+------> InvalidState <---------+
 //      |                   |               ^               |
 //      |                   |               |               |
 // ReadyToTakePod ---> RidingPod ---> JustArrived ---> Transitioning ---+
-//      ^                    ^                |             |    ^      |
-//      |                    +----------------+             |    |      |
-//      +---------------------------------------------------+    +------+
+//   |  ^                    ^                |          ^  |    ^      |
+//   |  |                    +----------------+          |  |    +------+
+//   |  +------------------------------------------------|--+
+//   v                                                   |
+// Exited                                          NotYetSpawned
+//
+// NotYetSpawned and Exited are only used for scheduled trips, people that enter
+// the network at a given time and leave it once they reached their destination.
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum PersonState {
     ReadyToTakePod {
         station_id: i32,
@@ -25,6 +32,14 @@ pub enum PersonState {
         previous_pod_id: i32,
         time_in_station: i32,
     },
+    NotYetSpawned {
+        station_id: i32,
+        destination_id: i32,
+        spawn_at: u32,
+    },
+    Exited {
+        station_id: i32,
+    },
 }
 
 impl Default for PersonState {
@@ -91,6 +106,31 @@ impl PersonState {
             _ => panic!("Person can only get ready to take a pod if in Transitioning state."),
         }
     }
+    // A spawned person still has to walk to the platform, so the transition starts at 0.
+    pub fn to_spawned(&self) -> PersonState {
+        match self {
+            PersonState::NotYetSpawned {
+                station_id,
+                destination_id: _,
+                spawn_at: _,
+            } => PersonState::Transitioning {
+                previous_pod_id: -1,
+                station_id: *station_id,
+                time_in_station: 0,
+            },
+            _ => panic!("Person can only spawn if in NotYetSpawned state."),
+        }
+    }
+
+    pub fn to_exited(&self) -> PersonState {
+        match self {
+            PersonState::ReadyToTakePod { station_id } => PersonState::Exited {
+                station_id: *station_id,
+            },
+            _ => panic!("Person can only exit if in ReadyToTakePod state."),
+        }
+    }
+
     pub fn wait_a_sec(&self) -> PersonState {
         match self {
             PersonState::Transitioning {
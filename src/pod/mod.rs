@@ -1,3 +1,5 @@
 pub mod pod;
 pub mod podsbox;
 pub mod podstate;
+pub mod stopevent;
+pub mod trip;
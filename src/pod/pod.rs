@@ -1,16 +1,23 @@
 use crate::config::structs::Config;
 use crate::helper::enums::LineName;
-use crate::helper::functions::get_screen_coordinates;
+use crate::helper::functions::{get_screen_coordinates, get_time_of_day, is_metrics_sample};
 use crate::line::linestate::LineState;
+use crate::metrics::components::link::{LinkLoad, LinkLoads};
 use crate::metrics::components::pod::PodMetrics;
+use crate::metrics::events::{Event, EventKind};
 use crate::metrics::timeseries::TimeSeries;
+use crate::metrics::timestamp::Timestamp;
 use crate::network::Network;
 use crate::pod::podstate::PodState;
+use crate::pod::stopevent::StopEvent;
+use crate::pod::trip::Trip;
 use ggez::{graphics, Context, GameResult};
-use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::mem::discriminant;
 // use rayon::prelude::*; // For Parralelism
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Pod {
     pub id: i32,
     pub needs_initialization: bool,
@@ -20,10 +27,22 @@ pub struct Pod {
     pub time_series: TimeSeries<PodMetrics>,
     pub in_station_for: i32,
     pub capacity: i32,
-    pub people_in_pod: HashSet<i32>,
+    // Ordered, so a loaded snapshot goes through them like the run that saved it
+    pub people_in_pod: BTreeSet<i32>,
     pub coordinates: (f32, f32),
     pub line_state: LineState,
     pub state: PodState,
+    pub trip: Option<Trip>,
+    // A pod whose trip is over and that left the network, it only keeps its metrics
+    pub retired: bool,
+    // Every stop the pod made, used to export what actually happened as GTFS
+    pub stop_events: Vec<StopEvent>,
+    gather_metrics_since: Option<u32>,
+    // The transitions since the last time the event log took them
+    #[serde(skip)]
+    pub log_events: bool,
+    #[serde(skip)]
+    pub events: Vec<Event>,
 }
 
 impl Pod {
@@ -46,25 +65,52 @@ impl Pod {
             time_series: time_series,
             in_station_for: in_station_for,
             capacity: capacity,
-            people_in_pod: HashSet::new(),
+            people_in_pod: BTreeSet::new(),
             coordinates: (0., 0.),
             line_state: line_state,
             state: PodState::InQueue {
                 station_id: station_id,
                 traveled_distance: 0,
             },
+            trip: None,
+            retired: false,
+            stop_events: vec![],
+            gather_metrics_since: None,
+            log_events: false,
+            events: vec![],
         }
     }
 
     // TODO: remove unused stuff
-    pub fn update(&mut self, network: &mut Network, config: &Config, time_passed: u32) {
+    pub fn update(
+        &mut self,
+        network: &mut Network,
+        config: &Config,
+        time_passed: u32,
+        sums: &mut TimeSeries<PodMetrics>,
+        link_loads: &mut LinkLoads,
+    ) {
+        if self.retired {
+            // Keeps counting for the averages like the other pods
+            if self.gather_metrics {
+                self.record_metrics(config, time_passed, sums);
+            }
+            return;
+        }
         if self.needs_initialization {
-            self.initialize(network)
+            let state_before = self.state.clone();
+            self.initialize(network);
+            self.record_stop_arrival(time_passed);
+            if self.log_events {
+                self.log_event(EventKind::PodSpawn, time_passed, None);
+                self.log_transition(&state_before, time_passed);
+            }
         }
         if self.gather_metrics {
-            self.do_gather_metrics(time_passed)
+            self.do_gather_metrics(config, time_passed, sums)
         }
         self.set_coordinates(network, config);
+        let state_before = self.state.clone();
         match &self.state {
             PodState::BetweenStations {
                 station_id_from: _,
@@ -77,6 +123,8 @@ impl Pod {
                     self.state = self.state.drive_a_sec();
                 } else {
                     self.arrive_in_station(network);
+                    self.record_arrival(time_passed);
+                    self.record_stop_arrival(time_passed);
                 }
             }
             PodState::JustArrived {
@@ -93,10 +141,16 @@ impl Pod {
                 // if self.id == 0 {
                 //     println!("Pod 0 in InStation state {}, {}", self.in_station_for, time_in_station);
                 // }
-                if self.in_station_for > *time_in_station {
+                if self.is_out_of_service() {
+                    self.try_retire(network);
+                } else if self.in_station_for > *time_in_station {
                     self.state = self.state.wait_a_sec();
                 } else {
                     self.depart_from_station(network);
+                    // Turning back leaves the platform too, the pod queues at the other one
+                    if !matches!(self.state, PodState::InStation { .. }) {
+                        self.record_stop_departure(time_passed);
+                    }
                 }
             }
             PodState::InQueue {
@@ -104,15 +158,113 @@ impl Pod {
                 traveled_distance: _,
             } => {
                 self.check_if_in_station(network, *station_id);
+                self.record_stop_arrival(time_passed);
+            }
+            PodState::PassingStation {
+                station_id: _,
+                traveled_distance: _,
+            } => {
+                self.depart_from_station(network);
+                // Held in front of a blocked connection, the distance to here is counted once
+                if let PodState::PassingStation {
+                    station_id: _,
+                    traveled_distance,
+                } = &mut self.state
+                {
+                    *traveled_distance = 0;
+                }
             }
         }
+        if self.gather_metrics {
+            self.record_link_departure(&state_before, config, time_passed, link_loads);
+        }
+        if self.log_events {
+            self.log_transition(&state_before, time_passed);
+        }
+    }
+
+    // Logs what the pod did in this update, compared to the state it was in before
+    fn log_transition(&mut self, state_before: &PodState, time_passed: u32) {
+        if self.retired {
+            self.log_event(EventKind::Retire, time_passed, None);
+            return;
+        }
+        if discriminant(state_before) == discriminant(&self.state) {
+            return;
+        }
+        match (state_before, &self.state) {
+            (
+                _,
+                PodState::BetweenStations {
+                    station_id_from: _,
+                    station_id_to,
+                    time_to_next_station: _,
+                    distance_between: _,
+                },
+            ) => {
+                let station_id_to = *station_id_to;
+                self.log_event(EventKind::Depart, time_passed, Some(station_id_to));
+            }
+            (_, PodState::JustArrived { .. }) => {
+                self.log_event(EventKind::Arrive, time_passed, None);
+            }
+            (PodState::BetweenStations { .. }, PodState::InQueue { .. }) => {
+                self.log_event(EventKind::Queue, time_passed, None);
+            }
+            // Only a pod that turns back goes from the platform to the queue
+            (_, PodState::InQueue { .. }) => {
+                self.log_event(EventKind::TurnBack, time_passed, None);
+            }
+            (_, PodState::PassingStation { .. }) => {
+                self.log_event(EventKind::Pass, time_passed, None);
+            }
+            (_, PodState::InStation { .. }) => {}
+        }
+    }
+
+    // A departing pod is logged with the station it left and the one it drives to
+    fn log_event(&mut self, event: EventKind, time_passed: u32, to_station_id: Option<i32>) {
+        let station_id = match self.state {
+            PodState::BetweenStations {
+                station_id_from,
+                station_id_to: _,
+                time_to_next_station: _,
+                distance_between: _,
+            } => station_id_from,
+            _ => self.state.get_station_id(),
+        };
+        let mut event = Event::new(time_passed, event);
+        event.pod_id = Some(self.id);
+        event.station_id = Some(station_id);
+        event.to_station_id = to_station_id;
+        event.line_name = Some(self.line_state.line.name.clone());
+        event.direction = Some(self.line_state.get_direction());
+        self.events.push(event);
     }
 
     pub fn start_gather_metrics(&mut self) {
         self.gather_metrics = true;
     }
 
-    pub fn do_gather_metrics(&mut self, time_passed: u32) {
+    // Pods that are spawned after gathering started get dummies for the time before
+    pub fn start_gather_metrics_since(&mut self, since: u32) {
+        self.gather_metrics = true;
+        self.gather_metrics_since = Some(since);
+    }
+
+    pub fn do_gather_metrics(
+        &mut self,
+        config: &Config,
+        time_passed: u32,
+        sums: &mut TimeSeries<PodMetrics>,
+    ) {
+        if let Some(since) = self.gather_metrics_since.take() {
+            for ts in since..time_passed {
+                if is_metrics_sample(config, ts) {
+                    self.time_series.time_series.push(Timestamp::dummy(ts));
+                }
+            }
+        }
         self.metrics
             .set_utilization(self.people_in_pod.len() as f32 / self.capacity as f32);
         match &self.state {
@@ -144,9 +296,31 @@ impl Pod {
             } => {
                 self.metrics.increase_time_in_queue();
             }
+            PodState::PassingStation {
+                station_id: _,
+                traveled_distance,
+            } => {
+                self.metrics
+                    .increase_meters_traveled(*traveled_distance as f32);
+                self.metrics.increase_time_driving();
+            }
+        }
+        self.record_metrics(config, time_passed, sums);
+    }
+
+    // The sums of all pods get the metrics every second, the own time series only
+    // every metrics_interval seconds
+    fn record_metrics(
+        &mut self,
+        config: &Config,
+        time_passed: u32,
+        sums: &mut TimeSeries<PodMetrics>,
+    ) {
+        sums.add_to_timestamp(time_passed, &self.metrics);
+        if is_metrics_sample(config, time_passed) {
+            self.time_series
+                .add_timestamp(time_passed, self.metrics.clone());
         }
-        self.time_series
-            .add_timestamp(time_passed, self.metrics.clone());
     }
 
     pub fn draw(&self, ctx: &mut Context, config: &Config) -> GameResult<()> {
@@ -226,14 +400,14 @@ impl Pod {
         match maybe_platform {
             Some(platform) => {
                 if platform.is_passable() {
-                    println!("Passable is not yet implemented so the behaviour is the same as for operational.")
+                    self.state = self.state.to_passing_station();
                 } else {
                     let is_at_platform = platform.register_pod(self.id);
                     if is_at_platform {
                         self.state = self.state.to_just_arrived();
                     }
-                    self.needs_initialization = false;
                 }
+                self.needs_initialization = false;
             }
             None => {
                 println!("Got no platform back")
@@ -244,6 +418,10 @@ impl Pod {
     fn arrive_in_station(&mut self, net: &mut Network) {
         self.line_state.update_line_ix();
         self.line_state.set_next_station_ix();
+        // Like at the end of the line the pod already stops at the platform it leaves from
+        if self.line_state.should_turn_back() {
+            self.line_state.turn_back();
+        }
         let station_id_to = self.state.get_station_id_to();
         let maybe_platform = net.try_get_platform(
             station_id_to,
@@ -254,7 +432,7 @@ impl Pod {
         match maybe_platform {
             Some(platform) => {
                 if platform.is_passable() {
-                    println!("Passable is not yet implemented so the behaviour is the same as for operational.")
+                    self.state = self.state.to_passing_station();
                 } else {
                     let is_at_platform = platform.register_pod(self.id);
                     if is_at_platform {
@@ -278,7 +456,9 @@ impl Pod {
         );
         match maybe_platform {
             Some(platform) => {
-                if platform.pods_at_platform.contains(&self.id) {
+                if platform.is_passable() {
+                    self.state = self.state.to_passing_station()
+                } else if platform.pods_at_platform.contains(&self.id) {
                     self.state = self.state.to_just_arrived()
                 }
             }
@@ -318,12 +498,138 @@ impl Pod {
                         connection.travel_time,
                         connection.distance,
                     );
+                } else if self.line_state.should_turn_back() {
+                    self.turn_back(net);
                 }
             }
             None => panic!("There is no connection between: {} and {}", current, next),
         }
     }
 
+    // Short-turn in the current station, the pod changes over to the platform of the
+    // other direction and queues up there like a pod that just arrived.
+    fn turn_back(&mut self, net: &mut Network) {
+        let station_id = self.state.get_station_id();
+        let maybe_platform = net.try_get_platform(
+            station_id,
+            &self.line_state.line.name,
+            self.line_state.get_direction(),
+        );
+        match maybe_platform {
+            Some(platform) => platform.deregister_pod(self.id),
+            None => panic!("There is no station with id: {}", station_id),
+        }
+
+        self.line_state.turn_back();
+        if let Some(trip) = &mut self.trip {
+            trip.turn_back();
+        }
+        let maybe_platform = net.try_get_platform(
+            station_id,
+            &self.line_state.line.name,
+            self.line_state.get_direction(),
+        );
+        match maybe_platform {
+            // check_if_in_station picks it up from the queue
+            Some(platform) => {
+                if !platform.is_passable() {
+                    platform.register_pod(self.id);
+                }
+            }
+            None => panic!("There is no station with id: {}", station_id),
+        }
+        self.state = self.state.to_turned_back();
+    }
+
+    fn record_arrival(&mut self, time_passed: u32) {
+        let station_id = self.line_state.get_station_id();
+        if let Some(trip) = &mut self.trip {
+            if let Some(lateness) = trip.try_arrive(station_id, time_passed) {
+                self.metrics.set_lateness(lateness as f32);
+            }
+        }
+    }
+
+    // A pod that left a station or passed it drives over the connection to the next one
+    // with everyone who is in it now
+    fn record_link_departure(
+        &self,
+        state_before: &PodState,
+        config: &Config,
+        time_passed: u32,
+        link_loads: &mut LinkLoads,
+    ) {
+        if matches!(state_before, PodState::BetweenStations { .. }) {
+            return;
+        }
+        if let PodState::BetweenStations {
+            station_id_from,
+            station_id_to,
+            time_to_next_station: _,
+            distance_between: _,
+        } = self.state
+        {
+            let link = LinkLoad::new(
+                station_id_from,
+                station_id_to,
+                self.line_state.line.name.clone(),
+                self.line_state.get_direction(),
+                get_time_of_day(config, time_passed) / 3600,
+            );
+            link_loads.add_pod(link, self.people_in_pod.len() as u32, self.capacity as u32);
+        }
+    }
+
+    // Only a pod that got to its platform stopped, passing or queueing pods did not
+    fn record_stop_arrival(&mut self, time_passed: u32) {
+        if let PodState::JustArrived {
+            station_id,
+            traveled_distance: _,
+        } = self.state
+        {
+            self.stop_events.push(StopEvent {
+                station_id: station_id,
+                direction: self.line_state.get_direction(),
+                arrival: time_passed,
+                departure: None,
+            });
+        }
+    }
+
+    fn record_stop_departure(&mut self, time_passed: u32) {
+        if let Some(stop_event) = self.stop_events.last_mut() {
+            if stop_event.departure.is_none() {
+                stop_event.departure = Some(time_passed);
+            }
+        }
+    }
+
+    // The trip is over, nobody gets in anymore and everyone inside has to get off
+    pub fn is_out_of_service(&self) -> bool {
+        match &self.trip {
+            Some(trip) => trip.is_finished(),
+            None => false,
+        }
+    }
+
+    fn try_retire(&mut self, net: &mut Network) {
+        if !self.people_in_pod.is_empty() {
+            return;
+        }
+        let station_id = self.state.get_station_id();
+        let maybe_platform = net.try_get_platform(
+            station_id,
+            &self.line_state.line.name,
+            self.line_state.get_direction(),
+        );
+        match maybe_platform {
+            Some(platform) => platform.deregister_pod(self.id),
+            None => panic!("There is no station with id: {}", station_id),
+        }
+        self.metrics.set_utilization(0.);
+        self.retired = true;
+    }
+
     pub fn try_register_person(&mut self, person_id: i32) -> bool {
         // println!("------------------------------------------------------");
         // println!("self.people_in_pod.len(): {}", self.people_in_pod.len());
@@ -353,6 +659,21 @@ impl Pod {
         }
     }
 
+    // Whether the pod can't leave the station it is in because the connection ahead is blocked
+    pub fn is_blocked_ahead(&self) -> bool {
+        self.line_state.is_blocked_ahead()
+    }
+
+    pub fn is_passing_station(&self) -> bool {
+        match self.state {
+            PodState::PassingStation {
+                station_id: _,
+                traveled_distance: _,
+            } => true,
+            _ => false,
+        }
+    }
+
     pub fn set_coordinates(&mut self, network: &Network, config: &Config) {
         match self.state {
             PodState::BetweenStations {
@@ -399,6 +720,10 @@ impl Pod {
             PodState::JustArrived {
                 station_id,
                 traveled_distance: _,
+            }
+            | PodState::PassingStation {
+                station_id,
+                traveled_distance: _,
             } => {
                 let station = network.try_get_station_by_id_unmut(station_id).unwrap();
                 self.coordinates = get_screen_coordinates(station.coordinates, config);
@@ -1,31 +1,151 @@
-use crate::config::structs::Config;
+use crate::config::structs::{Config, TimetableConfig};
 use crate::helper::enums::{Direction, LineName};
+use crate::helper::functions::get_time_of_day;
 use crate::line::line::Line;
 use crate::line::linestate::LineState;
+use crate::metrics::components::link::{LinkLoads, LINK_HEADER};
+use crate::metrics::components::pod::PodMetrics;
+use crate::metrics::events::Event;
+use crate::metrics::histogram::{format_distributions, Histogram};
 use crate::metrics::timeseries::TimeSeries;
-use crate::metrics::traits::Series;
+use crate::metrics::traits::{Metrics, Series};
 use crate::network::Network;
 use crate::pod::pod::Pod;
+use crate::pod::trip::Trip;
+use csv::Writer;
 use ggez::Context;
+use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 use std::fs::File;
 use std::fs::*;
 use std::io::prelude::*;
 use std::path::Path;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PodsBox {
     pub pods: Vec<Pod>,
+    gather_metrics_since: Option<u32>,
+    // The metrics of all pods added up for every second since gathering started, the
+    // averages don't need the time series of each pod
+    sums: TimeSeries<PodMetrics>,
+    // The pods with a trip of the timetable in every second of the sums, only they can be late
+    trip_counts: Vec<u32>,
+    // The utilization of every pod in the network in every second
+    utilization: Histogram,
+    // The people and places the pods carried over each connection
+    link_loads: LinkLoads,
+    #[serde(skip)]
+    log_events: bool,
 }
 
 impl PodsBox {
+    pub fn new() -> Self {
+        PodsBox {
+            pods: vec![],
+            gather_metrics_since: None,
+            sums: TimeSeries::new(),
+            trip_counts: vec![],
+            utilization: Histogram::new(0.01),
+            link_loads: LinkLoads::new(),
+            log_events: false,
+        }
+    }
+
     pub fn update(&mut self, network: &mut Network, config: &Config, time_passed: u32) {
+        if let Some(timetable) = &config.timetable {
+            self.dispatch_trips(timetable, network, config, time_passed);
+        }
+        let gather_metrics = self.gather_metrics_since.is_some();
+        if gather_metrics {
+            // Every second gets a timestamp, even one without pods
+            self.sums
+                .add_to_timestamp(time_passed, &PodMetrics::default());
+            let n_trips = self
+                .pods
+                .iter()
+                .filter(|pod| pod.gather_metrics && pod.trip.is_some())
+                .count();
+            self.trip_counts.push(n_trips as u32);
+        }
         for pod in &mut self.pods {
-            pod.update(network, config, time_passed)
+            pod.update(
+                network,
+                config,
+                time_passed,
+                &mut self.sums,
+                &mut self.link_loads,
+            )
+        }
+        if gather_metrics {
+            for pod in &self.pods {
+                if !pod.retired {
+                    self.utilization.add(pod.metrics.utilization);
+                }
+            }
         }
         // TODO: figure out a way to do this in parralel, maybe with message queues or something.
         // self.pods.par_iter_mut().for_each(|pod| pod.update(network, config));
     }
+    // Starts a pod for every trip that departs in this second of the day. The pods
+    // start in the first station of the line in their direction, whether or not the
+    // platform is an entrypoint for the line.
+    fn dispatch_trips(
+        &mut self,
+        timetable: &TimetableConfig,
+        network: &Network,
+        config: &Config,
+        time_passed: u32,
+    ) {
+        // The first update is at time_passed 1, so every second of the day is dispatched
+        // one update later and the departures at start_time are not left out
+        let time_of_day = get_time_of_day(config, time_passed - 1);
+        for (timetable_line, timetable_trip) in timetable.get_departures(time_of_day) {
+            let maybe_line = network
+                .lines
+                .iter()
+                .find(|line| line.name == timetable_line.line_name);
+            let line = match maybe_line {
+                Some(line) => line,
+                None => {
+                    println!(
+                        "trip {} can't be started, there is no line {}.",
+                        timetable_trip.id,
+                        timetable_line.line_name.to_config_string()
+                    );
+                    continue;
+                }
+            };
+            let station_id = match timetable_line.direction {
+                Direction::Neg if !line.circular => *line.stations.last().unwrap(),
+                _ => line.stations[0],
+            };
+
+            let n_pods = self.pods.len();
+            self.add_pod(
+                &timetable_line.line_name,
+                &timetable_line.direction,
+                &station_id,
+                &network.lines,
+                config,
+                time_passed,
+            );
+            if self.pods.len() == n_pods {
+                println!(
+                    "trip {} can't be started, no pod was added in station {}.",
+                    timetable_trip.id, station_id
+                );
+                continue;
+            }
+            let pod = self.pods.last_mut().unwrap();
+            pod.trip = Some(Trip::new(
+                timetable_trip.id.clone(),
+                &pod.line_state,
+                time_passed,
+                config,
+            ));
+        }
+    }
+
     pub fn try_get_pod_by_id_mut(&mut self, pod_id: i32) -> Option<&mut Pod> {
         for pod in &mut self.pods {
             if pod.id == pod_id {
@@ -35,12 +155,33 @@ impl PodsBox {
         return None;
     }
 
-    pub fn start_gather_metrics(&mut self) {
+    pub fn start_gather_metrics(&mut self, time_passed: u32) {
+        // The first timestamp is written in the update after this one
+        self.gather_metrics_since = Some(time_passed + 1);
         for pod in &mut self.pods {
             pod.start_gather_metrics();
         }
     }
 
+    pub fn set_log_events(&mut self, log_events: bool) {
+        self.log_events = log_events;
+        for pod in &mut self.pods {
+            pod.log_events = log_events;
+            pod.events.clear();
+        }
+    }
+
+    // The events of all pods since the last call, in the order of the pods
+    pub fn take_events(&mut self) -> Vec<Event> {
+        let mut events = vec![];
+        if self.log_events {
+            for pod in &mut self.pods {
+                events.append(&mut pod.events);
+            }
+        }
+        events
+    }
+
     pub fn add_pod(
         &mut self,
         line_name: &LineName,
@@ -109,13 +250,17 @@ impl PodsBox {
                         };
                     }
                 }
-                let pod = Pod::new(
+                let mut pod = Pod::new(
                     id,
                     config.logic.pod_in_station_seconds,
                     config.logic.pod_capacity,
                     line_state,
                     time_passed,
                 );
+                if let Some(since) = self.gather_metrics_since {
+                    pod.start_gather_metrics_since(since);
+                }
+                pod.log_events = self.log_events;
                 self.pods.push(pod);
             }
         }
@@ -145,6 +290,9 @@ impl PodsBox {
         let mut closest_distance = 10000.;
         let mut closest_pod = &self.pods[0];
         for pod in &self.pods {
+            if pod.retired {
+                continue;
+            }
             let pod_coordinates = pod.get_coordinates();
             let distance =
                 ((pod_coordinates.0 - x).powi(2) + (pod_coordinates.1 - y).powi(2)).sqrt();
@@ -163,7 +311,9 @@ impl PodsBox {
 
     pub fn draw(&self, ctx: &mut Context, config: &Config) {
         for pod in &self.pods {
-            let _res = pod.draw(ctx, config);
+            if !pod.retired {
+                let _res = pod.draw(ctx, config);
+            }
         }
     }
 
@@ -201,18 +351,8 @@ impl PodsBox {
         let maybe_pod = self.try_get_pod_by_id_unmut(pod_id);
         match maybe_pod {
             Some(pod) => {
-                if let Some(timestamp_run) = config.timestamp_run {
-                    let timestamp = timestamp_run
-                        .naive_utc()
-                        .format("%Y.%m.%d %H:%M")
-                        .to_string()
-                        .replace(" ", "_");
-                    println!("timestamp_run: {:?}", timestamp);
-
-                    let path_str = format!(
-                        "{}/{}/{}/{}/{}/{}.txt",
-                        "metrics", config.environment, timestamp, "pods", "ids", pod_id
-                    );
+                if let Some(output_dir) = config.get_output_dir() {
+                    let path_str = format!("{}/{}/{}/{}.txt", output_dir, "pods", "ids", pod_id);
                     let path = Path::new(&path_str);
                     let parent = path.parent().unwrap();
                     let _res = create_dir_all(parent);
@@ -220,12 +360,14 @@ impl PodsBox {
                     match res {
                         Ok(mut file) => {
                             let txt = pod.time_series.format_to_file(String::from(
-                                "ts,utilization,time_in_station,time_in_queue,time_driving,meters_traveled\n",
+                                "ts,utilization,time_in_station,time_in_queue,time_driving,meters_traveled,lateness\n",
                             ));
                             let res = file.write_all(txt.as_bytes());
                             match res {
                                 Ok(_) => {
-                                    println!("written file");
+                                    if !config.general.quiet {
+                                        println!("written file");
+                                    }
                                 }
                                 Err(e) => {
                                     println!("error writing file: {}", e);
@@ -248,26 +390,59 @@ impl PodsBox {
         }
     }
 
-    pub fn dump_avg_metrics(&self, config: &Config) {
-        let mut timeseries_accumulator = TimeSeries::new();
-        for pod in &self.pods {
-            timeseries_accumulator.add_layer(&pod.time_series);
+    pub fn get_avg_time_series(&self) -> TimeSeries<PodMetrics> {
+        if self.pods.is_empty() {
+            return TimeSeries::new();
         }
+        let n_pods = self.pods.len() as u32;
+        let mut timeseries_accumulator = self.sums.clone();
+        for (timestamp, n_trips) in timeseries_accumulator
+            .time_series
+            .iter_mut()
+            .zip(&self.trip_counts)
+        {
+            let lateness = timestamp.metrics.lateness;
+            timestamp.normalize_by(n_pods);
+            timestamp.metrics.lateness = if *n_trips > 0 {
+                lateness / *n_trips as f32
+            } else {
+                0.
+            };
+        }
+        timeseries_accumulator
+    }
 
-        timeseries_accumulator.normalize_by(self.pods.len() as u32);
-
-        if let Some(timestamp_run) = config.timestamp_run {
-            let timestamp = timestamp_run
-                .naive_utc()
-                .format("%Y.%m.%d %H:%M")
-                .to_string()
-                .replace(" ", "_");
-            println!("timestamp_run: {:?}", timestamp);
+    // The utilization over every second of every pod, the lateness over the pods with a
+    // trip and the rest over all pods
+    pub fn get_distributions(&self) -> Vec<(&'static str, Histogram)> {
+        let mut time_in_station = Histogram::new(1.);
+        let mut time_in_queue = Histogram::new(1.);
+        let mut time_driving = Histogram::new(1.);
+        let mut meters_traveled = Histogram::new(10.);
+        let mut lateness = Histogram::new(1.);
+        for pod in &self.pods {
+            time_in_station.add(pod.metrics.time_in_station);
+            time_in_queue.add(pod.metrics.time_in_queue);
+            time_driving.add(pod.metrics.time_driving);
+            meters_traveled.add(pod.metrics.meters_traveled);
+            if pod.trip.is_some() {
+                lateness.add(pod.metrics.lateness);
+            }
+        }
+        vec![
+            ("utilization", self.utilization.clone()),
+            ("time_in_station", time_in_station),
+            ("time_in_queue", time_in_queue),
+            ("time_driving", time_driving),
+            ("meters_traveled", meters_traveled),
+            ("lateness", lateness),
+        ]
+    }
 
-            let path_str = format!(
-                "{}/{}/{}/{}/{}.txt",
-                "metrics", config.environment, timestamp, "pods", "avg"
-            );
+    pub fn dump_avg_metrics(&self, config: &Config) {
+        let timeseries_accumulator = self.get_avg_time_series();
+        if let Some(output_dir) = config.get_output_dir() {
+            let path_str = format!("{}/{}/{}.txt", output_dir, "pods", "avg");
             let path = Path::new(&path_str);
             let parent = path.parent().unwrap();
             let _res = create_dir_all(parent);
@@ -275,12 +450,14 @@ impl PodsBox {
             match res {
                 Ok(mut file) => {
                     let txt = timeseries_accumulator.format_to_file(String::from(
-                        "ts,utilization,time_in_station,time_in_queue,time_driving,meters_traveled\n",
+                        "ts,utilization,time_in_station,time_in_queue,time_driving,meters_traveled,lateness\n",
                     ));
                     let res = file.write_all(txt.as_bytes());
                     match res {
                         Ok(_) => {
-                            println!("written file");
+                            if !config.general.quiet {
+                                println!("written file");
+                            }
                         }
                         Err(e) => {
                             println!("error writing file: {}", e);
@@ -291,6 +468,53 @@ impl PodsBox {
                     println!("error opening file: {}", e);
                 }
             }
+
+            let path_str = format!("{}/{}/{}.txt", output_dir, "pods", "dist");
+            let txt = format_distributions(&self.get_distributions());
+            match write(&path_str, txt) {
+                Ok(_) => {
+                    if !config.general.quiet {
+                        println!("written file");
+                    }
+                }
+                Err(e) => {
+                    println!("error writing file: {}", e);
+                }
+            }
+        }
+    }
+
+    // The link-load table, a row for every connection, line, direction and hour of the day
+    // pods drove in while metrics were gathered
+    pub fn dump_link_loads(&self, config: &Config) {
+        if let Some(output_dir) = config.get_output_dir() {
+            let path_str = format!("{}/{}.csv", output_dir, "links");
+            let _res = create_dir_all(&output_dir);
+            let mut writer = match Writer::from_path(&path_str) {
+                Ok(writer) => writer,
+                Err(e) => {
+                    println!("error opening file: {}", e);
+                    return;
+                }
+            };
+            if let Err(e) = writer.write_record(LINK_HEADER) {
+                println!("error writing file: {}", e);
+                return;
+            }
+            for link in self.link_loads.get_sorted() {
+                if let Err(e) = writer.write_record(link.to_record()) {
+                    println!("error writing file: {}", e);
+                    return;
+                }
+            }
+            match writer.flush() {
+                Ok(_) => {
+                    if !config.general.quiet {
+                        println!("written file");
+                    }
+                }
+                Err(e) => println!("error writing file: {}", e),
+            }
         }
     }
 }
@@ -1,3 +1,5 @@
+use serde::{Deserialize, Serialize};
+
 // Pod State Machine:
 //      +-------------------+------> InvalidState
 //      |                   |                 ^
@@ -6,9 +8,16 @@
 //      ^    ^   |                            |  |    |
 //      |    +---+                            |  +----+
 //      +-------------------------------------+
+//      |                                     |
+//      +---------- PassingStation <----------+ (from BetweenStations or InQueue)
+//
+// PassingStation is used when the platform is passable, the pod does not stop
+// and leaves again as soon as the connection ahead is not blocked.
+// A pod that turns back in InStation or PassingStation queues up again at the
+// platform of the other direction, so it goes back to InQueue.
 
 // Can add defects and stuff like that as a state
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum PodState {
     BetweenStations {
         station_id_from: i32,
@@ -28,6 +37,10 @@ pub enum PodState {
         station_id: i32,
         time_in_station: i32,
     },
+    PassingStation {
+        station_id: i32,
+        traveled_distance: i32,
+    },
 }
 
 // State Transitions
@@ -42,13 +55,34 @@ impl PodState {
             PodState::InStation {
                 station_id,
                 time_in_station: _,
+            }
+            | PodState::PassingStation {
+                station_id,
+                traveled_distance: _,
             } => PodState::BetweenStations {
                 station_id_from: *station_id,
                 station_id_to: to_pod_id,
                 time_to_next_station: time_to_next_station,
                 distance_between: distance,
             },
-            _ => panic!("Pod can only appart from InStation state."),
+            _ => panic!("Pod can only appart from InStation or PassingStation state."),
+        }
+    }
+
+    pub fn to_turned_back(&self) -> PodState {
+        match self {
+            PodState::InStation {
+                station_id,
+                time_in_station: _,
+            }
+            | PodState::PassingStation {
+                station_id,
+                traveled_distance: _,
+            } => PodState::InQueue {
+                station_id: *station_id,
+                traveled_distance: 0,
+            },
+            _ => panic!("Pod can only turn back from InStation or PassingStation state."),
         }
     }
 
@@ -89,6 +123,28 @@ impl PodState {
         }
     }
 
+    pub fn to_passing_station(&self) -> PodState {
+        match self {
+            PodState::BetweenStations {
+                station_id_from: _,
+                station_id_to,
+                time_to_next_station: _,
+                distance_between,
+            } => PodState::PassingStation {
+                station_id: *station_id_to,
+                traveled_distance: *distance_between,
+            },
+            PodState::InQueue {
+                station_id,
+                traveled_distance,
+            } => PodState::PassingStation {
+                station_id: *station_id,
+                traveled_distance: *traveled_distance,
+            },
+            _ => panic!("Pod can only pass a station if in BetweenStations or InQueue state."),
+        }
+    }
+
     pub fn to_in_station(&self) -> PodState {
         match self {
             PodState::JustArrived {
@@ -146,7 +202,11 @@ impl PodState {
                 time_in_station: _,
                 station_id,
             } => *station_id,
-            _ => panic!("Can only get id of stationif in JustArrived, InQueue or InStation state"),
+            PodState::PassingStation {
+                station_id,
+                traveled_distance: _,
+            } => *station_id,
+            _ => panic!("Can only get id of station if in JustArrived, InQueue, InStation or PassingStation state"),
         }
     }
 
@@ -172,7 +232,11 @@ impl PodState {
                 station_id: _,
                 traveled_distance,
             } => *traveled_distance,
-            _ => panic!("Can only get distance travelled if in InQueue or JustArrived State."),
+            PodState::PassingStation {
+                station_id: _,
+                traveled_distance,
+            } => *traveled_distance,
+            _ => panic!("Can only get distance travelled if in InQueue, JustArrived or PassingStation State."),
         }
     }
 }
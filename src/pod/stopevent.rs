@@ -0,0 +1,13 @@
+use crate::helper::enums::Direction;
+use serde::{Deserialize, Serialize};
+
+// A stop a pod actually made, times are in time_passed. Pods passing a station
+// don't stop, so they don't leave an event there.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StopEvent {
+    pub station_id: i32,
+    pub direction: Direction,
+    pub arrival: u32,
+    // None while the pod is still in the station or if it retired there
+    pub departure: Option<u32>,
+}
@@ -0,0 +1,115 @@
+use crate::config::constants::POD_SECONDS_PER_STOP;
+use crate::config::structs::Config;
+use crate::line::linestate::LineState;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+// A run of a timetabled pod from the first to the last station of its line. The
+// schedule is derived from the departure, the travel times of the connections and
+// the time pods stand in each station.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Trip {
+    pub id: String,
+    // Station ids and scheduled arrivals in time_passed, the next stop is in front
+    pub stops: VecDeque<(i32, u32)>,
+}
+
+impl Trip {
+    pub fn new(id: String, line_state: &LineState, departure: u32, config: &Config) -> Self {
+        let line = &line_state.line;
+        let n_stations = line.stations.len() as i32;
+        let n_stops = if line.circular {
+            n_stations
+        } else if line_state.direction > 0 {
+            n_stations - 1 - line_state.line_ix
+        } else {
+            line_state.line_ix
+        };
+
+        let mut stops: VecDeque<(i32, u32)> = VecDeque::new();
+        let mut run_state = line_state.clone();
+        // The first station is entered right away on dispatch, so the arriving second is saved there
+        let mut scheduled = departure.saturating_sub(1);
+        for _ in 0..n_stops {
+            let current = run_state.get_station_id();
+            let next = run_state.get_next_station_id();
+            let travel_time = match run_state.try_get_connection(current, next) {
+                Some(connection) => connection.travel_time as u32,
+                None => break,
+            };
+            scheduled +=
+                config.logic.pod_in_station_seconds as u32 + travel_time + POD_SECONDS_PER_STOP;
+            stops.push_back((next, scheduled));
+            run_state.update_line_ix();
+            run_state.set_next_station_ix();
+        }
+
+        Trip {
+            id: id,
+            stops: stops,
+        }
+    }
+
+    // Returns the lateness in seconds if the station is a stop of the trip. Stops before
+    // it were passed without stopping or skipped, they are dropped from the schedule.
+    pub fn try_arrive(&mut self, station_id: i32, time_passed: u32) -> Option<i32> {
+        let position = self
+            .stops
+            .iter()
+            .position(|(stop_station_id, _)| *stop_station_id == station_id)?;
+        self.stops.drain(..position);
+        let (_, scheduled) = self.stops.pop_front()?;
+        Some(time_passed as i32 - scheduled as i32)
+    }
+
+    // A pod that turned back can't get to the rest of its stops, the trip ends where it is
+    pub fn turn_back(&mut self) {
+        self.stops.clear();
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.stops.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_trip() -> Trip {
+        Trip {
+            id: String::from("trip"),
+            stops: VecDeque::from([(2, 100), (3, 200), (4, 300)]),
+        }
+    }
+
+    #[test]
+    fn arrives_at_next_stop() {
+        let mut trip = get_trip();
+        assert_eq!(trip.try_arrive(2, 110), Some(10));
+        assert_eq!(trip.stops.front(), Some(&(3, 200)));
+    }
+
+    #[test]
+    fn skipped_stops_are_dropped() {
+        let mut trip = get_trip();
+        assert_eq!(trip.try_arrive(4, 290), Some(-10));
+        assert!(trip.is_finished());
+    }
+
+    #[test]
+    fn station_not_in_trip() {
+        let mut trip = get_trip();
+        assert_eq!(trip.try_arrive(7, 110), None);
+        assert_eq!(trip.stops.len(), 3);
+    }
+
+    #[test]
+    fn turned_back_trip_is_finished() {
+        let mut trip = get_trip();
+        assert_eq!(trip.try_arrive(2, 100), Some(0));
+        trip.turn_back();
+        assert!(trip.is_finished());
+        assert_eq!(trip.try_arrive(3, 200), None);
+    }
+}
@@ -0,0 +1,536 @@
+use crate::config::structs::Config;
+use crate::helper::enums::{Direction, LineName};
+use crate::helper::functions::get_air_travel_time;
+use crate::line::line::Line;
+use crate::network::Network;
+use crate::pathstate::PathState;
+use crate::station::station::Station;
+use petgraph::algo::astar;
+use petgraph::graph::{DiGraph, NodeIndex};
+use petgraph::visit::EdgeRef;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+// A station node is where people walk around and change lines, a platform node is
+// a line in one direction at a station, people that are there sit in a pod.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum RoutingNode {
+    Station {
+        station_id: i32,
+    },
+    Platform {
+        station_id: i32,
+        line_name: LineName,
+        direction: Direction,
+    },
+}
+
+impl RoutingNode {
+    pub fn get_station_id(&self) -> i32 {
+        match self {
+            RoutingNode::Station { station_id } => *station_id,
+            RoutingNode::Platform {
+                station_id,
+                line_name: _,
+                direction: _,
+            } => *station_id,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum RoutingEdge {
+    // Travel time of the connection plus the time the pod stands in the next station
+    Ride { seconds: u32 },
+    // Getting to the platform and waiting for half a headway, crowding is added while routing
+    Board { station_id: i32, seconds: u32 },
+    Alight,
+}
+
+// The stations of a route and the line that is taken from each of them to the next,
+// so legs is one shorter than stations.
+#[derive(Debug, Clone)]
+pub struct Route {
+    pub stations: Vec<i32>,
+    pub legs: Vec<LineName>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RoutingGraph {
+    pub graph: DiGraph<RoutingNode, RoutingEdge>,
+    node_indices: HashMap<RoutingNode, NodeIndex>,
+}
+
+impl RoutingGraph {
+    // Blocked connections get no ride edge and passable platforms get no board and
+    // alight edges, pods still drive through them without the time in station.
+    pub fn new(lines: &Vec<Line>, stations: &Vec<Station>, config: &Config) -> Self {
+        let mut routing_graph = RoutingGraph::default();
+        let mut platform_nodes: Vec<RoutingNode> = vec![];
+
+        for station in stations {
+            routing_graph.get_or_add_node(RoutingNode::Station {
+                station_id: station.id,
+            });
+        }
+
+        for line in lines {
+            let n_stations = line.stations.len();
+            let mut pairs: Vec<(usize, usize)> = (1..n_stations).map(|j| (j - 1, j)).collect();
+            if line.circular && n_stations > 1 {
+                pairs.push((n_stations - 1, 0));
+            }
+
+            for (i, j) in pairs {
+                let from = line.stations[i];
+                let to = line.stations[j];
+                let maybe_connection = line
+                    .connections
+                    .iter()
+                    .find(|connection| connection.station_ids == HashSet::from([from, to]));
+                let travel_time = match maybe_connection {
+                    Some(connection) if !connection.is_blocked => connection.travel_time as u32,
+                    _ => continue,
+                };
+
+                // Pods turn around in the last station and stop at the platform of the other
+                // direction, the same happens in front of a blocked connection if the line turns
+                // and the way back is open, see LineState::should_turn_back
+                let mut arrival_pos = Direction::Pos;
+                if (!line.circular && j == n_stations - 1) || line.turns_back_at(j, 1) {
+                    arrival_pos = Direction::Neg;
+                }
+                let mut arrival_neg = Direction::Neg;
+                if (!line.circular && i == 0) || line.turns_back_at(i, -1) {
+                    arrival_neg = Direction::Pos;
+                }
+
+                for (start, end, direction, arrival_direction) in [
+                    (from, to, Direction::Pos, arrival_pos),
+                    (to, from, Direction::Neg, arrival_neg),
+                ] {
+                    let mut seconds = travel_time;
+                    if !is_platform_passable(stations, end, &line.name, &arrival_direction) {
+                        seconds += config.logic.pod_in_station_seconds as u32;
+                    }
+                    let start_node = RoutingNode::Platform {
+                        station_id: start,
+                        line_name: line.name.clone(),
+                        direction: direction,
+                    };
+                    let end_node = RoutingNode::Platform {
+                        station_id: end,
+                        line_name: line.name.clone(),
+                        direction: arrival_direction,
+                    };
+                    for node in [&start_node, &end_node] {
+                        if !routing_graph.node_indices.contains_key(node) {
+                            platform_nodes.push(node.clone());
+                        }
+                    }
+                    let start_ix = routing_graph.get_or_add_node(start_node);
+                    let end_ix = routing_graph.get_or_add_node(end_node);
+                    routing_graph.graph.add_edge(
+                        start_ix,
+                        end_ix,
+                        RoutingEdge::Ride { seconds: seconds },
+                    );
+                }
+            }
+        }
+
+        let headway = 3600 / config.logic.line_pods_per_hour.max(1) as u32;
+        let board_seconds = config.logic.transition_time as u32 + headway / 2;
+        for platform_node in platform_nodes {
+            if let RoutingNode::Platform {
+                station_id,
+                line_name,
+                direction,
+            } = &platform_node
+            {
+                if is_platform_passable(stations, *station_id, line_name, direction) {
+                    continue;
+                }
+                let station_ix = routing_graph.get_or_add_node(RoutingNode::Station {
+                    station_id: *station_id,
+                });
+                let platform_ix = routing_graph.get_or_add_node(platform_node.clone());
+                routing_graph.graph.add_edge(
+                    station_ix,
+                    platform_ix,
+                    RoutingEdge::Board {
+                        station_id: *station_id,
+                        seconds: board_seconds,
+                    },
+                );
+                routing_graph
+                    .graph
+                    .add_edge(platform_ix, station_ix, RoutingEdge::Alight);
+            }
+        }
+
+        routing_graph
+    }
+
+    fn get_or_add_node(&mut self, node: RoutingNode) -> NodeIndex {
+        if let Some(node_ix) = self.node_indices.get(&node) {
+            return *node_ix;
+        }
+        let node_ix = self.graph.add_node(node.clone());
+        self.node_indices.insert(node, node_ix);
+        node_ix
+    }
+
+    // Boarding gets more expensive the more people already wait in a station, for
+    // every full pod of people waiting one more headway is expected.
+    pub fn try_find_route(
+        &self,
+        start: i32,
+        end: i32,
+        network: &Network,
+        config: &Config,
+    ) -> Option<Route> {
+        let start_ix = *self
+            .node_indices
+            .get(&RoutingNode::Station { station_id: start })?;
+        let end_ix = *self
+            .node_indices
+            .get(&RoutingNode::Station { station_id: end })?;
+
+        let headway = 3600 / config.logic.line_pods_per_hour.max(1) as u32;
+        let capacity = config.logic.pod_capacity.max(1) as u32;
+        let mut waiting: HashMap<i32, u32> = HashMap::new();
+        for station in &network.stations {
+            waiting.insert(station.id, station.people_in_station.len() as u32);
+        }
+
+        let (_, node_path) = astar(
+            &self.graph,
+            start_ix,
+            |node_ix| node_ix == end_ix,
+            |edge| match edge.weight() {
+                RoutingEdge::Ride { seconds } => *seconds,
+                RoutingEdge::Board {
+                    station_id,
+                    seconds,
+                } => seconds + headway * waiting.get(station_id).unwrap_or(&0) / capacity,
+                RoutingEdge::Alight => 0,
+            },
+            |node_ix| {
+                let station_id = self.graph[node_ix].get_station_id() as u32;
+                get_air_travel_time(station_id, end as u32, network, config)
+            },
+        )?;
+
+        let mut route = Route {
+            stations: vec![start],
+            legs: vec![],
+        };
+        for node_ixs in node_path.windows(2) {
+            if let (
+                RoutingNode::Platform {
+                    station_id: _,
+                    line_name,
+                    direction: _,
+                },
+                RoutingNode::Platform {
+                    station_id,
+                    line_name: _,
+                    direction: _,
+                },
+            ) = (&self.graph[node_ixs[0]], &self.graph[node_ixs[1]])
+            {
+                route.stations.push(*station_id);
+                route.legs.push(line_name.clone());
+            }
+        }
+        Some(route)
+    }
+
+    // The least time a trip between the stations can take, on the same terms as the
+    // travel time of a trip. The first pod can be boarded right away, only changing pods
+    // takes the transition time and nobody waits for a pod. The trip ends when the last
+    // pod arrives, so the time it stands in the last station of each ride doesn't count.
+    pub fn try_get_shortest_time(
+        &self,
+        start: i32,
+        end: i32,
+        network: &Network,
+        config: &Config,
+    ) -> Option<u32> {
+        let start_ix = *self
+            .node_indices
+            .get(&RoutingNode::Station { station_id: start })?;
+        let end_ix = *self
+            .node_indices
+            .get(&RoutingNode::Station { station_id: end })?;
+
+        let transition_time = config.logic.transition_time as u32;
+        let in_station_seconds = config.logic.pod_in_station_seconds as u32;
+        // Exact if pods stand shorter than the transition time, otherwise the route with
+        // the least time in pods
+        let board_seconds = transition_time.saturating_sub(in_station_seconds);
+        let (seconds, node_ixs) = astar(
+            &self.graph,
+            start_ix,
+            |node_ix| node_ix == end_ix,
+            |edge| match edge.weight() {
+                RoutingEdge::Ride { seconds } => *seconds,
+                RoutingEdge::Board {
+                    station_id: _,
+                    seconds: _,
+                } => board_seconds,
+                RoutingEdge::Alight => 0,
+            },
+            |node_ix| {
+                let station_id = self.graph[node_ix].get_station_id() as u32;
+                get_air_travel_time(station_id, end as u32, network, config)
+            },
+        )?;
+        let n_pods = node_ixs
+            .iter()
+            .filter(|node_ix| matches!(self.graph[**node_ix], RoutingNode::Station { .. }))
+            .count() as u32
+            - 1;
+        if n_pods == 0 {
+            return Some(0);
+        }
+        Some(
+            seconds - n_pods * (board_seconds + in_station_seconds)
+                + (n_pods - 1) * transition_time,
+        )
+    }
+
+    fn try_find_ride(
+        &self,
+        from: i32,
+        to: i32,
+        line_name: &LineName,
+    ) -> Option<(NodeIndex, NodeIndex)> {
+        for direction in [Direction::Pos, Direction::Neg] {
+            let from_node = RoutingNode::Platform {
+                station_id: from,
+                line_name: line_name.clone(),
+                direction: direction,
+            };
+            if let Some(from_ix) = self.node_indices.get(&from_node) {
+                for edge in self.graph.edges(*from_ix) {
+                    if let RoutingNode::Platform {
+                        station_id,
+                        line_name: to_line_name,
+                        direction: _,
+                    } = &self.graph[edge.target()]
+                    {
+                        if *station_id == to && to_line_name == line_name {
+                            return Some((*from_ix, edge.target()));
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn has_edge_between(&self, from_ix: NodeIndex, to_ix: NodeIndex) -> bool {
+        self.graph.find_edge(from_ix, to_ix).is_some()
+    }
+
+    // Whether a path planned on an older graph can still be traveled. Every leg needs its
+    // ride and wherever the line changes people need to be able to get off and on again.
+    // People that sit in a pod already do not need to board for the first leg.
+    pub fn is_path_available(&self, path_state: &PathState, in_pod: bool) -> bool {
+        let path = &path_state.path;
+        let legs = &path_state.legs;
+        let mut previous_line_name: Option<&LineName> = None;
+        if in_pod {
+            previous_line_name = legs.front();
+        }
+
+        for (i, line_name) in legs.iter().enumerate() {
+            if i + 1 >= path.len() {
+                return false;
+            }
+            let from = path[i].index() as i32;
+            let to = path[i + 1].index() as i32;
+            let (from_ix, to_ix) = match self.try_find_ride(from, to, line_name) {
+                Some(ride) => ride,
+                None => return false,
+            };
+
+            if previous_line_name != Some(line_name) {
+                match self
+                    .node_indices
+                    .get(&RoutingNode::Station { station_id: from })
+                {
+                    Some(station_ix) if self.has_edge_between(*station_ix, from_ix) => {}
+                    _ => return false,
+                }
+            }
+            if legs.get(i + 1) != Some(line_name) {
+                match self
+                    .node_indices
+                    .get(&RoutingNode::Station { station_id: to })
+                {
+                    Some(station_ix) if self.has_edge_between(to_ix, *station_ix) => {}
+                    _ => return false,
+                }
+            }
+            previous_line_name = Some(line_name);
+        }
+        true
+    }
+}
+
+fn is_platform_passable(
+    stations: &Vec<Station>,
+    station_id: i32,
+    line_name: &LineName,
+    direction: &Direction,
+) -> bool {
+    for station in stations {
+        if station.id == station_id {
+            for platform in &station.platforms {
+                if platform.lines_using_this.contains(line_name) && &platform.direction == direction
+                {
+                    return platform.is_passable();
+                }
+            }
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper::enums::OnBlock;
+    use crate::helper::testing::{get_config, get_line, get_network, get_station};
+
+    // u1 from 1 over 2 to 3 and u2 from 3 over 4 to 5, 100 seconds between two stations
+    fn get_town() -> (Config, Network) {
+        let config = get_config(
+            vec![
+                get_station(1, 0., 0.),
+                get_station(2, 1., 0.),
+                get_station(3, 2., 0.),
+                get_station(4, 2., 1.),
+                get_station(5, 2., 2.),
+            ],
+            vec![
+                get_line("u1", vec![1, 2, 3], OnBlock::Wait),
+                get_line("u2", vec![3, 4, 5], OnBlock::Wait),
+            ],
+        );
+        let network = get_network(&config);
+        (config, network)
+    }
+
+    #[test]
+    fn route_with_transfer() {
+        let (config, network) = get_town();
+        let route = network
+            .routing_graph
+            .try_find_route(1, 5, &network, &config)
+            .unwrap();
+        assert_eq!(route.stations, vec![1, 2, 3, 4, 5]);
+        assert_eq!(
+            route.legs,
+            vec![
+                LineName::U(1),
+                LineName::U(1),
+                LineName::U(2),
+                LineName::U(2)
+            ]
+        );
+    }
+
+    #[test]
+    fn board_costs_transition_and_half_a_headway() {
+        let (_, network) = get_town();
+        let routing_graph = &network.routing_graph;
+        for edge in routing_graph.graph.edge_weights() {
+            if let RoutingEdge::Board {
+                station_id: _,
+                seconds,
+            } = edge
+            {
+                assert_eq!(*seconds, 10 + 30);
+            }
+        }
+    }
+
+    #[test]
+    fn shortest_time_is_what_a_trip_takes_without_waiting() {
+        let (config, network) = get_town();
+        let routing_graph = &network.routing_graph;
+        // Only the ride, the pod is boarded right away and the trip ends on arrival
+        assert_eq!(
+            routing_graph.try_get_shortest_time(1, 2, &network, &config),
+            Some(100)
+        );
+        // Standing in 2 and changing pods in 3
+        assert_eq!(
+            routing_graph.try_get_shortest_time(1, 4, &network, &config),
+            Some(100 + 5 + 100 + 10 + 100)
+        );
+        assert_eq!(
+            routing_graph.try_get_shortest_time(3, 3, &network, &config),
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn crowded_transfer_is_avoided() {
+        // From 1 to 3 with a transfer in 2 or without one but further on u3
+        let config = get_config(
+            vec![
+                get_station(1, 0., 0.),
+                get_station(2, 1., 0.),
+                get_station(3, 2., 0.),
+                get_station(6, 0., 1.),
+                get_station(7, 1., 1.),
+            ],
+            vec![
+                get_line("u1", vec![1, 2], OnBlock::Wait),
+                get_line("u2", vec![2, 3], OnBlock::Wait),
+                get_line("u3", vec![1, 6, 7, 3], OnBlock::Wait),
+            ],
+        );
+        let mut network = get_network(&config);
+        let route = network
+            .routing_graph
+            .try_find_route(1, 3, &network, &config)
+            .unwrap();
+        assert_eq!(route.legs, vec![LineName::U(1), LineName::U(2)]);
+
+        // Every full pod of people waiting in 2 costs another headway to board there
+        let station = network.try_get_station_by_id(2).unwrap();
+        station.people_in_station = (0..100).collect();
+        let route = network
+            .routing_graph
+            .try_find_route(1, 3, &network, &config)
+            .unwrap();
+        assert_eq!(route.stations, vec![1, 6, 7, 3]);
+        assert_eq!(route.legs, vec![LineName::U(3); 3]);
+    }
+
+    #[test]
+    fn path_is_not_available_after_blocking() {
+        let (config, mut network) = get_town();
+        let path_state = PathState::new(1, 5, &network, &config);
+        assert!(network.routing_graph.is_path_available(&path_state, false));
+
+        network.apply_block_connection(&HashSet::from([2, 3]));
+        network.recalculate_graph(&config);
+        assert!(!network.routing_graph.is_path_available(&path_state, false));
+        assert!(network
+            .routing_graph
+            .try_find_route(1, 5, &network, &config)
+            .is_none());
+        // The part before the block can still be traveled
+        assert!(network
+            .routing_graph
+            .try_find_route(1, 2, &network, &config)
+            .is_some());
+    }
+}
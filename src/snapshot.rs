@@ -0,0 +1,37 @@
+use crate::network::Network;
+use crate::person::peoplebox::PeopleBox;
+use crate::pod::podsbox::PodsBox;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use std::fs::{create_dir_all, File};
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
+
+// Everything a run changes while it goes on. The config is not part of it, the run
+// that loads a snapshot keeps its own, so the snapshot can be tried with other
+// settings. Only the town has to be the same.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub town: String,
+    pub seed: Option<u64>,
+    pub time_passed: u32,
+    pub network: Network,
+    pub pods_box: PodsBox,
+    pub people_box: PeopleBox,
+    pub rng: ChaCha8Rng,
+}
+
+pub fn write_snapshot(path: &Path, snapshot: &Snapshot) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        let _res = create_dir_all(parent);
+    }
+    let file = File::create(path).map_err(|e| format!("can't create {}: {}", path.display(), e))?;
+    bincode::serialize_into(BufWriter::new(file), snapshot)
+        .map_err(|e| format!("can't write {}: {}", path.display(), e))
+}
+
+pub fn read_snapshot(path: &Path) -> Result<Snapshot, String> {
+    let file = File::open(path).map_err(|e| format!("can't open {}: {}", path.display(), e))?;
+    bincode::deserialize_from(BufReader::new(file))
+        .map_err(|e| format!("can't read {}: {}", path.display(), e))
+}
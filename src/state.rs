@@ -1,21 +1,28 @@
-use crate::config::constants::{CONFIG_NAME, CONFIG_ROOT};
-use crate::config::structs::Config;
+use crate::config::structs::{Config, ExecutionMode};
 use crate::control::action::{Action, Actions};
 use crate::control::proxy::recv_actions;
-use crate::helper::enums::Direction;
-use crate::helper::functions::calc_graph;
+use crate::control::schedule::ActionSchedule;
+use crate::export::gtfs::write_gtfs;
+use crate::helper::enums::{Direction, LineName};
 use crate::helper::functions::{apply_zoom, format_seconds};
+use crate::helper::functions::{get_random_trip, get_sorted_station_ids};
 use crate::helper::printer::{print_get_person, print_get_pod, print_get_station};
+use crate::metrics::components::station::StationMetrics;
+use crate::metrics::events::{Event, EventKind, EventLog};
+use crate::metrics::timeseries::TimeSeries;
 use crate::network::Network;
 use crate::person::peoplebox::PeopleBox;
 use crate::person::person::Person;
 use crate::pod::podsbox::PodsBox;
+use crate::snapshot::{read_snapshot, write_snapshot, Snapshot};
 use crate::station::platform::Platform;
 use crate::station::station::Station;
 use ggez::event::{EventHandler, KeyCode, KeyMods, MouseButton};
 use ggez::graphics::{self, Color, DrawParam, Font, PxScale, Text};
 use ggez::{timer, Context, GameResult};
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::collections::BTreeSet;
 use std::collections::HashSet;
 use std::fs;
 use std::path::Path;
@@ -29,7 +36,13 @@ pub struct State {
     pub people_box: PeopleBox,
     pub time_passed: u32,
     pub config: Config,
+    pub rng: ChaCha8Rng,
+    action_schedule: ActionSchedule,
     rx: mpsc::Receiver<Actions>,
+    // Set by a kill without a window, the headless driver returns it
+    exit_code: Option<i32>,
+    // Set while the events are logged, see log events
+    event_log: Option<EventLog>,
 }
 
 impl State {
@@ -42,13 +55,57 @@ impl State {
             &mut self.network,
             &self.config,
             self.time_passed,
+            &mut self.rng,
         );
+        // After everyone moved, so the stations count the people that are left in them
+        self.network
+            .do_gather_metrics(&self.config, self.time_passed);
+    }
+
+    // One simulated second, shared by the ggez event loop and the headless driver.
+    // Received actions are scheduled relative to the current time_passed and only
+    // handed over once their sim sleeps are over.
+    fn tick(&mut self) {
+        for actions in recv_actions(&self.rx) {
+            self.action_schedule.schedule(actions, self.time_passed);
+        }
+        let actions = self.action_schedule.pop_due(self.time_passed);
+        self.handle_actions(actions);
+        if self.exit_code.is_some() {
+            return;
+        }
+
+        if !self.config.logic.on_pause {
+            self.time_passed += 1;
+            self.update();
+            self.write_events();
+        }
+    }
+
+    // Runs the simulation without a window as fast as possible. Scripts only sleep in
+    // simulated time, so the result does not depend on how fast the machine is.
+    // The first message is the startup command, waiting for it makes sure it is
+    // scheduled at time 0. The loop only ends through a kill action, its code is returned.
+    pub fn run_headless(&mut self) -> i32 {
+        match self.rx.recv() {
+            Ok(actions) => self.action_schedule.schedule(actions, self.time_passed),
+            Err(_) => {
+                println!("Nothing to run was received.");
+                return 1;
+            }
+        }
+        loop {
+            self.tick();
+            if let Some(code) = self.exit_code {
+                return code;
+            }
+        }
     }
 
     fn handle_actions(&mut self, action: Actions) {
         let mut recalculate_graph = false;
         for action in action.actions {
-            if action != Action::NoAction {
+            if action != Action::NoAction && !self.config.general.quiet {
                 println!("{:?}", action);
             }
             match action {
@@ -62,11 +119,13 @@ impl State {
                 Action::BlockConnection { ids } => {
                     self.network.apply_block_connection(&ids);
                     self.pods_box.apply_block_connection(&ids);
+                    self.log_connection_event(EventKind::Block, &ids);
                     recalculate_graph = true;
                 }
                 Action::UnblockConnection { ids } => {
                     self.network.apply_unblock_connection(&ids);
                     self.pods_box.apply_unblock_connection(&ids);
+                    self.log_connection_event(EventKind::Unblock, &ids);
                     recalculate_graph = true;
                 }
                 Action::MakePlatformOperational {
@@ -74,6 +133,12 @@ impl State {
                     line_name,
                     direction,
                 } => {
+                    self.log_platform_event(
+                        EventKind::PlatformOperational,
+                        station_id,
+                        &line_name,
+                        &direction,
+                    );
                     self.network
                         .apply_make_platform_op(station_id, line_name, direction);
                     recalculate_graph = true;
@@ -83,6 +148,12 @@ impl State {
                     line_name,
                     direction,
                 } => {
+                    self.log_platform_event(
+                        EventKind::PlatformPassable,
+                        station_id,
+                        &line_name,
+                        &direction,
+                    );
                     self.network
                         .apply_make_platform_pass(station_id, line_name, direction);
                     recalculate_graph = true;
@@ -92,6 +163,12 @@ impl State {
                     line_name,
                     direction,
                 } => {
+                    self.log_platform_event(
+                        EventKind::PlatformQueueable,
+                        station_id,
+                        &line_name,
+                        &direction,
+                    );
                     self.network
                         .apply_make_platform_qu(station_id, line_name, direction);
                     recalculate_graph = true;
@@ -125,11 +202,19 @@ impl State {
                     random_station: _,
                 } => self.people_box.apply_route_person(id, action),
                 Action::GatherMetrics => {
-                    self.people_box.start_gather_metrics();
-                    self.pods_box.start_gather_metrics();
+                    self.people_box.start_gather_metrics(self.time_passed);
+                    self.pods_box.start_gather_metrics(self.time_passed);
+                    self.network.start_gather_metrics();
                 }
                 Action::KillSimulation { code } => {
-                    exit(code);
+                    self.stop_log_events();
+                    match self.config.general.mode {
+                        ExecutionMode::Visual => exit(code),
+                        _ => {
+                            self.exit_code = Some(code);
+                            return;
+                        }
+                    }
                 }
                 Action::DumpMetricsPerson { person_id } => {
                     self.people_box.dump_metrics(person_id, &self.config);
@@ -153,23 +238,60 @@ impl State {
                 Action::DumpMetricsPod { pod_id } => {
                     self.pods_box.dump_metrics(pod_id, &self.config);
                 }
+                Action::DumpMetricsStations { all, avg } => {
+                    if avg {
+                        self.network.dump_avg_metrics(&self.config);
+                    }
+                    if all {
+                        self.network.dump_all_metrics(&self.config)
+                    }
+                }
+                Action::DumpMetricsStation { station_id } => {
+                    self.network.dump_metrics(station_id, &self.config);
+                }
+                Action::DumpTrips => {
+                    self.people_box.dump_trips(&self.config);
+                }
+                Action::DumpLinks => {
+                    self.pods_box.dump_link_loads(&self.config);
+                }
                 Action::DumpConfig => {
                     self.dump_config();
                 }
+                Action::DumpGtfs => {
+                    self.dump_gtfs();
+                }
+                Action::SaveState { path } => {
+                    self.save_state(&path);
+                }
+                Action::LoadState { path } => {
+                    self.load_state(&path);
+                }
+                Action::LogEvents { csv } => {
+                    self.start_log_events(csv);
+                }
+                Action::StopLogEvents => {
+                    self.stop_log_events();
+                }
                 Action::Sleep { duration: _ }
+                | Action::SleepSim { seconds: _ }
                 | Action::Loop { n: _ }
                 | Action::Endloop
                 | Action::StartConcurency
                 | Action::DoConcurrently
                 | Action::EndConcurency => {
-                    // Do Nothing, these are handled in the action proxy
-                    // Also this should never be reached since the proxy thread
-                    // is not supposed to send these actions forward.
+                    // Do Nothing, these are handled in the action proxy and the action schedule
+                    // Also this should never be reached since neither of them
+                    // is supposed to send these actions forward.
                 }
             }
         }
         if recalculate_graph {
-            self.network.graph = calc_graph(&self.network.lines);
+            self.network.recalculate_graph(&self.config);
+            let n_affected = self.people_box.apply_graph_change(&self.network);
+            if !self.config.general.quiet {
+                println!("{} people need a new path", n_affected);
+            }
         }
     }
 
@@ -272,57 +394,29 @@ impl State {
 
     // TODO:PRIO: implement spwaning of pods at a given rate till there are enough
     // as a next step spawn / divert pods dynamically
-    pub fn new(config: Config, rx: mpsc::Receiver<Actions>) -> Self {
-        let mut stations: Vec<Station> = vec![];
-        for abstract_station in config.network.coordinates_map_stations.iter() {
-            let station_id = abstract_station.0;
-            let (name, entrypoint_for, city, (lat, lon)) = abstract_station.1;
-
-            // println!("{:?}", config.network.edge_map.get(&station_id).unwrap());
-            let abstract_platforms = config.network.station_platforms.get(station_id).unwrap();
-            let mut platforms = vec![];
-
-            for abstract_platform in abstract_platforms {
-                platforms.push(Platform::new(
-                    &config,
-                    *station_id,
-                    entrypoint_for,
-                    Direction::Pos,
-                    &abstract_platform.0,
-                    &abstract_platform.1,
-                ));
-                platforms.push(Platform::new(
-                    &config,
-                    *station_id,
-                    entrypoint_for,
-                    Direction::Neg,
-                    &abstract_platform.0,
-                    &abstract_platform.1,
-                ));
-            }
-
-            stations.push(Station {
-                id: *station_id,
-                visualize: false,
-                name: name.clone(),
-                city: city.clone(),
-                edges_to: config.network.edge_map.get(&station_id).unwrap().clone(),
-                // pods_in_station: HashSet::from([]), // The pods will register themselves later
-                people_in_station: HashSet::from([]),
-                coordinates: (*lat as f32, *lon as f32),
-                platforms: platforms,
-            })
+    pub fn new(mut config: Config, rx: mpsc::Receiver<Actions>) -> Self {
+        // Everything random in a run is drawn from this one generator, so a seed
+        // reproduces a run. Without a configured seed one is picked and reported.
+        let seed = match config.logic.seed {
+            Some(seed) => seed,
+            None => rand::thread_rng().gen(),
+        };
+        config.logic.seed = Some(seed);
+        if !config.general.quiet {
+            print_run_setup(&config);
         }
 
+        let stations = build_stations(&config);
+
         // println!("{:?}", stations);
 
         let network = Network::new(stations, &config);
 
         // println!("{:?}", network.lines);
 
-        let people_box = PeopleBox { people: vec![] };
+        let people_box = PeopleBox::new(vec![]);
 
-        let pods_box = PodsBox { pods: vec![] };
+        let pods_box = PodsBox::new();
 
         let state = State {
             network: network,
@@ -330,56 +424,194 @@ impl State {
             pods_box: pods_box,
             time_passed: 0,
             config: config,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            action_schedule: ActionSchedule::new(),
             rx: rx,
+            exit_code: None,
+            event_log: None,
         };
 
         return state;
     }
 
     pub fn dump_config(&self) {
-        if let Some(timestamp_run) = self.config.timestamp_run {
-            let timestamp = timestamp_run
-                .naive_utc()
-                .format("%Y.%m.%d %H:%M")
-                .to_string()
-                .replace(" ", "_");
-            println!("timestamp_run: {:?}", timestamp);
-
-            let source_path_str = format!("{}/{}", CONFIG_ROOT, CONFIG_NAME);
-            let dest_path_str = format!(
-                "{}/{}/{}/{}.yaml",
-                "metrics", self.config.environment, timestamp, "config"
-            );
-            let path = Path::new(&dest_path_str);
+        if let Some(output_dir) = self.config.get_output_dir() {
+            // The effective config with the town, config.yaml and the overrides merged
+            let path_str = format!("{}/{}.yaml", output_dir, "config");
+            let path = Path::new(&path_str);
+            let parent = path.parent().unwrap();
+            let _res = fs::create_dir_all(parent);
+            match serde_yaml::to_string(&self.config.to_config_file()) {
+                Ok(txt) => match fs::write(path, txt) {
+                    Ok(_) if self.config.general.quiet => {}
+                    Ok(_) => println!("written file"),
+                    Err(e) => println!("error writing config: {}", e),
+                },
+                Err(e) => println!("error serializing config: {}", e),
+            }
+        }
+    }
+
+    pub fn dump_gtfs(&self) {
+        if let Some(output_dir) = self.config.get_output_dir() {
+            let path_str = format!("{}/{}.zip", output_dir, "gtfs");
+            let path = Path::new(&path_str);
             let parent = path.parent().unwrap();
             let _res = fs::create_dir_all(parent);
-            let _res = fs::copy(source_path_str, dest_path_str);
+            match write_gtfs(path, &self.network, &self.pods_box, &self.config) {
+                Ok(_) if self.config.general.quiet => {}
+                Ok(_) => println!("written file"),
+                Err(e) => println!("error writing gtfs: {}", e),
+            }
+        }
+    }
+
+    pub fn save_state(&self, path_str: &str) {
+        let snapshot = Snapshot {
+            town: self.config.general.town.clone(),
+            seed: self.config.logic.seed,
+            time_passed: self.time_passed,
+            network: self.network.clone(),
+            pods_box: self.pods_box.clone(),
+            people_box: self.people_box.clone(),
+            rng: self.rng.clone(),
+        };
+        match write_snapshot(Path::new(path_str), &snapshot) {
+            Ok(_) if self.config.general.quiet => {}
+            Ok(_) => println!("written file"),
+            Err(e) => println!("error saving state: {}", e),
+        }
+    }
+
+    // Continues from the snapshot as if this run had been the one that saved it. What
+    // the scripts still have to do stays scheduled, their sim sleeps are moved along.
+    pub fn load_state(&mut self, path_str: &str) {
+        let snapshot = match read_snapshot(Path::new(path_str)) {
+            Ok(snapshot) => snapshot,
+            Err(e) => {
+                println!("error loading state: {}", e);
+                return;
+            }
+        };
+        if snapshot.town != self.config.general.town {
+            println!(
+                "error loading state: it was saved in {}, this run is in {}",
+                snapshot.town, self.config.general.town
+            );
+            return;
+        }
+        self.action_schedule
+            .shift_sim(self.time_passed, snapshot.time_passed);
+        self.time_passed = snapshot.time_passed;
+        self.network = snapshot.network;
+        self.pods_box = snapshot.pods_box;
+        self.people_box = snapshot.people_box;
+        self.rng = snapshot.rng;
+        self.config.logic.seed = snapshot.seed;
+        // The snapshot doesn't know whether this run logs events
+        let log_events = self.event_log.is_some();
+        self.pods_box.set_log_events(log_events);
+        self.people_box.set_log_events(log_events);
+        if !self.config.general.quiet {
+            println!("loaded state at {}", format_seconds(self.time_passed));
+        }
+    }
+
+    // A log that is already open is closed, the new one starts from scratch
+    pub fn start_log_events(&mut self, csv: bool) {
+        self.stop_log_events();
+        if let Some(output_dir) = self.config.get_output_dir() {
+            match EventLog::create(&output_dir, csv) {
+                Ok(event_log) => {
+                    if !self.config.general.quiet {
+                        println!("logging events to {}", event_log.path);
+                    }
+                    self.event_log = Some(event_log);
+                    self.pods_box.set_log_events(true);
+                    self.people_box.set_log_events(true);
+                }
+                Err(e) => println!("error logging events: {}", e),
+            }
+        }
+    }
+
+    pub fn stop_log_events(&mut self) {
+        if let Some(mut event_log) = self.event_log.take() {
+            if let Err(e) = event_log.flush() {
+                println!("error logging events: {}", e);
+            }
+        }
+        self.pods_box.set_log_events(false);
+        self.people_box.set_log_events(false);
+    }
+
+    // Takes the events of this update from the pods and people, pods first
+    fn write_events(&mut self) {
+        if self.event_log.is_none() {
+            return;
+        }
+        let mut events = self.pods_box.take_events();
+        events.append(&mut self.people_box.take_events());
+        for event in &events {
+            self.write_event(event);
         }
     }
 
+    // A log that can't be written to is closed, instead of failing every second
+    fn write_event(&mut self, event: &Event) {
+        if let Some(event_log) = &mut self.event_log {
+            if let Err(e) = event_log.write(event) {
+                println!("error logging events: {}", e);
+                self.stop_log_events();
+            }
+        }
+    }
+
+    fn log_connection_event(&mut self, event: EventKind, ids: &HashSet<i32>) {
+        let mut ids: Vec<i32> = ids.iter().copied().collect();
+        ids.sort();
+        let mut event = Event::new(self.time_passed, event);
+        event.station_id = ids.first().copied();
+        event.to_station_id = ids.last().copied();
+        self.write_event(&event);
+    }
+
+    fn log_platform_event(
+        &mut self,
+        event: EventKind,
+        station_id: i32,
+        line_name: &LineName,
+        direction: &Direction,
+    ) {
+        let mut event = Event::new(self.time_passed, event);
+        event.station_id = Some(station_id);
+        event.line_name = Some(line_name.clone());
+        event.direction = Some(direction.clone());
+        self.write_event(&event);
+    }
+
     pub fn add_people(mut self) -> Self {
-        let mut rng = rand::thread_rng();
-        let station_ids: Vec<&i32> = self
-            .config
-            .network
-            .coordinates_map_stations
-            .keys()
-            .collect();
-        // println!("{:?}", station_ids);
+        // With hourly_trips people enter over the day, see PeopleBox::schedule_trips
+        if let Some(demand) = &self.config.demand {
+            if demand.hourly_trips.is_some() {
+                if !self.config.general.quiet {
+                    println!("demand: scheduled trips, number_of_people is not used");
+                }
+                return self;
+            }
+        }
 
         let mut people: Vec<Person> = vec![];
         for person_id in 0..self.config.logic.number_of_people {
-            let start_ix = rng.gen_range(0..station_ids.len());
-            let end_ix = rng.gen_range(0..station_ids.len());
-            let start = station_ids[start_ix];
-            let end = station_ids[end_ix];
+            let (start, end) = get_random_trip(&self.config, self.time_passed, &mut self.rng);
             people.push(Person::new(
                 person_id,
                 self.config.logic.transition_time,
                 &self.network,
-                *start,
-                *end,
+                start as i32,
+                end as i32,
                 &self.config,
+                self.time_passed,
             ));
         }
 
@@ -393,7 +625,7 @@ impl State {
             station.register_person(person.id);
         }
 
-        let people_box = PeopleBox { people: people };
+        let people_box = PeopleBox::new(people);
 
         self.people_box = people_box;
 
@@ -433,13 +665,7 @@ impl EventHandler for State {
         // Update code here...
         while timer::check_update_time(ctx, self.config.logic.speed_multiplier) {
             // println!("fps: {}", timer::fps(ctx));
-            let actions = recv_actions(&self.rx);
-            self.handle_actions(actions);
-
-            if !self.config.logic.on_pause {
-                self.time_passed += 1;
-                self.update();
-            }
+            self.tick();
         }
         Ok(())
     }
@@ -454,3 +680,265 @@ impl EventHandler for State {
         graphics::present(ctx)
     }
 }
+
+// The stations of the town with a platform per direction for each of their abstract platforms
+pub fn build_stations(config: &Config) -> Vec<Station> {
+    let mut stations: Vec<Station> = vec![];
+    for station_id in &get_sorted_station_ids(config) {
+        let (name, entrypoint_for, city, (lat, lon)) = config
+            .network
+            .coordinates_map_stations
+            .get(station_id)
+            .unwrap();
+
+        // println!("{:?}", config.network.edge_map.get(&station_id).unwrap());
+        let abstract_platforms = config.network.station_platforms.get(station_id).unwrap();
+        let mut platforms = vec![];
+
+        for abstract_platform in abstract_platforms {
+            platforms.push(Platform::new(
+                config,
+                *station_id,
+                entrypoint_for,
+                Direction::Pos,
+                &abstract_platform.0,
+                &abstract_platform.1,
+            ));
+            platforms.push(Platform::new(
+                config,
+                *station_id,
+                entrypoint_for,
+                Direction::Neg,
+                &abstract_platform.0,
+                &abstract_platform.1,
+            ));
+        }
+
+        stations.push(Station {
+            id: *station_id,
+            visualize: false,
+            name: name.clone(),
+            city: city.clone(),
+            edges_to: config.network.edge_map.get(&station_id).unwrap().clone(),
+            // pods_in_station: HashSet::from([]), // The pods will register themselves later
+            people_in_station: BTreeSet::new(),
+            coordinates: (*lat as f32, *lon as f32),
+            platforms: platforms,
+            gather_metrics: false,
+            metrics: StationMetrics::new(),
+            time_series: TimeSeries::new(),
+        })
+    }
+    stations
+}
+
+// What a run is set up with, so it can be told apart from others in the output
+fn print_run_setup(config: &Config) {
+    if let Some(seed) = config.logic.seed {
+        println!("seed: {}", seed);
+    }
+    match &config.demand {
+        Some(demand) => {
+            println!("demand: {} od pairs", demand.od.trips.len());
+            for profile in &demand.profiles {
+                println!(
+                    "demand profile {}: {} - {}, {} od pairs",
+                    profile.name,
+                    format_seconds(profile.from),
+                    format_seconds(profile.to),
+                    profile.od.trips.len()
+                );
+            }
+        }
+        None => println!("demand: uniform random trips"),
+    }
+    if let Some(timetable) = &config.timetable {
+        println!(
+            "timetable: {} trips on {} lines",
+            timetable.get_number_of_trips(),
+            timetable.lines.len()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper::enums::OnBlock;
+    use crate::helper::testing::{get_config, get_line, get_spawn_pods, get_state, get_station};
+    use crate::person::personstate::PersonState;
+
+    // u1 and u2 meet in 3, u3 goes around them from 1 to 5
+    fn get_town_config() -> Config {
+        let mut config = get_config(
+            vec![
+                get_station(1, 0., 0.),
+                get_station(2, 1., 0.),
+                get_station(3, 2., 0.),
+                get_station(4, 2., 1.),
+                get_station(5, 2., 2.),
+                get_station(6, 0., 1.),
+                get_station(7, 0., 2.),
+            ],
+            vec![
+                get_line("u1", vec![1, 2, 3], OnBlock::Wait),
+                get_line("u2", vec![3, 4, 5], OnBlock::Wait),
+                get_line("u3", vec![1, 6, 7, 5], OnBlock::Wait),
+            ],
+        );
+        config.logic.number_of_people = 40;
+        config
+    }
+
+    fn get_running_state(config: &Config) -> State {
+        let mut state = get_state(config.clone());
+        state.handle_actions(get_spawn_pods(config));
+        state.handle_actions(Actions {
+            actions: vec![Action::GatherMetrics],
+        });
+        state
+    }
+
+    fn run(state: &mut State, seconds: u32) {
+        for _ in 0..seconds {
+            state.tick();
+        }
+    }
+
+    #[test]
+    fn trips_never_beat_the_shortest_time() {
+        let mut state = get_running_state(&get_town_config());
+        run(&mut state, 3 * 3600);
+        let trips = state.people_box.get_trips();
+        assert!(trips.len() > 40, "only {} trips", trips.len());
+        for trip in trips {
+            let detour_ratio = trip.get_detour_ratio();
+            assert!(detour_ratio.unwrap_or(1.) >= 1., "{:?}", trip);
+        }
+    }
+
+    // What a run did, to tell runs apart
+    fn get_outcome(state: &State) -> String {
+        format!(
+            "{:?} {:?} {:?}",
+            state.people_box.get_trips(),
+            state.people_box.get_avg_time_series(),
+            state.pods_box.get_avg_time_series()
+        )
+    }
+
+    #[test]
+    fn same_seed_same_run() {
+        let config = get_town_config();
+        let mut state = get_running_state(&config);
+        let mut state_again = get_running_state(&config);
+        run(&mut state, 3600);
+        run(&mut state_again, 3600);
+        assert_eq!(get_outcome(&state), get_outcome(&state_again));
+
+        let mut other_config = config.clone();
+        other_config.logic.seed = Some(8);
+        let mut other_state = get_running_state(&other_config);
+        run(&mut other_state, 3600);
+        assert_ne!(get_outcome(&state), get_outcome(&other_state));
+    }
+
+    #[test]
+    fn restored_run_continues_like_the_original() {
+        let config = get_town_config();
+        let path = std::env::temp_dir().join(format!("publicflow_{}.state", std::process::id()));
+        let path_str = path.to_str().unwrap();
+
+        let mut state = get_running_state(&config);
+        run(&mut state, 900);
+        state.save_state(path_str);
+        run(&mut state, 900);
+
+        let mut restored_state = get_state(config.clone());
+        restored_state.load_state(path_str);
+        let _res = fs::remove_file(&path);
+        assert_eq!(restored_state.time_passed, 900);
+        run(&mut restored_state, 900);
+        assert_eq!(get_outcome(&state), get_outcome(&restored_state));
+    }
+
+    // One scheduled person from start to finish that spawns in the next second, the
+    // town has no other people
+    fn add_scheduled_person(state: &mut State, start: i32, finish: i32) -> i32 {
+        let id = 1000 + state.people_box.people.len() as i32;
+        let mut person = Person::new_scheduled(
+            id,
+            state.config.logic.transition_time,
+            start,
+            finish,
+            state.time_passed + 1,
+        );
+        person.gather_metrics = true;
+        state.people_box.people.push(person);
+        id
+    }
+
+    fn run_until_riding(state: &mut State, id: i32) {
+        for _ in 0..600 {
+            state.tick();
+            let person = state.people_box.people.iter().find(|p| p.id == id);
+            if matches!(person.unwrap().state, PersonState::RidingPod { .. }) {
+                return;
+            }
+        }
+        panic!("person {} never got into a pod", id);
+    }
+
+    fn block(state: &mut State, connections: &[[i32; 2]]) {
+        let actions = connections
+            .iter()
+            .map(|ids| Action::BlockConnection {
+                ids: HashSet::from(*ids),
+            })
+            .collect();
+        state.handle_actions(Actions { actions: actions });
+    }
+
+    // Where the trips of a person ended and whether they were aborted
+    fn get_trip_ends(state: &State, id: i32) -> Vec<(i32, bool)> {
+        state
+            .people_box
+            .get_trips()
+            .iter()
+            .filter(|trip| trip.person_id == id)
+            .map(|trip| (trip.destination, trip.aborted))
+            .collect()
+    }
+
+    #[test]
+    fn rerouted_after_block_connection() {
+        let mut config = get_town_config();
+        config.logic.number_of_people = 0;
+        // Pods of u1 come back from 2, so the way around over u3 and u2 can be taken
+        config.network.lines[0].on_block = OnBlock::TurnBack;
+        let mut state = get_running_state(&config);
+        let id = add_scheduled_person(&mut state, 1, 3);
+        run_until_riding(&mut state, id);
+        block(&mut state, &[[2, 3]]);
+        run(&mut state, 2 * 3600);
+        assert_eq!(get_trip_ends(&state, id), vec![(3, false)]);
+    }
+
+    #[test]
+    fn stranded_after_block_connection() {
+        let mut config = get_town_config();
+        config.logic.number_of_people = 0;
+        let mut state = get_running_state(&config);
+        // One in a pod, the other still in the station, 3 can't be reached anymore
+        let riding_id = add_scheduled_person(&mut state, 1, 3);
+        run_until_riding(&mut state, riding_id);
+        // Out of the station, on the way to 2
+        run(&mut state, 20);
+        let waiting_id = add_scheduled_person(&mut state, 1, 3);
+        run(&mut state, 2);
+        block(&mut state, &[[2, 3], [3, 4]]);
+        run(&mut state, 3600);
+        assert_eq!(get_trip_ends(&state, riding_id), vec![(2, true)]);
+        assert_eq!(get_trip_ends(&state, waiting_id), vec![(1, true)]);
+    }
+}
@@ -1,11 +1,14 @@
 use crate::config::structs::Config;
 use crate::helper::enums::{Direction, LineName};
 use crate::helper::functions::parse_str_to_line_and_directions;
+use crate::metrics::components::platform::PlatformMetrics;
 use crate::station::platformstate::PlatformState;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
 use std::collections::HashSet;
 use std::collections::VecDeque;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Platform {
     pub station_id: i32,
     pub direction: Direction,
@@ -14,8 +17,11 @@ pub struct Platform {
     pub seconds_between_pods: i32,
     pub edges_to: HashSet<i32>,
     pub lines_using_this: HashSet<LineName>,
-    pub pods_at_platform: HashSet<i32>,
+    // In the same order after a snapshot is loaded
+    pub pods_at_platform: BTreeSet<i32>,
     pub state: PlatformState,
+    pub gather_metrics: bool,
+    pub metrics: PlatformMetrics,
 }
 
 impl Platform {
@@ -44,10 +50,12 @@ impl Platform {
             seconds_between_pods: 3600 / config.logic.station_pods_per_hour,
             edges_to: edges_to.clone(),
             lines_using_this: lines_using_this.clone(),
-            pods_at_platform: HashSet::new(),
+            pods_at_platform: BTreeSet::new(),
             state: PlatformState::Operational {
                 queue: VecDeque::from([]),
             },
+            gather_metrics: false,
+            metrics: PlatformMetrics::new(),
         }
     }
 
@@ -83,7 +91,7 @@ impl Platform {
 
     pub fn is_passable(&self) -> bool {
         match self.state {
-            PlatformState::_Passable => true,
+            PlatformState::Passable => true,
             _ => false,
         }
     }
@@ -95,6 +103,9 @@ impl Platform {
                 if let Some(pod_id) = queue.pop_front() {
                     self.pods_at_platform.insert(pod_id);
                     self.since_last_pod = 0;
+                    if self.gather_metrics {
+                        self.metrics.increase_pods_served();
+                    }
                 }
                 self.state = PlatformState::Operational { queue: queue }
             }
@@ -102,7 +113,7 @@ impl Platform {
         }
     }
 
-    pub fn _try_get_queue(&self) -> Option<&VecDeque<i32>> {
+    pub fn try_get_queue(&self) -> Option<&VecDeque<i32>> {
         match &self.state {
             PlatformState::Queueable { queue } => {
                 return Some(&queue);
@@ -114,6 +125,49 @@ impl Platform {
         }
     }
 
+    // A passable platform has no queue
+    pub fn get_queue_length(&self) -> usize {
+        match self.try_get_queue() {
+            Some(queue) => queue.len(),
+            None => 0,
+        }
+    }
+
+    pub fn do_gather_metrics(&mut self) {
+        self.metrics.add_second(
+            self.pods_at_platform.len() as f32,
+            self.get_queue_length() as f32,
+        );
+    }
+
+    // A row under PLATFORM_HEADER, the load is the share of station_pods_per_hour that was used
+    pub fn format_metrics(&self, config: &Config) -> String {
+        let mut lines: Vec<String> = self
+            .lines_using_this
+            .iter()
+            .map(|line_name| line_name.to_config_string())
+            .collect();
+        lines.sort();
+        let direction = match self.direction {
+            Direction::Pos => "+",
+            Direction::Neg => "-",
+        };
+        let pods_per_hour = self.metrics.get_pods_per_hour();
+        format!(
+            "{},{},{},{},{},{},{},{},{},{}",
+            self.station_id,
+            lines.join(" "),
+            direction,
+            self.metrics.pods_served,
+            pods_per_hour,
+            config.logic.station_pods_per_hour,
+            pods_per_hour / config.logic.station_pods_per_hour as f32,
+            self.metrics.get_avg_dwell(),
+            self.metrics.get_avg_queue_length(),
+            self.metrics.max_queue_length
+        )
+    }
+
     pub fn register_pod(&mut self, pod_id: i32) -> bool {
         match &self.state {
             PlatformState::Queueable { queue } => {
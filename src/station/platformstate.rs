@@ -1,17 +1,18 @@
+use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum PlatformState {
     Operational { queue: VecDeque<i32> },
     Queueable { queue: VecDeque<i32> },
-    _Passable,
+    Passable,
 }
 
 impl PlatformState {
     pub fn get_queue(&self) -> &VecDeque<i32> {
         match self {
             PlatformState::Operational { queue } | PlatformState::Queueable { queue } => queue,
-            PlatformState::_Passable => {
+            PlatformState::Passable => {
                 panic!("PlatformState::Passable doesn't have a queue")
             }
         }
@@ -22,7 +23,7 @@ impl PlatformState {
             PlatformState::Queueable { queue } => PlatformState::Operational {
                 queue: queue.clone(),
             },
-            PlatformState::_Passable => PlatformState::Operational {
+            PlatformState::Passable => PlatformState::Operational {
                 queue: VecDeque::from([]),
             },
             PlatformState::Operational { queue: _ } => {
@@ -37,7 +38,7 @@ impl PlatformState {
             PlatformState::Operational { queue } => PlatformState::Queueable {
                 queue: queue.clone(),
             },
-            PlatformState::_Passable => PlatformState::Queueable {
+            PlatformState::Passable => PlatformState::Queueable {
                 queue: VecDeque::from([]),
             },
             PlatformState::Queueable { queue: _ } => {
@@ -47,11 +48,17 @@ impl PlatformState {
         }
     }
 
+    // Pods drive through a passable platform without stopping, the queue is dropped,
+    // the pods that waited in it notice that in Pod::check_if_in_station and pass as well.
     pub fn make_passable(&self) -> PlatformState {
-        println!(
-            "Passable is not implemented for Platform, stay in state: {:?}",
-            self
-        );
-        self.clone()
+        match self {
+            PlatformState::Operational { queue: _ } | PlatformState::Queueable { queue: _ } => {
+                PlatformState::Passable
+            }
+            PlatformState::Passable => {
+                println!("Is Passable already.");
+                self.clone()
+            }
+        }
     }
 }
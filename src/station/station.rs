@@ -1,14 +1,17 @@
 use crate::config::structs::Config;
 use crate::helper::enums::{Direction, LineName};
-use crate::helper::functions::get_screen_coordinates;
+use crate::helper::functions::{get_screen_coordinates, is_metrics_sample};
 use crate::line::line::Line;
+use crate::metrics::components::station::StationMetrics;
+use crate::metrics::timeseries::TimeSeries;
 use crate::pod::podsbox::PodsBox;
 use crate::station::platform::Platform;
 use ggez::graphics::{Font, Text};
 use ggez::{graphics, Context, GameResult};
-use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashSet};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Station {
     pub id: i32,
     pub visualize: bool,
@@ -16,9 +19,13 @@ pub struct Station {
     pub city: String,
     pub edges_to: HashSet<i32>,
     // pub pods_in_station: HashSet<i32>,
-    pub people_in_station: HashSet<i32>,
+    // Keeps its order through save state and load state
+    pub people_in_station: BTreeSet<i32>,
     pub coordinates: (f32, f32),
     pub platforms: Vec<Platform>,
+    pub gather_metrics: bool,
+    pub metrics: StationMetrics,
+    pub time_series: TimeSeries<StationMetrics>,
 }
 
 impl Station {
@@ -28,6 +35,51 @@ impl Station {
         }
     }
 
+    pub fn start_gather_metrics(&mut self) {
+        self.gather_metrics = true;
+        for platform in &mut self.platforms {
+            platform.gather_metrics = true;
+        }
+    }
+
+    // Like for people and pods the sums of all stations get the metrics every second,
+    // the own time series only every metrics_interval seconds
+    pub fn do_gather_metrics(
+        &mut self,
+        config: &Config,
+        time_passed: u32,
+        sums: &mut TimeSeries<StationMetrics>,
+    ) {
+        let mut queue_length = 0;
+        let mut pods_served = 0.;
+        for platform in &mut self.platforms {
+            platform.do_gather_metrics();
+            queue_length += platform.get_queue_length();
+            pods_served += platform.metrics.pods_served;
+        }
+        self.metrics
+            .set_people_in_station(self.people_in_station.len() as f32);
+        self.metrics.set_queue_length(queue_length as f32);
+        self.metrics.set_pods_served(pods_served);
+        sums.add_to_timestamp(time_passed, &self.metrics);
+        if is_metrics_sample(config, time_passed) {
+            self.time_series
+                .add_timestamp(time_passed, self.metrics.clone());
+        }
+    }
+
+    pub fn record_boarding(&mut self) {
+        if self.gather_metrics {
+            self.metrics.increase_boardings();
+        }
+    }
+
+    pub fn record_alighting(&mut self) {
+        if self.gather_metrics {
+            self.metrics.increase_alightings();
+        }
+    }
+
     pub fn spawn_pod(
         &mut self,
         line_name: &LineName,
@@ -0,0 +1,359 @@
+use crate::config::processing::{parse_config, set_key};
+use crate::config::structs::SweepConfig;
+use crate::control::queue_command_on_start;
+use crate::metrics::summary::{RunSummary, SUMMARY_HEADER};
+use crate::state::State;
+use chrono::{DateTime, Utc};
+use csv::Writer;
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+use serde_yaml::Value;
+use std::fs::create_dir_all;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+// The values one run of the sweep lays over the config, the seed is kept apart so
+// every combination can be run with each seed.
+#[derive(Debug, Clone)]
+struct SweepRun {
+    values: Vec<(String, Value)>,
+    seed: Option<u64>,
+}
+
+// Every entry of runs with every combination of the grid and every seed, in that order
+fn get_sweep_runs(sweep_config: &SweepConfig) -> Result<Vec<SweepRun>, String> {
+    let mut combinations: Vec<Vec<(String, Value)>> = vec![];
+    if sweep_config.runs.is_empty() {
+        combinations.push(vec![]);
+    }
+    for run in &sweep_config.runs {
+        combinations.push(
+            run.iter()
+                .map(|(key_path, value)| (format_value(key_path), value.clone()))
+                .collect(),
+        );
+    }
+
+    for (key_path, values) in &sweep_config.grid {
+        let values = match values {
+            Value::Sequence(values) => values,
+            _ => {
+                return Err(format!(
+                    "sweep.grid.{} needs a list of values",
+                    format_value(key_path)
+                ))
+            }
+        };
+        let mut extended = vec![];
+        for combination in &combinations {
+            for value in values {
+                let mut combination = combination.clone();
+                combination.push((format_value(key_path), value.clone()));
+                extended.push(combination);
+            }
+        }
+        combinations = extended;
+    }
+
+    let mut seeds: Vec<Option<u64>> = sweep_config.seeds.iter().map(|seed| Some(*seed)).collect();
+    if seeds.is_empty() {
+        seeds.push(None);
+    }
+    let mut sweep_runs = vec![];
+    for combination in &combinations {
+        for seed in &seeds {
+            sweep_runs.push(SweepRun {
+                values: combination.clone(),
+                seed: *seed,
+            });
+        }
+    }
+    Ok(sweep_runs)
+}
+
+// The keys in the order they first show up, these are the columns of the summary
+fn get_swept_keys(sweep_runs: &Vec<SweepRun>) -> Vec<String> {
+    let mut keys: Vec<String> = vec![];
+    for sweep_run in sweep_runs {
+        for (key_path, _) in &sweep_run.values {
+            if !keys.contains(key_path) {
+                keys.push(key_path.clone());
+            }
+        }
+    }
+    keys
+}
+
+fn format_value(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Number(number) => number.to_string(),
+        Value::Bool(boolean) => boolean.to_string(),
+        Value::Null => String::new(),
+        other => match serde_yaml::to_string(other) {
+            Ok(txt) => txt.trim_start_matches("---").trim().to_string(),
+            Err(_) => String::new(),
+        },
+    }
+}
+
+// The row of a finished run in the summary
+fn get_record(run: usize, state: &State, swept_keys: &Vec<String>) -> Vec<String> {
+    // The effective values, so keys a run doesn't set show what it ran with
+    let effective = serde_yaml::to_value(state.config.to_config_file()).unwrap_or(Value::Null);
+    let mut record = vec![run.to_string()];
+    for key_path in swept_keys {
+        let mut value = &effective;
+        for key in key_path.split('.') {
+            value = value.get(key).unwrap_or(&Value::Null);
+        }
+        record.push(format_value(value));
+    }
+    match state.config.logic.seed {
+        Some(seed) => record.push(seed.to_string()),
+        None => record.push(String::new()),
+    }
+    let maybe_summary = RunSummary::from(
+        &state.people_box.get_avg_time_series(),
+        &state.pods_box.get_avg_time_series(),
+    );
+    match maybe_summary {
+        Some(summary) => record.extend(summary.to_record()),
+        None => {
+            println!("sweep run_{} gathered no metrics", run);
+            record.extend(SUMMARY_HEADER.iter().map(|_| String::new()));
+        }
+    }
+    record
+}
+
+// Runs every combination of the sweep headless, sweep.workers of them at the same time.
+// Each run writes to metrics/<environment>/<timestamp>/run_<n>, the summary.csv next to
+// them gets a row per run with the swept values, the seed and the key figures of the
+// notebooks. The command_on_start of the runs has to gather metrics, dump them and kill
+// the simulation.
+pub fn run_sweep(
+    sweep_config: &SweepConfig,
+    config_root: &str,
+    config_name: &str,
+    raw_config: &Value,
+    raw_overrides: &Value,
+) -> i32 {
+    let sweep_runs = match get_sweep_runs(sweep_config) {
+        Ok(sweep_runs) => sweep_runs,
+        Err(e) => {
+            println!("error in the sweep: {}", e);
+            return 1;
+        }
+    };
+    let swept_keys = get_swept_keys(&sweep_runs);
+    let timestamp_sweep: DateTime<Utc> = DateTime::from(SystemTime::now());
+    let mut sweep_dir: Option<String> = None;
+
+    // All configs are read before the first run starts, so a broken one stops the
+    // sweep right away and not when its turn comes
+    let mut configs = vec![];
+    for (i, sweep_run) in sweep_runs.iter().enumerate() {
+        let mut raw_run_overrides = raw_overrides.clone();
+        // The runs have no terminal, so they are always headless
+        set_key(
+            &mut raw_run_overrides,
+            "general.mode",
+            Value::String(String::from("headless")),
+        );
+        // Runs at the same time would mix their output
+        if sweep_config.workers != 1 {
+            set_key(&mut raw_run_overrides, "general.quiet", Value::Bool(true));
+        }
+        for (key_path, value) in &sweep_run.values {
+            set_key(&mut raw_run_overrides, key_path, value.clone());
+        }
+        if let Some(seed) = sweep_run.seed {
+            set_key(&mut raw_run_overrides, "logic.seed", Value::from(seed));
+        }
+
+        let mut config = parse_config(config_root, config_name, raw_config, &raw_run_overrides);
+        config.add_timestamp_run(timestamp_sweep);
+        let sweep_dir = sweep_dir
+            .get_or_insert_with(|| config.get_output_dir().unwrap())
+            .clone();
+        config.output_dir = Some(format!("{}/run_{}", sweep_dir, i));
+        configs.push(config);
+    }
+    let sweep_dir = match sweep_dir {
+        Some(sweep_dir) => sweep_dir,
+        None => {
+            println!("the sweep has no runs.");
+            return 1;
+        }
+    };
+
+    let pool = match ThreadPoolBuilder::new()
+        .num_threads(sweep_config.workers)
+        .build()
+    {
+        Ok(pool) => pool,
+        Err(e) => {
+            println!("error starting the workers: {}", e);
+            return 1;
+        }
+    };
+    println!(
+        "sweep: {} runs on {} workers, writing to {}",
+        configs.len(),
+        pool.current_num_threads(),
+        sweep_dir
+    );
+
+    let n_runs = configs.len();
+    let records: Mutex<Vec<Option<Vec<String>>>> = Mutex::new(vec![None; n_runs]);
+    let exit_codes: Vec<i32> = pool.install(|| {
+        configs
+            .into_par_iter()
+            .enumerate()
+            .map(|(i, config)| {
+                let described: Vec<String> = sweep_runs[i]
+                    .values
+                    .iter()
+                    .map(|(key_path, value)| format!("{}={}", key_path, format_value(value)))
+                    .collect();
+                println!(
+                    "sweep run_{} of {} started: {} seed {:?}",
+                    i,
+                    n_runs,
+                    described.join(" "),
+                    sweep_runs[i].seed
+                );
+
+                // Every run has its own action queue, output dir and generator
+                let rx = queue_command_on_start(&config);
+                let mut state = State::new(config, rx).add_people();
+                let code = state.run_headless();
+                if code != 0 {
+                    println!("sweep run_{} ended with code {}", i, code);
+                }
+                let record = get_record(i, &state, &swept_keys);
+
+                // Written after every run, so the finished runs are kept if the sweep is stopped
+                let mut records = records.lock().unwrap();
+                records[i] = Some(record);
+                write_summary(&sweep_dir, &swept_keys, &records);
+                println!("sweep run_{} of {} finished", i, n_runs);
+                code
+            })
+            .collect()
+    });
+
+    for code in exit_codes {
+        if code != 0 {
+            return code;
+        }
+    }
+    0
+}
+
+fn write_summary(sweep_dir: &str, swept_keys: &Vec<String>, records: &Vec<Option<Vec<String>>>) {
+    let mut header = vec![String::from("run")];
+    header.extend(swept_keys.clone());
+    header.push(String::from("seed"));
+    header.extend(SUMMARY_HEADER.iter().map(|column| column.to_string()));
+
+    let path_str = format!("{}/{}.csv", sweep_dir, "summary");
+    let _res = create_dir_all(sweep_dir);
+    let mut writer = match Writer::from_path(&path_str) {
+        Ok(writer) => writer,
+        Err(e) => {
+            println!("error opening file: {}", e);
+            return;
+        }
+    };
+    // Rows of runs that are still going are left out
+    for record in std::iter::once(&header).chain(records.iter().flatten()) {
+        if let Err(e) = writer.write_record(record) {
+            println!("error writing file: {}", e);
+            return;
+        }
+    }
+    match writer.flush() {
+        Ok(_) => {}
+        Err(e) => println!("error writing file: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_sweep_config(yaml: &str) -> SweepConfig {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    // The values of a run as key=value, then the seed
+    fn describe(sweep_run: &SweepRun) -> String {
+        let values: Vec<String> = sweep_run
+            .values
+            .iter()
+            .map(|(key_path, value)| format!("{}={}", key_path, format_value(value)))
+            .collect();
+        format!("{} {:?}", values.join(" "), sweep_run.seed)
+    }
+
+    #[test]
+    fn runs_times_grid_times_seeds() {
+        let sweep_config = get_sweep_config(
+            "
+grid:
+  logic.pod_capacity: [60, 90]
+  logic.transition_time: [30, 60]
+runs:
+  - general.town: a
+  - general.town: b
+seeds: [1, 2]
+",
+        );
+        let sweep_runs: Vec<String> = get_sweep_runs(&sweep_config)
+            .unwrap()
+            .iter()
+            .map(describe)
+            .collect();
+        assert_eq!(sweep_runs.len(), 2 * 4 * 2);
+        assert_eq!(
+            sweep_runs[..5],
+            [
+                "general.town=a logic.pod_capacity=60 logic.transition_time=30 Some(1)",
+                "general.town=a logic.pod_capacity=60 logic.transition_time=30 Some(2)",
+                "general.town=a logic.pod_capacity=60 logic.transition_time=60 Some(1)",
+                "general.town=a logic.pod_capacity=60 logic.transition_time=60 Some(2)",
+                "general.town=a logic.pod_capacity=90 logic.transition_time=30 Some(1)",
+            ]
+        );
+        assert_eq!(
+            sweep_runs[15],
+            "general.town=b logic.pod_capacity=90 logic.transition_time=60 Some(2)"
+        );
+    }
+
+    #[test]
+    fn without_runs_and_seeds() {
+        let sweep_config = get_sweep_config("grid:\n  logic.pod_capacity: [60, 90]\n");
+        let sweep_runs: Vec<String> = get_sweep_runs(&sweep_config)
+            .unwrap()
+            .iter()
+            .map(describe)
+            .collect();
+        assert_eq!(
+            sweep_runs,
+            ["logic.pod_capacity=60 None", "logic.pod_capacity=90 None"]
+        );
+        assert_eq!(get_sweep_runs(&SweepConfig::default()).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn grid_value_that_is_no_list() {
+        let sweep_config = get_sweep_config("grid:\n  logic.pod_capacity: 60\n");
+        assert_eq!(
+            get_sweep_runs(&sweep_config).unwrap_err(),
+            "sweep.grid.logic.pod_capacity needs a list of values"
+        );
+    }
+}